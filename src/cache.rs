@@ -0,0 +1,448 @@
+use crate::dependency_resolver::{Import, ParseIssue, ParsedImports};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// Bump when the cached data changes shape or meaning
+const CACHE_VERSION: u32 = 2;
+
+// Project-local cache directory, used instead of the user cache when present
+pub const LOCAL_CACHE_DIR: &str = ".pcc-cache";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    modified: u64,
+    size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedFile {
+    #[serde(flatten)]
+    stamp: FileStamp,
+    hash: u64,
+    imports: Vec<Import>,
+    errors: Vec<ParseIssue>,
+    resolutions: HashMap<String, PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheData {
+    version: u32,
+    config_files: HashMap<PathBuf, FileStamp>,
+    files: HashMap<PathBuf, CachedFile>,
+}
+
+/// On-disk cache of the imports extracted from each file, so unchanged files
+/// are not parsed again, and optionally of the paths they resolved to.
+///
+/// Resolutions are only reused when enabled with `with_resolutions`: a file
+/// added where an import would now resolve, such as `src/foo.ts` next to a
+/// cached `src/foo/index.ts`, or a new tsconfig or package.json, is not
+/// noticed. Only successful resolutions are stored, and every resolution is
+/// dropped when a tsconfig or package.json consulted by the previous run has
+/// changed.
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    enabled: bool,
+    /// Whether resolutions are reused
+    resolutions: bool,
+    /// Where the cache is saved; `None` keeps it in memory only
+    path: Option<PathBuf>,
+    data: CacheData,
+    dirty: bool,
+}
+
+impl ParseCache {
+    /// A cache that never hits and is never written.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// A cache that lives as long as the process, for long-running modes
+    /// that should not touch the disk.
+    pub fn in_memory() -> Self {
+        Self {
+            enabled: true,
+            ..Self::default()
+        }
+    }
+
+    /// Also reuses where imports resolved in earlier runs.
+    pub fn with_resolutions(mut self, enabled: bool) -> Self {
+        self.resolutions = enabled;
+        self
+    }
+
+    /// Opens the cache for `project_root`, stored in `cache_dir` when given,
+    /// otherwise in `<project_root>/.pcc-cache/` if that directory exists, or
+    /// in the user cache directory (`$XDG_CACHE_HOME/pcc`).
+    pub fn open(project_root: &Path, cache_dir: Option<&Path>) -> Self {
+        let Some(path) = cache_file_path(project_root, cache_dir) else {
+            return Self::disabled();
+        };
+
+        let data = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CacheData>(&content).ok())
+            .filter(|data| data.version == CACHE_VERSION)
+            .unwrap_or_else(|| CacheData {
+                version: CACHE_VERSION,
+                ..CacheData::default()
+            });
+
+        let mut cache = Self {
+            enabled: true,
+            resolutions: false,
+            path: Some(path),
+            data,
+            dirty: false,
+        };
+
+        let configs_changed = cache
+            .data
+            .config_files
+            .iter()
+            .any(|(config, stamp)| file_stamp(config).ok() != Some(*stamp));
+        if configs_changed {
+            for file in cache.data.files.values_mut() {
+                file.resolutions.clear();
+            }
+            cache.data.config_files.clear();
+            cache.dirty = true;
+        }
+
+        cache
+    }
+
+    /// Returns the imports stored for `file` if its content is unchanged.
+    ///
+    /// A file whose timestamp changed but whose content hash still matches
+    /// (e.g. after switching branches back and forth) is still a hit.
+    pub fn cached_imports(&mut self, file: &Path) -> Option<ParsedImports> {
+        if !self.enabled {
+            return None;
+        }
+        let entry = self.data.files.get_mut(file)?;
+        let stamp = file_stamp(file).ok()?;
+
+        if entry.stamp != stamp {
+            if entry.stamp.size != stamp.size {
+                return None;
+            }
+            let content = fs::read(file).ok()?;
+            if content_hash(&content) != entry.hash {
+                return None;
+            }
+            entry.stamp = stamp;
+            self.dirty = true;
+        }
+
+        Some(ParsedImports {
+            imports: entry.imports.clone(),
+            errors: entry.errors.clone(),
+        })
+    }
+
+    pub fn store_imports(&mut self, file: &Path, content: &str, parsed: &ParsedImports) {
+        if !self.enabled {
+            return;
+        }
+        let Ok(stamp) = file_stamp(file) else {
+            return;
+        };
+
+        // Resolutions depend on where the file is, not on what it contains
+        let resolutions = self
+            .data
+            .files
+            .remove(file)
+            .map(|entry| entry.resolutions)
+            .unwrap_or_default();
+
+        self.data.files.insert(
+            file.to_path_buf(),
+            CachedFile {
+                stamp,
+                hash: content_hash(content.as_bytes()),
+                imports: parsed.imports.clone(),
+                errors: parsed.errors.clone(),
+                resolutions,
+            },
+        );
+        self.dirty = true;
+    }
+
+    /// Returns where `specifier` imported from `file` resolved last time, as
+    /// long as that file still exists and resolutions are reused.
+    pub fn resolution(&self, file: &Path, specifier: &str) -> Option<PathBuf> {
+        if !self.resolutions {
+            return None;
+        }
+        self.data
+            .files
+            .get(file)?
+            .resolutions
+            .get(specifier)
+            .filter(|resolved| resolved.is_file())
+            .cloned()
+    }
+
+    pub fn store_resolution(&mut self, file: &Path, specifier: &str, resolved: &Path) {
+        if !self.resolutions {
+            return;
+        }
+        if let Some(entry) = self.data.files.get_mut(file) {
+            entry
+                .resolutions
+                .insert(specifier.to_string(), resolved.to_path_buf());
+            self.dirty = true;
+        }
+    }
+
+    /// Records a config file that resolutions depend on.
+    pub fn track_config_file(&mut self, config: &Path) {
+        if !self.enabled || self.data.config_files.contains_key(config) {
+            return;
+        }
+        if let Ok(stamp) = file_stamp(config) {
+            self.data.config_files.insert(config.to_path_buf(), stamp);
+            self.dirty = true;
+        }
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string(&self.data)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        // Write then rename so an interrupted run never leaves a truncated
+        // cache, to a file of its own so concurrent runs do not mix theirs
+        let temp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        fs::write(&temp_path, content)?;
+        fs::rename(&temp_path, path)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+fn cache_file_path(project_root: &Path, cache_dir: Option<&Path>) -> Option<PathBuf> {
+    let project_root = fs::canonicalize(project_root).ok()?;
+    let file_name = format!(
+        "{:016x}.json",
+        content_hash(project_root.to_string_lossy().as_bytes())
+    );
+
+    if let Some(cache_dir) = cache_dir {
+        return Some(cache_dir.join(file_name));
+    }
+
+    let local_dir = project_root.join(LOCAL_CACHE_DIR);
+    if local_dir.is_dir() {
+        return Some(local_dir.join("cache.json"));
+    }
+
+    let user_cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(user_cache_dir.join("pcc").join(file_name))
+}
+
+fn file_stamp(path: &Path) -> io::Result<FileStamp> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0);
+
+    Ok(FileStamp {
+        modified,
+        size: metadata.len(),
+    })
+}
+
+// FNV-1a; stable across Rust versions, unlike `DefaultHasher`
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dependency_resolver::ImportKind;
+    use std::time::{Duration, SystemTime};
+
+    fn parsed(specifier: &str) -> ParsedImports {
+        ParsedImports {
+            imports: vec![Import {
+                specifier: specifier.to_string(),
+                kind: ImportKind::Runtime,
+            }],
+            errors: Vec::new(),
+        }
+    }
+
+    fn specifiers(cache: &mut ParseCache, file: &Path) -> Option<Vec<String>> {
+        let parsed = cache.cached_imports(file)?;
+        Some(
+            parsed
+                .imports
+                .into_iter()
+                .map(|import| import.specifier)
+                .collect(),
+        )
+    }
+
+    // Moves the modification time so the stamp no longer matches
+    fn touch(file: &Path, seconds: u64) {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000 + seconds);
+        fs::File::options()
+            .write(true)
+            .open(file)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    fn store(cache: &mut ParseCache, file: &Path, content: &str, specifier: &str) {
+        fs::write(file, content).unwrap();
+        cache.store_imports(file, content, &parsed(specifier));
+    }
+
+    #[test]
+    fn unchanged_files_are_hits() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.ts");
+        let mut cache = ParseCache::in_memory();
+        store(&mut cache, &file, "import './b';", "./b");
+
+        assert_eq!(specifiers(&mut cache, &file), Some(vec!["./b".to_string()]));
+        assert_eq!(specifiers(&mut cache, &dir.path().join("c.ts")), None);
+        assert!(ParseCache::disabled().cached_imports(&file).is_none());
+    }
+
+    #[test]
+    fn touched_files_with_the_same_content_are_hits() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.ts");
+        let mut cache = ParseCache::in_memory();
+        store(&mut cache, &file, "import './b';", "./b");
+
+        touch(&file, 1);
+        assert_eq!(specifiers(&mut cache, &file), Some(vec!["./b".to_string()]));
+        assert_eq!(cache.data.files[&file].stamp, file_stamp(&file).unwrap());
+    }
+
+    #[test]
+    fn changed_content_is_a_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.ts");
+        let mut cache = ParseCache::in_memory();
+        store(&mut cache, &file, "import './b';", "./b");
+
+        // Same size, so only the hash tells the difference
+        fs::write(&file, "import './c';").unwrap();
+        touch(&file, 2);
+        assert_eq!(specifiers(&mut cache, &file), None);
+
+        fs::write(&file, "import './longer';").unwrap();
+        assert_eq!(specifiers(&mut cache, &file), None);
+    }
+
+    #[test]
+    fn resolutions_are_dropped_when_a_config_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        let cache_dir = dir.path().join("cache");
+        fs::create_dir(&root).unwrap();
+        let file = root.join("a.ts");
+        let resolved = root.join("b.ts");
+        let tsconfig = root.join("tsconfig.json");
+        fs::write(&resolved, "").unwrap();
+        fs::write(&tsconfig, "{}").unwrap();
+
+        let mut cache = ParseCache::open(&root, Some(&cache_dir)).with_resolutions(true);
+        store(&mut cache, &file, "import './b';", "./b");
+        cache.store_resolution(&file, "./b", &resolved);
+        cache.track_config_file(&tsconfig);
+        cache.save().unwrap();
+
+        let mut cache = ParseCache::open(&root, Some(&cache_dir)).with_resolutions(true);
+        assert_eq!(cache.resolution(&file, "./b"), Some(resolved.clone()));
+        // Not reused unless asked for
+        let unused = ParseCache::open(&root, Some(&cache_dir));
+        assert_eq!(unused.resolution(&file, "./b"), None);
+
+        fs::write(&tsconfig, r#"{ "compilerOptions": {} }"#).unwrap();
+        cache.save().unwrap();
+        let mut cache = ParseCache::open(&root, Some(&cache_dir)).with_resolutions(true);
+        assert_eq!(cache.resolution(&file, "./b"), None);
+        // The imports themselves do not depend on the config
+        assert_eq!(specifiers(&mut cache, &file), Some(vec!["./b".to_string()]));
+    }
+
+    #[test]
+    fn caches_of_another_version_are_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.ts");
+        let cache_dir = dir.path().join("cache");
+        let mut cache = ParseCache::open(dir.path(), Some(&cache_dir));
+        store(&mut cache, &file, "import './b';", "./b");
+        cache.save().unwrap();
+
+        let path = cache.path.clone().unwrap();
+        let mut data: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(data["version"], CACHE_VERSION);
+        data["version"] = (CACHE_VERSION - 1).into();
+        fs::write(&path, data.to_string()).unwrap();
+
+        let mut cache = ParseCache::open(dir.path(), Some(&cache_dir));
+        assert_eq!(cache.data.version, CACHE_VERSION);
+        assert_eq!(specifiers(&mut cache, &file), None);
+    }
+
+    #[test]
+    fn saving_replaces_the_cache_file_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.ts");
+        let cache_dir = dir.path().join("cache");
+        let mut cache = ParseCache::open(dir.path(), Some(&cache_dir));
+
+        // Nothing to save yet
+        cache.save().unwrap();
+        assert!(!cache_dir.exists());
+
+        store(&mut cache, &file, "import './b';", "./b");
+        cache.save().unwrap();
+        let entries: Vec<_> = fs::read_dir(&cache_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(entries, [cache.path.clone().unwrap()]);
+        assert!(!cache.dirty);
+
+        let mut reopened = ParseCache::open(dir.path(), Some(&cache_dir));
+        assert_eq!(
+            specifiers(&mut reopened, &file),
+            Some(vec!["./b".to_string()])
+        );
+    }
+}
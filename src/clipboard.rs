@@ -0,0 +1,349 @@
+use crate::error::{Error, Result};
+use ::clipboard::{ClipboardContext, ClipboardProvider};
+use std::env;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+// The most base64 text copied with OSC 52; terminals drop or cut longer
+// sequences without telling, so larger outputs go to the next clipboard
+const OSC52_LIMIT: usize = 100_000;
+
+/// A way of placing text on the clipboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardBackend {
+    /// The X11 selection on Linux, or the macOS and Windows clipboard
+    X11,
+    /// `wl-copy`, from wl-clipboard
+    Wayland,
+    /// An OSC 52 escape sequence written to the terminal, which copies to the
+    /// clipboard of the terminal emulator, also over SSH and inside tmux
+    Osc52,
+    /// A shell command reading the text from its standard input, e.g.
+    /// `xclip -selection clipboard`
+    Command(String),
+}
+
+/// Chooses how text is copied: a configured backend, or else the first of
+/// those available in the environment that works.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Clipboard {
+    backend: Option<ClipboardBackend>,
+    command: Option<String>,
+}
+
+impl ClipboardBackend {
+    pub fn name(&self) -> &str {
+        match self {
+            ClipboardBackend::X11 => "x11",
+            ClipboardBackend::Wayland => "wayland",
+            ClipboardBackend::Osc52 => "osc52",
+            ClipboardBackend::Command(command) => command,
+        }
+    }
+
+    fn copy(&self, text: &str) -> io::Result<()> {
+        match self {
+            ClipboardBackend::X11 => {
+                let mut ctx: ClipboardContext =
+                    ClipboardProvider::new().map_err(|err| io::Error::other(err.to_string()))?;
+                ctx.set_contents(text.to_string())
+                    .map_err(|err| io::Error::other(err.to_string()))
+            }
+            ClipboardBackend::Wayland => pipe_to(Command::new("wl-copy"), text),
+            ClipboardBackend::Osc52 => write_osc52(text),
+            ClipboardBackend::Command(command) => pipe_to(shell(command), text),
+        }
+    }
+}
+
+impl fmt::Display for ClipboardBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClipboardBackend::Command(command) => write!(f, "`{}`", command),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+/// Parses `x11`, `wayland` or `osc52`.
+impl FromStr for ClipboardBackend {
+    type Err = String;
+
+    fn from_str(backend: &str) -> std::result::Result<Self, Self::Err> {
+        match backend {
+            "x11" => Ok(ClipboardBackend::X11),
+            "wayland" => Ok(ClipboardBackend::Wayland),
+            "osc52" => Ok(ClipboardBackend::Osc52),
+            _ => Err(format!(
+                "unknown clipboard `{}`; expected `auto`, `x11`, `wayland`, `osc52` or `command`",
+                backend
+            )),
+        }
+    }
+}
+
+impl Clipboard {
+    /// Picks the backend from the environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Always copies with `backend`.
+    pub fn backend(mut self, backend: ClipboardBackend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Tries `command` when no display clipboard works, before OSC 52.
+    pub fn command(mut self, command: impl Into<String>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    /// The backends tried in turn: Wayland and X11 where the environment
+    /// offers them, then the command, then OSC 52 when there is a terminal.
+    pub fn candidates(&self) -> Vec<ClipboardBackend> {
+        let is_set = |name: &str| env::var_os(name).is_some_and(|value| !value.is_empty());
+        self.candidates_in(is_set, has_terminal())
+    }
+
+    fn candidates_in(
+        &self,
+        is_set: impl Fn(&str) -> bool,
+        terminal: bool,
+    ) -> Vec<ClipboardBackend> {
+        if let Some(backend) = &self.backend {
+            return vec![backend.clone()];
+        }
+
+        let mut candidates = Vec::new();
+        if is_set("WAYLAND_DISPLAY") {
+            candidates.push(ClipboardBackend::Wayland);
+        }
+        if is_set("DISPLAY") || cfg!(any(target_os = "macos", windows)) {
+            candidates.push(ClipboardBackend::X11);
+        }
+        if let Some(command) = &self.command {
+            candidates.push(ClipboardBackend::Command(command.clone()));
+        }
+        // Last, since writing the sequence succeeds whether or not the
+        // terminal copies anything
+        if terminal {
+            candidates.push(ClipboardBackend::Osc52);
+        }
+        candidates
+    }
+
+    /// Copies `text` with the first backend that works, and returns it.
+    pub fn copy(&self, text: &str) -> Result<ClipboardBackend> {
+        let candidates = self.candidates();
+        if candidates.is_empty() {
+            return Err(Error::Clipboard(
+                "no clipboard found: there is no Wayland or X11 display and no terminal; \
+                 set `clipboard_command` in the configuration file"
+                    .to_string(),
+            ));
+        }
+
+        let mut failures = Vec::new();
+        for backend in candidates {
+            match backend.copy(text) {
+                Ok(()) => return Ok(backend),
+                Err(err) => failures.push(format!("{}: {}", backend, err)),
+            }
+        }
+        Err(Error::Clipboard(failures.join("; ")))
+    }
+}
+
+/// Replaces the contents of the clipboard with `combined_code`, using the
+/// first backend the environment offers that works.
+pub fn copy_to_clipboard(combined_code: String) -> Result<()> {
+    Clipboard::new().copy(&combined_code).map(|_| ())
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+fn pipe_to(mut command: Command, text: &str) -> io::Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                err.kind(),
+                format!("{} not found", command.get_program().to_string_lossy()),
+            ),
+            _ => err,
+        })?;
+    // The child may exit without reading everything, which `wait` reports
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(io::Error::other(match stderr.trim() {
+        "" => format!("exited with {}", output.status),
+        message => message.to_string(),
+    }))
+}
+
+fn has_terminal() -> bool {
+    open_terminal().is_ok() || io::stdout().is_terminal()
+}
+
+// The controlling terminal, which still reaches the terminal emulator when
+// the standard streams are redirected
+fn open_terminal() -> io::Result<std::fs::File> {
+    OpenOptions::new().write(true).open("/dev/tty")
+}
+
+fn write_osc52(text: &str) -> io::Result<()> {
+    let sequence = osc52_sequence(text, env::var_os("TMUX").is_some())?;
+    match open_terminal() {
+        Ok(mut terminal) => terminal.write_all(sequence.as_bytes()),
+        Err(_) if io::stdout().is_terminal() => io::stdout().write_all(sequence.as_bytes()),
+        Err(err) => Err(err),
+    }
+}
+
+fn osc52_sequence(text: &str, in_tmux: bool) -> io::Result<String> {
+    let encoded = base64(text.as_bytes());
+    if encoded.len() > OSC52_LIMIT {
+        return Err(io::Error::other(format!(
+            "{} bytes is more than terminals accept over OSC 52",
+            text.len()
+        )));
+    }
+
+    let sequence = format!("\x1b]52;c;{}\x07", encoded);
+    // tmux only passes sequences it does not handle itself through when they
+    // are wrapped, with every escape doubled
+    if in_tmux {
+        return Ok(format!(
+            "\x1bPtmux;{}\x1b\\",
+            sequence.replace('\x1b', "\x1b\x1b")
+        ));
+    }
+    Ok(sequence)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |triple, (index, byte)| {
+                triple | (*byte as u32) << (16 - 8 * index)
+            });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(triple >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads_the_last_group() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0xfe, 0x00]), "//4A");
+    }
+
+    #[test]
+    fn osc52_sequences_are_wrapped_for_tmux() {
+        assert_eq!(osc52_sequence("hi", false).unwrap(), "\x1b]52;c;aGk=\x07");
+        assert_eq!(
+            osc52_sequence("hi", true).unwrap(),
+            "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\"
+        );
+    }
+
+    #[test]
+    fn large_outputs_are_not_copied_with_osc52() {
+        // Three bytes encode to four characters
+        let limit = OSC52_LIMIT / 4 * 3;
+        assert!(osc52_sequence(&"a".repeat(limit), false).is_ok());
+        assert!(osc52_sequence(&"a".repeat(limit + 1), false).is_err());
+
+        let clipboard = Clipboard::new().backend(ClipboardBackend::Osc52);
+        assert!(matches!(
+            clipboard.copy(&"a".repeat(limit + 1)),
+            Err(Error::Clipboard(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_backends_are_rejected() {
+        assert_eq!("osc52".parse(), Ok(ClipboardBackend::Osc52));
+        let err = "xsel".parse::<ClipboardBackend>().unwrap_err();
+        assert!(err.contains("unknown clipboard `xsel`"), "{}", err);
+        assert!("X11".parse::<ClipboardBackend>().is_err());
+    }
+
+    #[test]
+    fn candidates_follow_the_environment() {
+        let clipboard = Clipboard::new().command("xclip");
+        let wayland = |name: &str| name == "WAYLAND_DISPLAY";
+        let both = |name: &str| name == "WAYLAND_DISPLAY" || name == "DISPLAY";
+        let command = ClipboardBackend::Command("xclip".to_string());
+
+        assert_eq!(
+            clipboard.candidates_in(both, true),
+            [
+                ClipboardBackend::Wayland,
+                ClipboardBackend::X11,
+                command.clone(),
+                ClipboardBackend::Osc52
+            ]
+        );
+        if cfg!(not(any(target_os = "macos", windows))) {
+            assert_eq!(
+                clipboard.candidates_in(wayland, false),
+                [ClipboardBackend::Wayland, command.clone()]
+            );
+            assert_eq!(Clipboard::new().candidates_in(|_| false, false), []);
+        }
+    }
+
+    #[test]
+    fn a_chosen_backend_is_the_only_candidate() {
+        let clipboard = Clipboard::new()
+            .command("xclip")
+            .backend(ClipboardBackend::X11);
+        assert_eq!(
+            clipboard.candidates_in(|_| true, true),
+            [ClipboardBackend::X11]
+        );
+    }
+}
@@ -0,0 +1,1420 @@
+use crate::cache::ParseCache;
+use crate::clipboard::Clipboard;
+use crate::dependency_resolver::{
+    canonicalize_path, DependencyResolver, ImportKind, ParseIssue, UnresolvedImport,
+};
+use crate::error::{Error, Result};
+use crate::external_types::{self, ExternalTypes};
+use crate::ignore_patterns::{
+    convert_ignore_patterns, invalid_ignore_pattern, is_ignored, matching_pattern,
+};
+use crate::listing::{SkipReason, SkippedFile};
+use crate::project::{CombinedProject, Dependency, Importer, LineRange, RenderOptions, SourceFile};
+use crate::selection::{excerpt, parse_symbol, parse_target, unsupported_file, Selection};
+use crate::split::{part_path, SplitLimit};
+use crate::symbols::{self, Definition, SymbolIndex};
+use crate::tsconfig::normalize_path;
+use crate::typescript_resolver::TypeScriptResolver;
+use ignore::Walk;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How a combined project is rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// The `<project>` XML document written by the `pcc` command
+    #[default]
+    Xml,
+    /// The `CombinedProject` model serialized as JSON
+    Json,
+}
+
+/// Where `Combiner::run` delivers the rendered project.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Sink {
+    /// Only return the project
+    #[default]
+    None,
+    Stdout,
+    File(PathBuf),
+    Clipboard,
+}
+
+/// What happens to a file that cannot be read, e.g. because it was deleted
+/// during the run, is not readable or is not valid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorPolicy {
+    /// Stop and return the error
+    #[default]
+    Abort,
+    /// Leave the file out, with a warning
+    Skip,
+    /// Put an `<error>` element in place of the file, with a warning
+    Embed,
+}
+
+/// How the paths of files are shown in the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PathStyle {
+    /// Relative to the working directory
+    #[default]
+    Relative,
+    /// Absolute, with symbolic links resolved
+    Absolute,
+    /// Relative to the project root, wherever in the project pcc runs
+    RootRelative,
+}
+
+/// The sections of the output that an error policy can be set for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileSection {
+    Targets,
+    References,
+    /// Files found under the combined paths
+    Files,
+    Dependencies,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> std::result::Result<Self, Self::Err> {
+        match policy {
+            "abort" => Ok(ErrorPolicy::Abort),
+            "skip" => Ok(ErrorPolicy::Skip),
+            "embed" => Ok(ErrorPolicy::Embed),
+            _ => Err(format!(
+                "unknown error policy `{}`; expected `abort`, `skip` or `embed`",
+                policy
+            )),
+        }
+    }
+}
+
+impl FromStr for PathStyle {
+    type Err = String;
+
+    fn from_str(style: &str) -> std::result::Result<Self, Self::Err> {
+        match style {
+            "relative" => Ok(PathStyle::Relative),
+            "absolute" => Ok(PathStyle::Absolute),
+            "root-relative" => Ok(PathStyle::RootRelative),
+            _ => Err(format!(
+                "unknown path style `{}`; expected `relative`, `absolute` or `root-relative`",
+                style
+            )),
+        }
+    }
+}
+
+impl FromStr for FileSection {
+    type Err = String;
+
+    fn from_str(section: &str) -> std::result::Result<Self, Self::Err> {
+        match section {
+            "targets" => Ok(FileSection::Targets),
+            "references" => Ok(FileSection::References),
+            "files" => Ok(FileSection::Files),
+            "dependencies" => Ok(FileSection::Dependencies),
+            _ => Err(format!(
+                "unknown section `{}`; expected `targets`, `references`, `files` or `dependencies`",
+                section
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ProcessingOptions {
+    pub paths: Vec<PathBuf>,
+    pub ignore_patterns: String,
+    pub path_style: PathStyle,
+    /// The directory root-relative paths start from; found when building
+    /// unless given
+    pub root: Option<PathBuf>,
+    /// Prepended to every relative path shown
+    pub path_prefix: Option<PathBuf>,
+    pub deps: bool,
+    pub skip_type_imports: bool,
+    pub skip_dynamic_imports: bool,
+    pub include_assets: bool,
+    /// Include the type declarations of imported packages
+    pub external_types: bool,
+    pub cache: bool,
+    /// Reuse cached resolutions, which miss files added since
+    pub cache_resolutions: bool,
+    pub cache_dir: Option<PathBuf>,
+    pub target_files: Vec<PathBuf>,
+    pub reference_files: Vec<PathBuf>,
+    /// Parts of files requested with `file:10-20` or `file#fn:name`
+    pub selections: HashMap<PathBuf, Selection>,
+    /// Definitions requested with `--symbol`, as a file and a path of names
+    pub symbols: Vec<(PathBuf, Vec<String>)>,
+    /// How many levels of references to follow from the symbols
+    pub symbol_depth: usize,
+    /// What happens to unreadable files in sections without a policy of
+    /// their own
+    pub on_error: ErrorPolicy,
+    pub section_on_error: HashMap<FileSection, ErrorPolicy>,
+    /// Files found in directories, dependencies and package declarations
+    /// outside this directory are left out, for servers bound to a project
+    /// root
+    pub confine_to: Option<PathBuf>,
+}
+
+impl ProcessingOptions {
+    /// Whether a canonical path is under `confine_to`, when set.
+    pub(crate) fn is_within_root(&self, file_path: &Path) -> bool {
+        self.confine_to
+            .as_ref()
+            .is_none_or(|root| file_path.starts_with(root))
+    }
+
+    fn error_policy(&self, section: FileSection) -> ErrorPolicy {
+        self.section_on_error
+            .get(&section)
+            .copied()
+            .unwrap_or(self.on_error)
+    }
+}
+
+/// Dependency and module resolvers, kept between runs by long-lived callers
+/// so that tsconfig, workspace and resolution caches stay warm.
+pub(crate) struct Resolvers {
+    pub deps: DependencyResolver,
+    pub ts: TypeScriptResolver,
+}
+
+impl Resolvers {
+    pub fn new(options: &ProcessingOptions) -> io::Result<Self> {
+        let mut resolvers = Self {
+            deps: create_dependency_resolver(options)?,
+            ts: TypeScriptResolver::new(),
+        };
+        resolvers.configure(options);
+        Ok(resolvers)
+    }
+
+    /// Applies the per-run import filters of `options`.
+    pub fn configure(&mut self, options: &ProcessingOptions) {
+        self.deps
+            .set_skipped_imports(options.skip_type_imports, options.skip_dynamic_imports);
+        self.deps.set_include_assets(options.include_assets);
+    }
+
+    /// Forgets what the module resolver knows of the filesystem, before a run
+    /// that may see files added or removed since the last one.
+    pub fn forget_files(&self) {
+        self.ts.clear_cache();
+    }
+
+    pub fn save_cache(&mut self) -> Option<String> {
+        self.deps
+            .save_cache()
+            .err()
+            .map(|err| format!("failed to write the dependency cache: {}", err))
+    }
+}
+
+/// A combined project along with what was read and left out to build it.
+pub(crate) struct Assembly {
+    pub project: CombinedProject,
+    /// Every file read, as canonical paths
+    pub sources: Vec<PathBuf>,
+    pub skipped: Vec<SkippedFile>,
+    /// Spent resolving dependencies and following symbol references
+    pub resolve_time: Duration,
+}
+
+/// Configures a `Combiner`. Defaults match the `pcc` command: relative
+/// paths, no dependency resolution, the on-disk cache and XML output.
+#[derive(Debug, Clone)]
+pub struct CombinerBuilder {
+    options: ProcessingOptions,
+    ignore_patterns: Vec<String>,
+    symbols: Vec<String>,
+    format: Format,
+    render_options: RenderOptions,
+    sink: Sink,
+    clipboard: Clipboard,
+    split: Option<SplitLimit>,
+}
+
+/// Combines source files, and optionally everything they import, into a
+/// single document.
+#[derive(Debug, Clone)]
+pub struct Combiner {
+    options: ProcessingOptions,
+    format: Format,
+    render_options: RenderOptions,
+    sink: Sink,
+    clipboard: Clipboard,
+    split: Option<SplitLimit>,
+}
+
+impl CombinerBuilder {
+    fn new() -> Self {
+        Self {
+            options: ProcessingOptions {
+                paths: Vec::new(),
+                ignore_patterns: String::new(),
+                path_style: PathStyle::default(),
+                root: None,
+                path_prefix: None,
+                deps: false,
+                skip_type_imports: false,
+                skip_dynamic_imports: false,
+                include_assets: false,
+                external_types: false,
+                cache: true,
+                cache_resolutions: false,
+                cache_dir: None,
+                target_files: Vec::new(),
+                reference_files: Vec::new(),
+                selections: HashMap::new(),
+                symbols: Vec::new(),
+                symbol_depth: 0,
+                on_error: ErrorPolicy::default(),
+                section_on_error: HashMap::new(),
+                confine_to: None,
+            },
+            ignore_patterns: Vec::new(),
+            symbols: Vec::new(),
+            format: Format::default(),
+            render_options: RenderOptions::default(),
+            sink: Sink::default(),
+            clipboard: Clipboard::default(),
+            split: None,
+        }
+    }
+
+    /// Adds a file or directory to combine. Like targets and references,
+    /// a file can be narrowed to a range of lines with `src/main.ts:120-220`
+    /// or to a definition with `src/main.ts#fn:processFiles`.
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.paths.push(path.into());
+        self
+    }
+
+    pub fn paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.options.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds a file to the `<targets>` section.
+    pub fn target(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.target_files.push(path.into());
+        self
+    }
+
+    /// Adds a file to the `<references>` section.
+    pub fn reference(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.reference_files.push(path.into());
+        self
+    }
+
+    /// Adds a single definition, named like `src/main.ts::processFiles` or
+    /// `src/main.ts::Combiner::run`, to the files.
+    pub fn symbol(mut self, symbol: impl Into<String>) -> Self {
+        self.symbols.push(symbol.into());
+        self
+    }
+
+    /// Also adds the definitions the symbols reference, and those they
+    /// reference, up to `depth` levels, to the dependencies.
+    pub fn symbol_depth(mut self, depth: usize) -> Self {
+        self.options.symbol_depth = depth;
+        self
+    }
+
+    /// Skips files matching a pattern in .gitignore format.
+    pub fn ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore_patterns.push(pattern.into());
+        self
+    }
+
+    /// Shows paths relative to the working directory, or else absolute.
+    pub fn relative_paths(mut self, enabled: bool) -> Self {
+        self.options.path_style = if enabled {
+            PathStyle::Relative
+        } else {
+            PathStyle::Absolute
+        };
+        self
+    }
+
+    pub fn path_style(mut self, style: PathStyle) -> Self {
+        self.options.path_style = style;
+        self
+    }
+
+    /// The directory `PathStyle::RootRelative` paths start from. Defaults to
+    /// the nearest directory containing `.git`, from the working directory
+    /// up, or else the working directory.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.options.root = Some(root.into());
+        self
+    }
+
+    /// Prepends `prefix` to every relative path, e.g. `frontend/` to tell
+    /// apart the files of several repositories combined into one document.
+    /// Files outside the working directory or root keep absolute paths.
+    pub fn path_prefix(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.options.path_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Follows the imports of script files and adds what they reach to the
+    /// `<dependencies>` section.
+    pub fn deps(mut self, enabled: bool) -> Self {
+        self.options.deps = enabled;
+        self
+    }
+
+    pub fn skip_type_imports(mut self, enabled: bool) -> Self {
+        self.options.skip_type_imports = enabled;
+        self
+    }
+
+    pub fn skip_dynamic_imports(mut self, enabled: bool) -> Self {
+        self.options.skip_dynamic_imports = enabled;
+        self
+    }
+
+    /// Includes imported stylesheets and assets among the dependencies.
+    pub fn include_assets(mut self, enabled: bool) -> Self {
+        self.options.include_assets = enabled;
+        self
+    }
+
+    /// Includes the type declarations of the packages imported by the
+    /// dependencies, in the `<external>` section.
+    pub fn external_types(mut self, enabled: bool) -> Self {
+        self.options.external_types = enabled;
+        self
+    }
+
+    /// Sets what happens to files that cannot be read, in every section
+    /// without a policy of its own. By default the run is aborted.
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.options.on_error = policy;
+        self
+    }
+
+    /// Sets what happens to files of `section` that cannot be read.
+    pub fn section_on_error(mut self, section: FileSection, policy: ErrorPolicy) -> Self {
+        self.options.section_on_error.insert(section, policy);
+        self
+    }
+
+    /// Leaves out dependencies outside `root`, which must be canonical.
+    pub(crate) fn confine_to(mut self, root: PathBuf) -> Self {
+        self.options.confine_to = Some(root);
+        self
+    }
+
+    pub fn cache(mut self, enabled: bool) -> Self {
+        self.options.cache = enabled;
+        self
+    }
+
+    /// Also reuses where imports resolved in earlier runs, which saves the
+    /// resolution of unchanged files but does not notice files added where
+    /// an import would now resolve. Off by default.
+    pub fn cache_resolutions(mut self, enabled: bool) -> Self {
+        self.options.cache_resolutions = enabled;
+        self
+    }
+
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.cache_dir = Some(dir.into());
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Prefixes every line of XML output with its line number in the file.
+    pub fn line_numbers(mut self, enabled: bool) -> Self {
+        self.render_options.line_numbers = enabled;
+        self
+    }
+
+    pub fn sink(mut self, sink: Sink) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Sets how `Sink::Clipboard` copies the output. By default the
+    /// clipboard is picked from the environment.
+    pub fn clipboard(mut self, clipboard: Clipboard) -> Self {
+        self.clipboard = clipboard;
+        self
+    }
+
+    /// Renders the project in parts of at most `limit` each when it does not
+    /// fit in one. Only XML output can be split, and not to the clipboard,
+    /// which holds one part at a time: copy each of `render_parts` instead.
+    pub fn split(mut self, limit: SplitLimit) -> Self {
+        self.split = Some(limit);
+        self
+    }
+
+    pub fn build(mut self) -> Result<Combiner> {
+        if self.options.paths.is_empty()
+            && self.options.target_files.is_empty()
+            && self.options.reference_files.is_empty()
+            && self.symbols.is_empty()
+        {
+            return Err(Error::Config(
+                "no paths, targets, references or symbols to combine".to_string(),
+            ));
+        }
+        if self.split.is_some() {
+            if self.format != Format::Xml {
+                return Err(Error::Config("only XML output can be split".to_string()));
+            }
+            if self.sink == Sink::Clipboard {
+                return Err(Error::Config(
+                    "split output cannot be copied to the clipboard at once".to_string(),
+                ));
+            }
+        }
+
+        for paths in [
+            &mut self.options.paths,
+            &mut self.options.target_files,
+            &mut self.options.reference_files,
+        ] {
+            for path in paths.iter_mut() {
+                let (file, selection) = parse_target(path)?;
+                // One part is shown per file, so a second one would be lost
+                if let Some(selection) = selection {
+                    if self.options.selections.contains_key(&file) {
+                        return Err(Error::Config(format!(
+                            "{} is selected more than once; select one range or symbol per file",
+                            file.display()
+                        )));
+                    }
+                    self.options.selections.insert(file.clone(), selection);
+                }
+                *path = file;
+            }
+        }
+
+        self.options.symbols = self
+            .symbols
+            .iter()
+            .map(|symbol| parse_symbol(symbol))
+            .collect::<Result<_>>()?;
+
+        if self.options.path_style == PathStyle::RootRelative {
+            let root = match &self.options.root {
+                Some(root) => fs::canonicalize(root).map_err(|err| Error::io(root, err))?,
+                None => project_root(&canonicalize_path(&env::current_dir()?)),
+            };
+            self.options.root = Some(root);
+        }
+
+        self.options.ignore_patterns = convert_ignore_patterns(&self.ignore_patterns);
+        if let Some((pattern, err)) = invalid_ignore_pattern(&self.options.ignore_patterns) {
+            return Err(Error::IgnorePattern {
+                pattern: pattern.to_string(),
+                message: err.to_string(),
+            });
+        }
+        Ok(Combiner {
+            options: self.options,
+            format: self.format,
+            render_options: self.render_options,
+            sink: self.sink,
+            clipboard: self.clipboard,
+            split: self.split,
+        })
+    }
+}
+
+impl Combiner {
+    pub fn builder() -> CombinerBuilder {
+        CombinerBuilder::new()
+    }
+
+    /// Combines the project and delivers it to the configured sink.
+    pub fn run(&self) -> Result<CombinedProject> {
+        let project = self.combine()?;
+        self.deliver(&project)?;
+        Ok(project)
+    }
+
+    /// Renders `project` and writes it to the configured sink. The parts of
+    /// a split output go to numbered files next to the output file, and
+    /// parts left over from a previous, longer output are removed.
+    pub(crate) fn deliver(&self, project: &CombinedProject) -> Result<()> {
+        self.write_parts(self.render_parts(project)?)
+    }
+
+    /// Writes already rendered parts to the configured sink.
+    pub(crate) fn write_parts(&self, mut parts: Vec<String>) -> Result<()> {
+        match &self.sink {
+            Sink::None => {}
+            Sink::Stdout => {
+                for part in &parts {
+                    io::stdout().write_all(part.as_bytes())?;
+                }
+            }
+            Sink::File(path) => {
+                let write = |path: PathBuf, part: &str| {
+                    fs::write(&path, part).map_err(|source| Error::Write { path, source })
+                };
+                let written = if parts.len() == 1 {
+                    write(path.clone(), &parts[0])?;
+                    0
+                } else {
+                    for (index, part) in parts.iter().enumerate() {
+                        write(part_path(path, index + 1), part)?;
+                    }
+                    parts.len()
+                };
+                let mut stale = written + 1;
+                while part_path(path, stale).is_file() {
+                    let stale_path = part_path(path, stale);
+                    fs::remove_file(&stale_path).map_err(|source| Error::Write {
+                        path: stale_path,
+                        source,
+                    })?;
+                    stale += 1;
+                }
+            }
+            Sink::Clipboard => {
+                self.clipboard.copy(&parts.remove(0))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn sink(&self) -> &Sink {
+        &self.sink
+    }
+
+    pub fn render(&self, project: &CombinedProject) -> Result<String> {
+        match self.format {
+            Format::Xml => Ok(project.to_xml_with(self.render_options)),
+            Format::Json => Ok(project.to_json()?),
+        }
+    }
+
+    /// Renders `project` as the parts it is split into, or as a single
+    /// document when it is not split or fits within the limit.
+    pub fn render_parts(&self, project: &CombinedProject) -> Result<Vec<String>> {
+        match self.split {
+            Some(limit) if self.format == Format::Xml => {
+                Ok(project.to_xml_parts(limit, self.render_options))
+            }
+            _ => Ok(vec![self.render(project)?]),
+        }
+    }
+
+    /// Reads every file and, when dependency resolution is enabled, the
+    /// files they import.
+    pub fn combine(&self) -> Result<CombinedProject> {
+        if self.options.deps {
+            let mut resolvers = Resolvers::new(&self.options)?;
+            self.combine_with(Some(&mut resolvers))
+        } else {
+            self.combine_with(None)
+        }
+    }
+
+    pub(crate) fn options(&self) -> &ProcessingOptions {
+        &self.options
+    }
+
+    /// A copy that skips unreadable files wherever this one would abort.
+    pub(crate) fn skipping_unreadable(&self) -> Combiner {
+        let mut combiner = self.clone();
+        let options = &mut combiner.options;
+        for policy in iter::once(&mut options.on_error).chain(options.section_on_error.values_mut())
+        {
+            if *policy == ErrorPolicy::Abort {
+                *policy = ErrorPolicy::Skip;
+            }
+        }
+        combiner
+    }
+
+    /// Like `combine`, reusing `resolvers` when dependencies are resolved.
+    pub(crate) fn combine_with(
+        &self,
+        resolvers: Option<&mut Resolvers>,
+    ) -> Result<CombinedProject> {
+        self.assemble(resolvers).map(|assembly| assembly.project)
+    }
+
+    /// Like `combine_with`, also returning the files read and skipped.
+    pub(crate) fn assemble(&self, resolvers: Option<&mut Resolvers>) -> Result<Assembly> {
+        let options = &self.options;
+        let mut processor = FileProcessor::new(options);
+
+        // Process target files
+        for file_path in &options.target_files {
+            if let Some(file) = processor.read_source_file(file_path, FileSection::Targets)? {
+                processor.project.targets.push(file);
+            }
+            processor
+                .processed_files
+                .insert(canonicalize_path(file_path));
+        }
+
+        // Process reference files
+        for file_path in &options.reference_files {
+            if let Some(file) = processor.read_source_file(file_path, FileSection::References)? {
+                processor.project.references.push(file);
+            }
+            processor
+                .processed_files
+                .insert(canonicalize_path(file_path));
+        }
+
+        let mut resolvers = resolvers.filter(|_| options.deps);
+        if let Some(resolvers) = resolvers.as_mut() {
+            resolvers.configure(options);
+        }
+
+        // Process main files and their dependencies
+        for target_path in &options.paths {
+            if target_path.is_file() {
+                if options.target_files.contains(target_path)
+                    || options.reference_files.contains(target_path)
+                {
+                    continue;
+                }
+
+                if let (true, Some(resolvers)) = (
+                    TypeScriptResolver::is_supported_file(target_path),
+                    resolvers.as_mut(),
+                ) {
+                    processor.process_file_with_deps(target_path, resolvers)?;
+                } else {
+                    processor.process_file(target_path)?;
+                }
+            } else if target_path.is_dir() {
+                for entry in Walk::new(target_path).filter_map(|entry| entry.ok()) {
+                    let path = entry.path();
+                    if !path.is_file() {
+                        continue;
+                    }
+                    // Symlinked files are followed, so one may lead anywhere
+                    if !options.is_within_root(&canonicalize_path(path)) {
+                        processor.skip_outside_root(path)?;
+                        continue;
+                    }
+                    if let Some(pattern) = matching_pattern(path, &options.ignore_patterns) {
+                        processor.skip(path, SkipReason::Ignored(pattern.to_string()))?;
+                        continue;
+                    }
+                    if options.target_files.contains(&path.to_path_buf())
+                        || options.reference_files.contains(&path.to_path_buf())
+                        || processor.processed_files.contains(&canonicalize_path(path))
+                    {
+                        processor.skip(path, SkipReason::Duplicate)?;
+                        continue;
+                    }
+
+                    if let (true, Some(resolvers)) = (
+                        TypeScriptResolver::is_supported_file(path),
+                        resolvers.as_mut(),
+                    ) {
+                        processor.process_file_with_deps(path, resolvers)?;
+                    } else {
+                        processor.process_file(path)?;
+                    }
+                }
+            } else {
+                return Err(Error::io(
+                    target_path,
+                    io::Error::new(io::ErrorKind::NotFound, "no such file or directory"),
+                ));
+            }
+        }
+
+        if let Some(warning) = resolvers.and_then(Resolvers::save_cache) {
+            processor.project.warnings.push(warning);
+        }
+
+        let started = Instant::now();
+        processor.process_symbols(|| self.project_files())?;
+        processor.resolve_time += started.elapsed();
+
+        let sources = processor
+            .processed_files
+            .iter()
+            .chain(processor.dependency_map.keys())
+            .chain(&processor.symbol_files)
+            .cloned()
+            .collect();
+        let resolve_time = processor.resolve_time;
+        let (project, skipped) = processor.finalize()?;
+        Ok(Assembly {
+            project,
+            sources,
+            skipped,
+            resolve_time,
+        })
+    }
+
+    /// Resolves the dependencies of every supported file under the paths and
+    /// returns the circular import chains, without reading anything else.
+    pub fn find_cycles(&self) -> Result<Vec<Vec<PathBuf>>> {
+        let options = &self.options;
+        let mut processor = FileProcessor::new(options);
+        let mut resolvers = Resolvers::new(options)?;
+
+        for file in self.supported_files() {
+            resolvers.deps.resolve_deps(&file, &mut resolvers.ts)?;
+            processor.record_cycles(&resolvers.deps);
+        }
+        // A cache that cannot be written only costs speed on the next run
+        let _ = resolvers.save_cache();
+
+        processor.cycles.sort();
+        processor
+            .cycles
+            .iter()
+            .map(|cycle| {
+                cycle
+                    .iter()
+                    .map(|file| Ok(display_path(file, options)?))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Combiner {
+    /// Lists the files that would be combined from the paths, sorted and
+    /// without following imports.
+    pub fn list_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for file in self.walk_paths() {
+            if !is_ignored(&file, &self.options.ignore_patterns) {
+                files.push(display_path(&file, &self.options)?);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Script files under the paths whose imports can be resolved.
+    pub(crate) fn supported_files(&self) -> Vec<PathBuf> {
+        self.walk_paths()
+            .into_iter()
+            .filter(|file| {
+                TypeScriptResolver::is_supported_file(file)
+                    && !is_ignored(file, &self.options.ignore_patterns)
+            })
+            .collect()
+    }
+
+    // Every file under the working directory that definitions may be looked
+    // up in, as canonical paths
+    fn project_files(&self) -> Vec<PathBuf> {
+        let Ok(current_dir) = env::current_dir() else {
+            return Vec::new();
+        };
+        Walk::new(current_dir)
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                path.is_file()
+                    && self.options.is_within_root(&canonicalize_path(path))
+                    && symbols::is_supported_file(path)
+                    && !is_ignored(path, &self.options.ignore_patterns)
+                    && !path
+                        .components()
+                        .any(|component| component.as_os_str() == "node_modules")
+            })
+            .map(|path| canonicalize_path(&path))
+            .collect()
+    }
+
+    pub(crate) fn walk_paths(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for target_path in &self.options.paths {
+            if target_path.is_dir() {
+                files.extend(
+                    Walk::new(target_path)
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.into_path())
+                        .filter(|path| {
+                            path.is_file() && self.options.is_within_root(&canonicalize_path(path))
+                        }),
+                );
+            } else {
+                files.push(target_path.clone());
+            }
+        }
+        files
+    }
+}
+
+// Tracks processed files and dependencies while the project is assembled
+struct FileProcessor<'a> {
+    options: &'a ProcessingOptions,
+    processed_files: HashSet<PathBuf>,
+    dependency_map: HashMap<PathBuf, Vec<(PathBuf, Option<ImportKind>)>>,
+    cycles: Vec<Vec<PathBuf>>,
+    parse_errors: BTreeMap<PathBuf, Vec<ParseIssue>>,
+    symbol_files: HashSet<PathBuf>,
+    skipped: BTreeMap<PathBuf, SkipReason>,
+    unresolved: BTreeSet<UnresolvedImport>,
+    package_imports: BTreeSet<(PathBuf, String)>,
+    resolve_time: Duration,
+    project: CombinedProject,
+}
+
+impl<'a> FileProcessor<'a> {
+    fn new(options: &'a ProcessingOptions) -> Self {
+        Self {
+            options,
+            processed_files: HashSet::new(),
+            dependency_map: HashMap::new(),
+            cycles: Vec::new(),
+            parse_errors: BTreeMap::new(),
+            symbol_files: HashSet::new(),
+            skipped: BTreeMap::new(),
+            unresolved: BTreeSet::new(),
+            package_imports: BTreeSet::new(),
+            resolve_time: Duration::ZERO,
+            project: CombinedProject::default(),
+        }
+    }
+
+    fn read_source_file(
+        &mut self,
+        file_path: &Path,
+        section: FileSection,
+    ) -> Result<Option<SourceFile>> {
+        if let Some(pattern) = matching_pattern(file_path, &self.options.ignore_patterns) {
+            self.skip(file_path, SkipReason::Ignored(pattern.to_string()))?;
+            return Ok(None);
+        }
+
+        let content = match read_text(file_path) {
+            Ok(Some(content)) => content,
+            // Images and other binary files cannot be shown as text
+            Ok(None) => {
+                self.skip(file_path, SkipReason::Binary)?;
+                return Ok(None);
+            }
+            Err(err) => {
+                let Some(message) = self.recover(file_path, section, err)? else {
+                    return Ok(None);
+                };
+                return Ok(Some(SourceFile {
+                    path: display_path(file_path, self.options)?,
+                    content: String::new(),
+                    lines: None,
+                    error: Some(message),
+                }));
+            }
+        };
+        let (content, lines) = match self.options.selections.get(file_path) {
+            Some(selection) => {
+                let (excerpt, range) = selection.extract(file_path, &content)?;
+                (excerpt, Some(range))
+            }
+            None => (content, None),
+        };
+
+        Ok(Some(SourceFile {
+            path: display_path(file_path, self.options)?,
+            content,
+            lines,
+            error: None,
+        }))
+    }
+
+    // Returns whether the file was read, so that its imports can be followed
+    fn process_file(&mut self, file_path: &Path) -> Result<bool> {
+        let file = self.read_source_file(file_path, FileSection::Files)?;
+        let read = file.as_ref().is_some_and(|file| file.error.is_none());
+        if let Some(file) = file {
+            self.project.files.push(file);
+        }
+        self.processed_files.insert(canonicalize_path(file_path));
+        Ok(read)
+    }
+
+    // Applies the error policy of `section` to a file that could not be
+    // read, returning the message to embed in its place, if any
+    fn recover(
+        &mut self,
+        file_path: &Path,
+        section: FileSection,
+        err: Error,
+    ) -> Result<Option<String>> {
+        let policy = self.options.error_policy(section);
+        let message = match (&err, policy) {
+            (_, ErrorPolicy::Abort) => return Err(err),
+            (Error::Io { source, .. }, _) => source.to_string(),
+            (Error::Parse { message, .. }, _) => message.clone(),
+            _ => return Err(err),
+        };
+        let shown = display_path(file_path, self.options)?;
+
+        if policy == ErrorPolicy::Embed {
+            self.project.warnings.push(format!(
+                "{} cannot be read and was replaced by an error: {}",
+                shown.display(),
+                message
+            ));
+            return Ok(Some(message));
+        }
+        self.project.warnings.push(format!(
+            "{} cannot be read and was skipped: {}",
+            shown.display(),
+            message
+        ));
+        self.skip(file_path, SkipReason::Unreadable)?;
+        Ok(None)
+    }
+
+    // Process a single file and its dependencies
+    fn process_file_with_deps(
+        &mut self,
+        file_path: &Path,
+        resolvers: &mut Resolvers,
+    ) -> Result<()> {
+        let Resolvers {
+            deps: deps_resolver,
+            ts: ts_resolver,
+        } = resolvers;
+
+        // Skip if already processed
+        let canonical_path = canonicalize_path(file_path);
+        if self.processed_files.contains(&canonical_path) {
+            return Ok(());
+        }
+
+        // Process main file; the imports of a file that cannot be read are
+        // unknown
+        if !self.process_file(file_path)? {
+            return Ok(());
+        }
+
+        // Process dependencies
+        let started = Instant::now();
+        let resolved_files = deps_resolver.resolve_deps(file_path, ts_resolver)?;
+        self.resolve_time += started.elapsed();
+
+        for dep_file in resolved_files {
+            if let Some(pattern) = matching_pattern(&dep_file, &self.options.ignore_patterns) {
+                self.skip(&dep_file, SkipReason::Ignored(pattern.to_string()))?;
+            } else if !self.options.is_within_root(&dep_file) {
+                self.skip_outside_root(&dep_file)?;
+            } else if dep_file != canonical_path {
+                let mut all_importers: Vec<_> = deps_resolver
+                    .get_all_importers(&dep_file)
+                    .into_iter()
+                    .map(|importer| {
+                        let kind = deps_resolver.get_import_kind(&importer, &dep_file);
+                        (importer, kind)
+                    })
+                    .collect();
+                all_importers.sort();
+                self.dependency_map.insert(dep_file, all_importers);
+            }
+        }
+
+        for external in deps_resolver.get_external_files() {
+            self.skip(external, SkipReason::NodeModules)?;
+        }
+        self.unresolved
+            .extend(deps_resolver.get_unresolved().iter().cloned());
+        if self.options.external_types {
+            self.package_imports
+                .extend(deps_resolver.get_package_imports().iter().cloned());
+        }
+
+        self.record_cycles(deps_resolver);
+        self.parse_errors.extend(
+            deps_resolver
+                .get_parse_errors()
+                .iter()
+                .map(|(file, errors)| (file.clone(), errors.clone())),
+        );
+
+        Ok(())
+    }
+
+    // Adds the requested definitions to the files, then follows the names
+    // they use to the definitions those refer to, level by level
+    fn process_symbols(&mut self, project_files: impl FnOnce() -> Vec<PathBuf>) -> Result<()> {
+        let mut included: HashMap<PathBuf, Vec<LineRange>> = HashMap::new();
+        let mut pending = VecDeque::new();
+
+        for (file_path, names) in &self.options.symbols {
+            let name = names.join("::");
+            if !symbols::is_supported_file(file_path) {
+                return Err(unsupported_file(file_path, &name));
+            }
+            let content = fs::read_to_string(file_path).map_err(|err| Error::io(file_path, err))?;
+            let definition = symbols::find_path(file_path, &content, names).ok_or_else(|| {
+                Error::Config(format!(
+                    "no definition named `{}` in {}",
+                    name,
+                    file_path.display()
+                ))
+            })?;
+
+            let canonical_path = canonicalize_path(file_path);
+            if !include_once(&mut included, &canonical_path, definition.lines) {
+                continue;
+            }
+            self.project.files.push(SourceFile {
+                path: display_path(file_path, self.options)?,
+                content: excerpt(&content, definition.lines),
+                lines: Some(definition.lines),
+                error: None,
+            });
+            self.symbol_files.insert(canonical_path.clone());
+            pending.push_back((canonical_path, content, definition, 0));
+        }
+
+        if self.options.symbol_depth == 0 || pending.is_empty() {
+            return Ok(());
+        }
+
+        let index = SymbolIndex::build(project_files());
+        while let Some((file_path, content, definition, depth)) = pending.pop_front() {
+            if depth >= self.options.symbol_depth {
+                continue;
+            }
+
+            for name in symbols::referenced_names(&file_path, &content, definition.bytes.clone()) {
+                let Some((found_path, found)) = index.lookup(&name, &file_path) else {
+                    continue;
+                };
+                if !include_once(&mut included, found_path, found.lines) {
+                    continue;
+                }
+                let found_content = index.content(found_path).unwrap_or_default();
+                let found_excerpt = excerpt(found_content, found.lines);
+
+                self.project.dependencies.push(Dependency {
+                    path: display_path(found_path, self.options)?,
+                    imported_by: vec![Importer {
+                        path: display_path(&file_path, self.options)?,
+                        kind: None,
+                    }],
+                    bytes: found_excerpt.len() as u64,
+                    content: Some(found_excerpt),
+                    lines: Some(found.lines),
+                    error: None,
+                });
+                self.symbol_files.insert(found_path.clone());
+                pending.push_back((
+                    found_path.clone(),
+                    found_content.to_string(),
+                    Definition::clone(found),
+                    depth + 1,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Records why a file was left out, keeping the first reason found
+    fn skip(&mut self, file_path: &Path, reason: SkipReason) -> io::Result<()> {
+        self.skipped
+            .entry(display_path(file_path, self.options)?)
+            .or_insert(reason);
+        Ok(())
+    }
+
+    fn skip_outside_root(&mut self, file_path: &Path) -> io::Result<()> {
+        let shown = display_path(file_path, self.options)?;
+        if !self.skipped.contains_key(&shown) {
+            self.project.warnings.push(format!(
+                "{} is outside the project root and was left out",
+                shown.display()
+            ));
+        }
+        self.skip(file_path, SkipReason::OutsideRoot)
+    }
+
+    // Keep each cycle once, even when reached from several entry files
+    fn record_cycles(&mut self, deps_resolver: &DependencyResolver) {
+        for cycle in deps_resolver.find_cycles() {
+            if !self.cycles.contains(&cycle) {
+                self.cycles.push(cycle);
+            }
+        }
+    }
+
+    fn finalize(mut self) -> Result<(CombinedProject, Vec<SkippedFile>)> {
+        // Sort dependencies to ensure consistent output
+        let mut deps: Vec<_> = std::mem::take(&mut self.dependency_map)
+            .into_iter()
+            .collect();
+        deps.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (dep_file, importers) in deps {
+            // Skip if already processed in main section
+            if self.processed_files.contains(&dep_file) {
+                self.skipped
+                    .entry(display_path(&dep_file, self.options)?)
+                    .or_insert(SkipReason::Duplicate);
+                continue;
+            }
+
+            let read = read_text(&dep_file).and_then(|content| {
+                let metadata = fs::metadata(&dep_file).map_err(|err| Error::io(&dep_file, err))?;
+                Ok((content, metadata.len()))
+            });
+            let (content, bytes, error) = match read {
+                Ok((content, bytes)) => (content, bytes, None),
+                Err(err) => match self.recover(&dep_file, FileSection::Dependencies, err)? {
+                    Some(message) => (None, 0, Some(message)),
+                    None => continue,
+                },
+            };
+            self.project.dependencies.push(Dependency {
+                path: display_path(&dep_file, self.options)?,
+                imported_by: importers
+                    .iter()
+                    .map(|(importer, kind)| {
+                        Ok(Importer {
+                            path: display_path(importer, self.options)?,
+                            kind: *kind,
+                        })
+                    })
+                    .collect::<io::Result<_>>()?,
+                content,
+                bytes,
+                lines: None,
+                error,
+            });
+        }
+
+        self.cycles.sort();
+        for cycle in &self.cycles {
+            self.project.cycles.push(
+                cycle
+                    .iter()
+                    .map(|file| display_path(file, self.options))
+                    .collect::<io::Result<_>>()?,
+            );
+        }
+
+        for types in external_types::collect(&self.package_imports) {
+            if !self.options.is_within_root(&types.path) {
+                self.skip_outside_root(&types.path)?;
+                continue;
+            }
+            self.project.external.push(ExternalTypes {
+                path: display_path(&types.path, self.options)?,
+                imported_by: types
+                    .imported_by
+                    .iter()
+                    .map(|importer| display_path(importer, self.options))
+                    .collect::<io::Result<_>>()?,
+                ..types
+            });
+        }
+
+        for unresolved in &self.unresolved {
+            self.project.unresolved.push(UnresolvedImport {
+                from: display_path(&unresolved.from, self.options)?,
+                ..unresolved.clone()
+            });
+        }
+
+        for (file, errors) in self.parse_errors {
+            self.project
+                .parse_errors
+                .insert(display_path(&file, self.options)?, errors);
+        }
+
+        let skipped = self
+            .skipped
+            .into_iter()
+            .map(|(path, reason)| SkippedFile { path, reason })
+            .collect();
+        Ok((self.project, skipped))
+    }
+}
+
+// Records `lines` of `file_path` as included, unless they overlap lines that
+// already are, such as a method of an included class
+fn include_once(
+    included: &mut HashMap<PathBuf, Vec<LineRange>>,
+    file_path: &Path,
+    lines: LineRange,
+) -> bool {
+    let ranges = included.entry(file_path.to_path_buf()).or_default();
+    if ranges
+        .iter()
+        .any(|range| range.start <= lines.end && lines.start <= range.end)
+    {
+        return false;
+    }
+    ranges.push(lines);
+    true
+}
+
+fn create_dependency_resolver(options: &ProcessingOptions) -> io::Result<DependencyResolver> {
+    let mut resolver = DependencyResolver::new(&env::current_dir()?, true)?;
+    if options.cache {
+        resolver.set_cache(
+            ParseCache::open(resolver.get_base_path(), options.cache_dir.as_deref())
+                .with_resolutions(options.cache_resolutions),
+        );
+    }
+    Ok(resolver)
+}
+
+/// The path of `file_path` shown in the output, in the style of `options`.
+/// Relative paths never start with `./`, and files outside the directory
+/// they are relative to are shown with absolute paths.
+pub(crate) fn display_path(file_path: &Path, options: &ProcessingOptions) -> io::Result<PathBuf> {
+    let absolute = normalize_path(&env::current_dir()?.join(file_path));
+    let base = match options.path_style {
+        PathStyle::Absolute => return Ok(canonicalize_path(&absolute)),
+        PathStyle::Relative => env::current_dir()?,
+        PathStyle::RootRelative => match &options.root {
+            Some(root) => root.clone(),
+            None => project_root(&canonicalize_path(&env::current_dir()?)),
+        },
+    };
+
+    let relative = match absolute.strip_prefix(&base) {
+        Ok(relative) => Some(relative.to_path_buf()),
+        // Walked paths keep symbolic links, resolved dependencies do not
+        Err(_) => {
+            let canonical_base = canonicalize_path(&base);
+            let strip = |path: &Path| Some(path.strip_prefix(&canonical_base).ok()?.to_path_buf());
+            strip(&absolute).or_else(|| strip(&canonicalize_path(&absolute)))
+        }
+    };
+    Ok(match (relative, &options.path_prefix) {
+        (Some(relative), Some(prefix)) => prefix.join(relative),
+        (Some(relative), None) if relative.as_os_str().is_empty() => PathBuf::from("."),
+        (Some(relative), None) => relative,
+        (None, _) => canonicalize_path(&absolute),
+    })
+}
+
+// The nearest directory containing `.git`, from `dir` up, or else `dir`
+fn project_root(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+// The content of a text file, or `None` for a binary one
+fn read_text(file_path: &Path) -> Result<Option<String>> {
+    if is_binary_file(file_path).map_err(|err| Error::io(file_path, err))? {
+        return Ok(None);
+    }
+    fs::read_to_string(file_path)
+        .map(Some)
+        .map_err(|err| Error::io(file_path, err))
+}
+
+// Text never contains NUL bytes; text that is not valid UTF-8 is still
+// text, and fails to be read as such instead of being skipped
+fn is_binary_file(file_path: &Path) -> io::Result<bool> {
+    let mut buffer = [0; 8192];
+    let read = fs::File::open(file_path)?.read(&mut buffer)?;
+    Ok(buffer[..read].contains(&0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_leading_outside_the_root_are_skipped() {
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let root_path = fs::canonicalize(root.path()).unwrap();
+        fs::create_dir(root_path.join("src")).unwrap();
+        fs::write(root_path.join("src/main.txt"), "main").unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("secret.txt"),
+            root_path.join("src/link.txt"),
+        )
+        .unwrap();
+
+        let combiner = Combiner::builder()
+            .path(root_path.join("src"))
+            .path_style(PathStyle::RootRelative)
+            .root(&root_path)
+            .cache(false)
+            .confine_to(root_path.clone())
+            .build()
+            .unwrap();
+
+        let assembly = combiner.assemble(None).unwrap();
+        let files: Vec<_> = assembly
+            .project
+            .files
+            .iter()
+            .map(|file| &file.path)
+            .collect();
+        assert_eq!(files, [Path::new("src/main.txt")]);
+        assert_eq!(
+            assembly.skipped,
+            [SkippedFile {
+                path: PathBuf::from("src/link.txt"),
+                reason: SkipReason::OutsideRoot,
+            }]
+        );
+        assert_eq!(
+            combiner.list_files().unwrap(),
+            [PathBuf::from("src/main.txt")]
+        );
+    }
+
+    #[test]
+    fn a_file_can_only_be_selected_once() {
+        let result = Combiner::builder()
+            .path("a.ts:1-10")
+            .path("a.ts:50-60")
+            .cache(false)
+            .build();
+        assert!(
+            matches!(&result, Err(Error::Config(message)) if message.contains("more than once")),
+            "{:?}",
+            result.err()
+        );
+
+        assert!(Combiner::builder()
+            .path("a.ts:1-10")
+            .path("b.ts:50-60")
+            .cache(false)
+            .build()
+            .is_ok());
+    }
+}
@@ -1,89 +1,517 @@
-use crate::Args;
+use crate::{expand_tilde, Args};
+use pcc::{
+    Clipboard, ClipboardBackend, Combiner, CombinerBuilder, Error, ErrorPolicy, FileSection,
+    PathStyle, SplitLimit,
+};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
+use std::ffi::OsString;
+use std::fmt;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Deserialize)]
+const FILE_NAME: &str = ".pcc_config.toml";
+
+#[derive(Debug, Default, Deserialize)]
 pub struct Default {
     pub action: Option<String>,
     pub output_path: Option<String>,
     pub output_file_name: Option<String>,
     pub ignore_patterns: Option<Vec<String>>,
     pub use_relative_paths: Option<bool>,
+    pub paths: Option<PathStyle>,
+    pub root: Option<String>,
+    pub path_prefix: Option<String>,
     pub deps: Option<bool>,
+    pub skip_type_imports: Option<bool>,
+    pub skip_dynamic_imports: Option<bool>,
+    pub include_assets: Option<bool>,
+    pub external_types: Option<bool>,
+    pub cache: Option<bool>,
+    pub cache_resolutions: Option<bool>,
+    pub cache_dir: Option<String>,
+    pub line_numbers: Option<bool>,
+    pub split_tokens: Option<usize>,
+    pub split_bytes: Option<usize>,
+    pub clipboard: Option<String>,
+    pub clipboard_command: Option<String>,
+    pub on_error: Option<ErrorPolicy>,
+    pub on_error_sections: Option<HashMap<FileSection, ErrorPolicy>>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    #[serde(default)]
     pub default: Default,
 }
 
-#[derive(Debug)]
-pub struct ProcessingOptions {
-    pub ignore_patterns: String,
-    pub use_relative_paths: bool,
-    pub deps: bool,
-    pub target_files: Vec<PathBuf>,
-    pub reference_files: Vec<PathBuf>,
+/// Where a configuration value comes from, from the highest precedence to
+/// the lowest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    CommandLine,
+    /// `PCC_*` variables, named after the keys
+    Environment,
+    /// `.pcc_config.toml` in the working directory
+    ProjectFile(PathBuf),
+    /// The file named by `PCC_CONFIG`, `$XDG_CONFIG_HOME/pcc/config.toml` or
+    /// `.pcc_config.toml` in the home directory
+    GlobalFile(PathBuf),
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::CommandLine => write!(f, "command line"),
+            Source::Environment => write!(f, "environment"),
+            Source::ProjectFile(path) => write!(f, "project file {}", path.display()),
+            Source::GlobalFile(path) => write!(f, "global file {}", path.display()),
+            Source::Default => write!(f, "default"),
+        }
+    }
 }
 
 impl Config {
-    pub fn load() -> io::Result<Self> {
-        let home_dir = env::var("HOME").unwrap_or_else(|_| env::var("USERPROFILE").unwrap());
-        let config_path = PathBuf::from(home_dir).join(".pcc_config.toml");
-
-        let config_str = match fs::read_to_string(&config_path) {
-            Ok(content) => content,
-            Err(err) if err.kind() == io::ErrorKind::NotFound => {
-                return Err(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("Config file not found at: {}", config_path.display()),
-                ));
+    /// The global configuration file: the one named by `PCC_CONFIG`, else
+    /// `$XDG_CONFIG_HOME/pcc/config.toml` if it exists, else
+    /// `.pcc_config.toml` in the home directory.
+    pub fn path() -> Result<PathBuf, Error> {
+        if let Some(path) = env::var_os("PCC_CONFIG").filter(|path| !path.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+
+        let config_home = match env::var_os("XDG_CONFIG_HOME").filter(|path| !path.is_empty()) {
+            Some(config_home) => Some(PathBuf::from(config_home)),
+            None => home_dir().ok().map(|home| home.join(".config")),
+        };
+        if let Some(xdg_path) = config_home.map(|config_home| config_home.join("pcc/config.toml")) {
+            if xdg_path.is_file() {
+                return Ok(xdg_path);
+            }
+        }
+        Ok(home_dir()?.join(FILE_NAME))
+    }
+
+    /// The configuration file of the project being combined.
+    pub fn project_path() -> io::Result<PathBuf> {
+        Ok(env::current_dir()?.join(FILE_NAME))
+    }
+
+    /// The configuration files that exist, the global one first. Without a
+    /// home directory there is no global file, unless `PCC_CONFIG` or
+    /// `XDG_CONFIG_HOME` names one.
+    pub fn files() -> Result<Vec<(Source, PathBuf)>, Error> {
+        let global_path = Self::path().ok();
+        let project_path = Self::project_path()?;
+
+        let mut files = Vec::new();
+        match &global_path {
+            Some(path) if path.is_file() => {
+                files.push((Source::GlobalFile(path.clone()), path.clone()));
+            }
+            Some(path) if env::var_os("PCC_CONFIG").is_some_and(|path| !path.is_empty()) => {
+                return Err(Error::Config(format!(
+                    "PCC_CONFIG names a file that does not exist: {}",
+                    path.display()
+                )));
             }
-            Err(err) => return Err(err),
+            _ => {}
+        }
+        // In the home directory, its file is the global one
+        let is_global = |path: &Path| {
+            global_path
+                .as_deref()
+                .is_some_and(|global| same_file(path, global))
         };
+        if project_path.is_file() && !is_global(&project_path) {
+            files.push((Source::ProjectFile(project_path.clone()), project_path));
+        }
+        Ok(files)
+    }
 
-        toml::from_str(&config_str).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    /// The `[default]` table of each configuration file, the global one
+    /// first, then the `PCC_*` environment variables, if any are set. Each
+    /// overrides the keys it sets.
+    pub fn layers() -> Result<Vec<(Source, toml::Table)>, Error> {
+        let mut layers = Self::files()?
+            .into_iter()
+            .map(|(source, path)| {
+                let mut file = read_table(&path)?;
+                let default = match file.remove("default") {
+                    Some(toml::Value::Table(table)) => table,
+                    Some(_) => {
+                        return Err(Error::Config(format!(
+                            "{}: `default` must be a table",
+                            path.display()
+                        )))
+                    }
+                    None => toml::Table::new(),
+                };
+                Ok((source, default))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let environment = environment_table()?;
+        if !environment.is_empty() {
+            layers.push((Source::Environment, environment));
+        }
+        Ok(layers)
     }
-}
 
-fn convert_ignore_patterns(patterns: &[String]) -> String {
-    patterns
-        .iter()
-        .map(|pattern| {
-            if pattern.ends_with('/') {
-                format!("{}**/*", pattern)
+    /// Reads the configuration files and the environment. Without either,
+    /// every key has its default.
+    pub fn load() -> Result<Self, Error> {
+        let mut merged = toml::Table::new();
+        let mut sources = Vec::new();
+        for (source, table) in Self::layers()? {
+            merged.extend(table);
+            sources.push(source.to_string());
+        }
+
+        let mut file = toml::Table::new();
+        file.insert("default".to_string(), toml::Value::Table(merged));
+        file.try_into().map_err(|err: toml::de::Error| {
+            Error::Config(format!(
+                "{}: {}; run `pcc config check` for details",
+                sources.join(", "),
+                err.message().trim()
+            ))
+        })
+    }
+
+    /// Starts a combiner for `paths` from the command line flags, falling
+    /// back to the configuration file for anything not given on the command line.
+    pub fn combiner_builder(
+        &self,
+        args: &Args,
+        paths: &[PathBuf],
+    ) -> Result<CombinerBuilder, Error> {
+        let defaults = &self.default;
+
+        // Command line patterns are added after the config file patterns
+        let ignore_patterns = defaults
+            .ignore_patterns
+            .iter()
+            .flatten()
+            .chain(&args.ignore_patterns);
+
+        let mut builder = Combiner::builder()
+            .paths(paths.iter().cloned())
+            .path_style(self.path_style(args))
+            .deps(args.deps || defaults.deps.unwrap_or(false))
+            .skip_type_imports(
+                args.skip_type_imports || defaults.skip_type_imports.unwrap_or(false),
+            )
+            .skip_dynamic_imports(
+                args.skip_dynamic_imports || defaults.skip_dynamic_imports.unwrap_or(false),
+            )
+            .include_assets(args.assets || defaults.include_assets.unwrap_or(false))
+            .external_types(args.external_types || defaults.external_types.unwrap_or(false))
+            .cache(!args.no_cache && defaults.cache.unwrap_or(true))
+            .cache_resolutions(
+                args.cache_resolutions || defaults.cache_resolutions.unwrap_or(false),
+            )
+            .symbol_depth(args.symbol_depth)
+            .line_numbers(args.line_numbers || defaults.line_numbers.unwrap_or(false));
+
+        for pattern in ignore_patterns {
+            builder = builder.ignore(pattern.clone());
+        }
+        for file in &args.target_files {
+            builder = builder.target(file.clone());
+        }
+        for file in &args.reference_files {
+            builder = builder.reference(file.clone());
+        }
+        for symbol in &args.symbols {
+            builder = builder.symbol(symbol.clone());
+        }
+        if let Some(cache_dir) = &defaults.cache_dir {
+            builder = builder.cache_dir(expand_tilde(cache_dir)?);
+        }
+        match (&args.root, &defaults.root) {
+            (Some(root), _) => builder = builder.root(root.clone()),
+            (None, Some(root)) => builder = builder.root(expand_tilde(root)?),
+            (None, None) => {}
+        }
+        if let Some(prefix) = args.path_prefix.as_ref().or(defaults.path_prefix.as_ref()) {
+            builder = builder.path_prefix(prefix.clone());
+        }
+        // A policy for every section, then those for single sections, each
+        // from the command line or else the configuration file
+        let on_error = args
+            .on_error
+            .iter()
+            .filter(|(section, _)| section.is_none())
+            .map(|(_, policy)| *policy)
+            .next_back()
+            .or(args.keep_going.then_some(ErrorPolicy::Skip))
+            .or(defaults.on_error);
+        if let Some(policy) = on_error {
+            builder = builder.on_error(policy);
+        }
+        let section_policies = defaults
+            .on_error_sections
+            .iter()
+            .flatten()
+            .map(|(section, policy)| (*section, *policy))
+            .chain(
+                args.on_error
+                    .iter()
+                    .filter_map(|(section, policy)| Some(((*section)?, *policy))),
+            );
+        for (section, policy) in section_policies {
+            builder = builder.section_on_error(section, policy);
+        }
+        if let Some(limit) = self.split_limit(args) {
+            builder = builder.split(limit);
+        }
+
+        Ok(builder)
+    }
+
+    /// How paths are shown: `--paths`, `--relative` or `--no-relative`, or
+    /// else `paths` or `use_relative_paths` in the configuration file.
+    pub fn path_style(&self, args: &Args) -> PathStyle {
+        let from_flag = |relative: bool| {
+            if relative {
+                PathStyle::Relative
             } else {
-                pattern.clone()
+                PathStyle::Absolute
             }
+        };
+        args.path_style
+            .or(args.relative_flag().map(from_flag))
+            .or(self.default.paths)
+            .or(self.default.use_relative_paths.map(from_flag))
+            .unwrap_or_default()
+    }
+
+    /// How to copy to the clipboard, from the command line or else the
+    /// configuration file, with the configured command as the last resort.
+    pub fn clipboard(&self, args: &Args) -> Result<Clipboard, Error> {
+        let defaults = &self.default;
+        let mut clipboard = Clipboard::new();
+        if let Some(command) = &defaults.clipboard_command {
+            clipboard = clipboard.command(command.clone());
+        }
+
+        match args.clipboard.as_deref().or(defaults.clipboard.as_deref()) {
+            None | Some("auto") => Ok(clipboard),
+            Some("command") => match &defaults.clipboard_command {
+                Some(command) => Ok(clipboard.backend(ClipboardBackend::Command(command.clone()))),
+                None => Err(Error::Config(
+                    "the `command` clipboard needs `clipboard_command`".to_string(),
+                )),
+            },
+            Some(backend) => backend
+                .parse()
+                .map(|backend| clipboard.backend(backend))
+                .map_err(Error::Config),
+        }
+    }
+
+    /// The part size limit, from the command line or else the configuration
+    /// file, tokens taking precedence over bytes.
+    pub fn split_limit(&self, args: &Args) -> Option<SplitLimit> {
+        let defaults = &self.default;
+        match (args.split_tokens, args.split_bytes) {
+            (Some(tokens), _) => Some(SplitLimit::Tokens(tokens)),
+            (None, Some(bytes)) => Some(SplitLimit::Bytes(bytes)),
+            (None, None) => defaults
+                .split_tokens
+                .map(SplitLimit::Tokens)
+                .or(defaults.split_bytes.map(SplitLimit::Bytes)),
+        }
+    }
+}
+
+/// Reads a configuration file as a TOML table.
+pub fn read_table(path: &Path) -> Result<toml::Table, Error> {
+    let content = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+    toml::from_str(&content).map_err(|err| Error::Config(format!("{}: {}", path.display(), err)))
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// The home directory, from `HOME` or else `USERPROFILE`.
+pub fn home_dir() -> Result<PathBuf, Error> {
+    ["HOME", "USERPROFILE"]
+        .iter()
+        .filter_map(env::var_os)
+        .find(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            Error::Config(
+                "cannot find the home directory; set HOME, or PCC_CONFIG to the configuration file"
+                    .to_string(),
+            )
         })
-        .collect::<Vec<_>>()
-        .join("\n")
 }
 
-impl ProcessingOptions {
-    pub fn new(args: &Args, config: &Config) -> io::Result<Self> {
-        let mut patterns = Vec::new();
+// The keys of `Default` whose values are not strings
+pub const NON_STRING_KEYS: &[&str] = &[
+    "ignore_patterns",
+    "use_relative_paths",
+    "deps",
+    "skip_type_imports",
+    "skip_dynamic_imports",
+    "include_assets",
+    "external_types",
+    "cache",
+    "cache_resolutions",
+    "line_numbers",
+    "split_tokens",
+    "split_bytes",
+    "on_error_sections",
+];
+
+/// The name of the environment variable overriding `key`.
+pub fn environment_variable(key: &str) -> String {
+    format!("PCC_{}", key.to_uppercase())
+}
+
+/// The keys set by `PCC_*` variables other than `PCC_CONFIG`. Values of keys
+/// that are not strings are read as TOML, so `PCC_DEPS=true` sets a boolean,
+/// and every other value is kept as it is, so `PCC_OUTPUT_FILE_NAME=2024` is
+/// a file name. Ignore patterns and section policies can also be listed as
+/// `*.log,target` and `dependencies=embed,files=skip`. Other variables are
+/// ignored whatever their contents, but a `PCC_*` value must be UTF-8.
+pub fn environment_table() -> Result<toml::Table, Error> {
+    variables_table(env::vars_os())
+}
 
-        // First, apply patterns from the config file
-        if let Some(config_patterns) = &config.default.ignore_patterns {
-            patterns.extend(config_patterns.clone());
+fn variables_table(
+    variables: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Result<toml::Table, Error> {
+    let mut table = toml::Table::new();
+    for (name, value) in variables {
+        let Some(key) = name.to_str().and_then(|name| name.strip_prefix("PCC_")) else {
+            continue;
+        };
+        if key == "CONFIG" || key.is_empty() {
+            continue;
         }
+        let value = value
+            .into_string()
+            .map_err(|_| Error::Config(format!("PCC_{} is not valid UTF-8", key)))?;
+        let key = key.to_lowercase();
+        let value = if NON_STRING_KEYS.contains(&key.as_str()) {
+            parse_value(&value)
+        } else {
+            value.into()
+        };
+        let value = match (key.as_str(), value) {
+            ("ignore_patterns", toml::Value::String(list)) => toml::Value::Array(
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|pattern| !pattern.is_empty())
+                    .map(Into::into)
+                    .collect(),
+            ),
+            ("on_error_sections", toml::Value::String(list)) => {
+                let mut sections = toml::Table::new();
+                for entry in list.split(',').filter(|entry| !entry.trim().is_empty()) {
+                    let (section, policy) = entry.split_once('=').unwrap_or((entry, ""));
+                    sections.insert(section.trim().to_string(), policy.trim().into());
+                }
+                toml::Value::Table(sections)
+            }
+            (_, value) => value,
+        };
+        table.insert(key, value);
+    }
+    Ok(table)
+}
 
-        // Command line patterns can override config file patterns
-        patterns.extend(args.ignore_patterns.clone());
+fn parse_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| value.into())
+}
 
-        // Convert patterns to proper ignore format
-        let ignore_patterns = convert_ignore_patterns(&patterns);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(ProcessingOptions {
-            ignore_patterns,
-            use_relative_paths: args.relative,
-            deps: args.deps || config.default.deps.unwrap_or(false),
-            target_files: args.target_files.clone(),
-            reference_files: args.reference_files.clone(),
-        })
+    fn table(variables: &[(&str, &str)]) -> toml::Table {
+        variables_table(
+            variables
+                .iter()
+                .map(|(name, value)| (OsString::from(name), OsString::from(value))),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn only_non_string_keys_are_read_as_toml() {
+        let table = table(&[
+            ("PCC_OUTPUT_FILE_NAME", "2024"),
+            ("PCC_ROOT", "[draft]"),
+            ("PCC_PATH_PREFIX", "1e3"),
+            ("PCC_DEPS", "true"),
+            ("PCC_SPLIT_BYTES", "1000"),
+            ("PCC_IGNORE_PATTERNS", "target, *.log"),
+            ("PCC_ON_ERROR_SECTIONS", "dependencies=embed,files=skip"),
+            ("PCC_CONFIG", "/etc/pcc.toml"),
+            ("HOME", "/root"),
+        ]);
+        let expected: toml::Table = toml::from_str(
+            r#"
+            output_file_name = "2024"
+            root = "[draft]"
+            path_prefix = "1e3"
+            deps = true
+            split_bytes = 1000
+            ignore_patterns = ["target", "*.log"]
+            on_error_sections = { dependencies = "embed", files = "skip" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(table, expected);
+
+        let config: Config = toml::Value::Table(
+            [("default".to_string(), toml::Value::Table(table))]
+                .into_iter()
+                .collect(),
+        )
+        .try_into()
+        .unwrap();
+        assert_eq!(config.default.output_file_name.as_deref(), Some("2024"));
+    }
+
+    #[test]
+    fn non_string_keys_do_not_take_strings() {
+        for key in NON_STRING_KEYS {
+            let content = format!("[default]\n{} = \"x\"", key);
+            assert!(toml::from_str::<Config>(&content).is_err(), "{}", key);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_pcc_values_must_be_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let invalid = || OsString::from_vec(vec![0xff]);
+        let table = variables_table([
+            (OsString::from("BADVAR"), invalid()),
+            (invalid(), OsString::from("x")),
+            (OsString::from("PCC_DEPS"), OsString::from("true")),
+        ])
+        .unwrap();
+        assert_eq!(table.get("deps"), Some(&toml::Value::Boolean(true)));
+
+        let err = variables_table([(OsString::from("PCC_ROOT"), invalid())]).unwrap_err();
+        assert!(matches!(err, Error::Config(message) if message.contains("PCC_ROOT")));
     }
 }
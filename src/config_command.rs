@@ -0,0 +1,765 @@
+use crate::config::{self, Config, Source};
+use crate::{plural, Args};
+use pcc::{ClipboardBackend, Error, ErrorPolicy};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+// A key of the `[default]` table
+struct Key {
+    name: &'static str,
+    /// Shown by `config show` when no file sets the key; empty when the key
+    /// has no default
+    default: &'static str,
+    /// Written, commented out, by `config init`
+    example: &'static str,
+    description: &'static str,
+}
+
+// Kept in sync with `config::Default` and `config::NON_STRING_KEYS` by the
+// tests below
+const KEYS: &[Key] = &[
+    Key {
+        name: "action",
+        default: "",
+        example: "\"copy\"",
+        description: "`copy` or `save`, when neither --copy nor --save is given",
+    },
+    Key {
+        name: "output_path",
+        default: "",
+        example: "\"~/combined_code.txt\"",
+        description: "The file `save` writes to",
+    },
+    Key {
+        name: "output_file_name",
+        default: "\"combined_code.txt\"",
+        example: "\"combined_code.txt\"",
+        description: "The file `save` writes to in the working directory, without output_path",
+    },
+    Key {
+        name: "ignore_patterns",
+        default: "[]",
+        example: "[\"target\", \"*.log\"]",
+        description: "Files to skip, in .gitignore format; --ignore adds to them",
+    },
+    Key {
+        name: "use_relative_paths",
+        default: "true",
+        example: "true",
+        description: "Shows paths relative to the working directory, or absolute when false",
+    },
+    Key {
+        name: "paths",
+        default: "",
+        example: "\"root-relative\"",
+        description:
+            "`relative`, `absolute` or `root-relative`; takes precedence over use_relative_paths",
+    },
+    Key {
+        name: "root",
+        default: "",
+        example: "\"~/projects/app\"",
+        description:
+            "The directory root-relative paths start from, instead of the nearest one with .git",
+    },
+    Key {
+        name: "path_prefix",
+        default: "",
+        example: "\"frontend/\"",
+        description: "Prepended to every relative path, to tell repositories apart",
+    },
+    Key {
+        name: "deps",
+        default: "false",
+        example: "true",
+        description: "Resolves and includes dependencies, like --deps",
+    },
+    Key {
+        name: "skip_type_imports",
+        default: "false",
+        example: "true",
+        description: "Does not follow type-only imports, like --skip-type-imports",
+    },
+    Key {
+        name: "skip_dynamic_imports",
+        default: "false",
+        example: "true",
+        description: "Does not follow dynamic import() expressions, like --skip-dynamic-imports",
+    },
+    Key {
+        name: "include_assets",
+        default: "false",
+        example: "true",
+        description: "Includes imported stylesheets and assets, like --assets",
+    },
+    Key {
+        name: "external_types",
+        default: "false",
+        example: "true",
+        description: "Includes type declarations of imported packages, like --external-types",
+    },
+    Key {
+        name: "cache",
+        default: "true",
+        example: "false",
+        description: "Reuses the dependency cache; false is like --no-cache",
+    },
+    Key {
+        name: "cache_resolutions",
+        default: "false",
+        example: "true",
+        description: "Also reuses cached import resolutions, like --cache-resolutions",
+    },
+    Key {
+        name: "cache_dir",
+        default: "",
+        example: "\"~/.cache/pcc\"",
+        description: "Where the dependency cache is stored",
+    },
+    Key {
+        name: "line_numbers",
+        default: "false",
+        example: "true",
+        description: "Prefixes every line with its line number, like --line-numbers",
+    },
+    Key {
+        name: "split_tokens",
+        default: "",
+        example: "30000",
+        description: "Splits the output into parts of at most about this many tokens",
+    },
+    Key {
+        name: "split_bytes",
+        default: "",
+        example: "100000",
+        description: "Splits the output into parts of at most this many bytes",
+    },
+    Key {
+        name: "clipboard",
+        default: "\"auto\"",
+        example: "\"osc52\"",
+        description: "How to copy: `auto`, `x11`, `wayland`, `osc52` or `command`",
+    },
+    Key {
+        name: "clipboard_command",
+        default: "",
+        example: "\"xclip -selection clipboard\"",
+        description: "A command copying its standard input, tried when no other clipboard works",
+    },
+    Key {
+        name: "on_error",
+        default: "\"abort\"",
+        example: "\"skip\"",
+        description: "What to do with files that cannot be read: `abort`, `skip` or `embed`",
+    },
+    Key {
+        name: "on_error_sections",
+        default: "{}",
+        example: "{ dependencies = \"embed\" }",
+        description:
+            "Error policies of single sections: targets, references, files or dependencies",
+    },
+];
+
+// A problem found in a configuration file, at a 1-based line and column
+struct Problem {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+/// Validates every configuration file and the `PCC_*` variables, printing
+/// each problem found with its position.
+pub fn check() -> Result<(), Error> {
+    let files = Config::files()?;
+    if files.is_empty() {
+        match Config::path() {
+            Ok(path) => println!(
+                "No configuration file found at {}; the defaults are used.",
+                path.display()
+            ),
+            Err(_) => println!("No configuration file found; the defaults are used."),
+        }
+    }
+
+    let mut problems = 0;
+    for (_, path) in &files {
+        let found = check_file(path)?;
+        if found.is_empty() {
+            println!("{}: ok", path.display());
+        }
+        for problem in &found {
+            println!(
+                "{}:{}:{}: {}",
+                path.display(),
+                problem.line,
+                problem.column,
+                problem.message
+            );
+        }
+        problems += found.len();
+    }
+
+    let environment = config::environment_table()?;
+    let found = check_environment(&environment);
+    if !environment.is_empty() && found.is_empty() {
+        println!("environment: ok");
+    }
+    for (variable, message) in &found {
+        println!("{}: {}", variable, message);
+    }
+    problems += found.len();
+
+    match problems {
+        0 => Ok(()),
+        count => Err(Error::Config(format!("{} found", plural(count, "problem")))),
+    }
+}
+
+fn check_file(path: &Path) -> Result<Vec<Problem>, Error> {
+    let content = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+    let file: toml::Table = match toml::from_str(&content) {
+        Ok(file) => file,
+        Err(err) => return Ok(vec![at_span(&content, err.span(), err.message())]),
+    };
+
+    let mut problems = Vec::new();
+    for (name, value) in &file {
+        if name != "default" {
+            problems.push(at_key(&content, name, unknown_key(name, &["default"])));
+            continue;
+        }
+        let Some(default) = value.as_table() else {
+            problems.push(at_key(
+                &content,
+                name,
+                "`default` must be a table".to_string(),
+            ));
+            continue;
+        };
+        let names: Vec<_> = KEYS.iter().map(|key| key.name).collect();
+        for name in default.keys() {
+            if !names.contains(&name.as_str()) {
+                problems.push(at_key(&content, name, unknown_key(name, &names)));
+            }
+        }
+    }
+
+    // Types and the values of enumerations are checked by reading the file
+    if let Err(err) = toml::from_str::<Config>(&content) {
+        problems.push(at_span(&content, err.span(), err.message()));
+    }
+
+    let default = file.get("default").and_then(toml::Value::as_table);
+    let setting = |name: &str| default?.get(name)?.as_str();
+    if let Some(action) = setting("action").filter(|action| !["copy", "save"].contains(action)) {
+        problems.push(at_key(
+            &content,
+            "action",
+            format!("unknown action `{}`; expected `copy` or `save`", action),
+        ));
+    }
+    match setting("clipboard") {
+        None | Some("auto") => {}
+        Some("command") if setting("clipboard_command").is_none() => problems.push(at_key(
+            &content,
+            "clipboard",
+            "the `command` clipboard needs `clipboard_command`".to_string(),
+        )),
+        Some("command") => {}
+        Some(backend) => {
+            if let Err(message) = backend.parse::<ClipboardBackend>() {
+                problems.push(at_key(&content, "clipboard", message));
+            }
+        }
+    }
+    problems.sort_by_key(|problem| (problem.line, problem.column));
+    Ok(problems)
+}
+
+// The problems of each `PCC_*` variable, by name
+fn check_environment(environment: &toml::Table) -> Vec<(String, String)> {
+    let names: Vec<_> = KEYS.iter().map(|key| key.name).collect();
+    let mut problems = Vec::new();
+    for (name, value) in environment {
+        let variable = config::environment_variable(name);
+        if !names.contains(&name.as_str()) {
+            let message = match closest(name, &names) {
+                Some(candidate) => format!(
+                    "unknown variable; did you mean `{}`?",
+                    config::environment_variable(candidate)
+                ),
+                None => "unknown variable".to_string(),
+            };
+            problems.push((variable, message));
+            continue;
+        }
+
+        let mut default = toml::Table::new();
+        default.insert(name.clone(), value.clone());
+        let mut file = toml::Table::new();
+        file.insert("default".to_string(), toml::Value::Table(default));
+        if let Err(err) = file.try_into::<Config>() {
+            problems.push((variable, err.message().trim().to_string()));
+        }
+    }
+    problems
+}
+
+fn at_span(content: &str, span: Option<Range<usize>>, message: &str) -> Problem {
+    let offset = span.map_or(0, |span| span.start).min(content.len());
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    Problem {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+        message: message.trim().to_string(),
+    }
+}
+
+// Positions a problem at the line setting `key`, or its table header
+fn at_key(content: &str, key: &str, message: String) -> Problem {
+    let position = content.lines().enumerate().find_map(|(index, line)| {
+        let trimmed = line.trim_start();
+        let rest = trimmed
+            .strip_prefix(key)
+            .or_else(|| trimmed.strip_prefix(&format!("\"{}\"", key)))
+            .or_else(|| trimmed.strip_prefix(&format!("[{}", key)))
+            .or_else(|| trimmed.strip_prefix(&format!("[default.{}", key)))?;
+        let rest = rest.trim_start();
+        (rest.starts_with('=') || rest.starts_with(']'))
+            .then(|| (index + 1, line.len() - trimmed.len() + 1))
+    });
+    let (line, column) = position.unwrap_or((1, 1));
+    Problem {
+        line,
+        column,
+        message,
+    }
+}
+
+fn unknown_key(name: &str, known: &[&str]) -> String {
+    match closest(name, known) {
+        Some(candidate) => format!("unknown key `{}`; did you mean `{}`?", name, candidate),
+        None => format!("unknown key `{}`", name),
+    }
+}
+
+// The known name `name` is most likely a misspelling of, if any
+fn closest<'a>(name: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), *candidate))
+        .min()
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(2))
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Prints the value of every key that a run would use, and where it comes
+/// from.
+pub fn show(args: &Args) -> Result<(), Error> {
+    let width = KEYS.iter().map(|key| key.name.len()).max().unwrap_or(0);
+    for (name, value, source) in settings(args, &Config::layers()?) {
+        println!(
+            "{:<width$} = {:<30}  # {}",
+            name,
+            value,
+            source,
+            width = width
+        );
+    }
+    Ok(())
+}
+
+// Every key with its value and source, given the configuration layers from
+// the lowest precedence to the highest
+fn settings(args: &Args, layers: &[(Source, toml::Table)]) -> Vec<(&'static str, String, String)> {
+    let mut settings = Vec::new();
+    for key in KEYS {
+        let from_files = layers
+            .iter()
+            .rev()
+            .find_map(|(source, table)| Some((table.get(key.name)?, source)));
+        let (value, source) = match (
+            command_line_value(args, key.name, from_files.map(|(value, _)| value)),
+            from_files,
+        ) {
+            (Some(value), _) => (value.to_string(), Source::CommandLine.to_string()),
+            (None, Some((value, Source::Environment))) => (
+                value.to_string(),
+                format!("environment {}", config::environment_variable(key.name)),
+            ),
+            (None, Some((value, source))) => (value.to_string(), source.to_string()),
+            (None, None) if key.default.is_empty() => {
+                ("(not set)".to_string(), Source::Default.to_string())
+            }
+            (None, None) => (key.default.to_string(), Source::Default.to_string()),
+        };
+        settings.push((key.name, value, source));
+    }
+    settings
+}
+
+// The value the command line gives `name`, if any. Ignore patterns and
+// section policies from the command line are added to `from_files`.
+fn command_line_value(
+    args: &Args,
+    name: &str,
+    from_files: Option<&toml::Value>,
+) -> Option<toml::Value> {
+    let flag = |enabled: bool| enabled.then_some(toml::Value::Boolean(true));
+    let policy = |policy: ErrorPolicy| toml::Value::try_from(policy).ok();
+
+    match name {
+        "action" => match (args.copy, args.save) {
+            (true, _) => Some("copy".into()),
+            (false, true) => Some("save".into()),
+            (false, false) => None,
+        },
+        "output_path" => args.output_path.clone().map(Into::into),
+        "use_relative_paths" => args.relative_flag().map(Into::into),
+        // `--relative` and `--no-relative` also take precedence over `paths`
+        "paths" => match (args.path_style, args.relative_flag()) {
+            (Some(style), _) => toml::Value::try_from(style).ok(),
+            (None, Some(true)) => Some("relative".into()),
+            (None, Some(false)) => Some("absolute".into()),
+            (None, None) => None,
+        },
+        "root" => args
+            .root
+            .as_ref()
+            .map(|root| root.display().to_string().into()),
+        "path_prefix" => args.path_prefix.clone().map(Into::into),
+        "ignore_patterns" if !args.ignore_patterns.is_empty() => {
+            let mut patterns = from_files
+                .and_then(toml::Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            patterns.extend(
+                args.ignore_patterns
+                    .iter()
+                    .map(|pattern| pattern.as_str().into()),
+            );
+            Some(toml::Value::Array(patterns))
+        }
+        "deps" => flag(args.deps),
+        "skip_type_imports" => flag(args.skip_type_imports),
+        "skip_dynamic_imports" => flag(args.skip_dynamic_imports),
+        "include_assets" => flag(args.assets),
+        "external_types" => flag(args.external_types),
+        "line_numbers" => flag(args.line_numbers),
+        "cache" => args.no_cache.then_some(toml::Value::Boolean(false)),
+        "cache_resolutions" => flag(args.cache_resolutions),
+        "split_tokens" => args.split_tokens.map(|tokens| (tokens as i64).into()),
+        "split_bytes" => args.split_bytes.map(|bytes| (bytes as i64).into()),
+        "clipboard" => args.clipboard.clone().map(Into::into),
+        "on_error" => args
+            .on_error
+            .iter()
+            .filter(|(section, _)| section.is_none())
+            .map(|(_, policy)| *policy)
+            .next_back()
+            .or(args.keep_going.then_some(ErrorPolicy::Skip))
+            .and_then(policy),
+        "on_error_sections" => {
+            let mut sections = from_files
+                .and_then(toml::Value::as_table)
+                .cloned()
+                .unwrap_or_default();
+            let mut given = false;
+            for (section, section_policy) in &args.on_error {
+                let (Some(section), Some(section_policy)) = (
+                    section.and_then(|section| toml::Value::try_from(section).ok()),
+                    policy(*section_policy),
+                ) else {
+                    continue;
+                };
+                sections.insert(section.as_str()?.to_string(), section_policy);
+                given = true;
+            }
+            given.then_some(toml::Value::Table(sections))
+        }
+        _ => None,
+    }
+}
+
+/// Writes a commented configuration file with every key, to the home
+/// directory or, with `project`, the working directory.
+pub fn init(project: bool, force: bool) -> Result<(), Error> {
+    let path = if project {
+        Config::project_path()?
+    } else {
+        Config::path()?
+    };
+    write_starter(&path, force)?;
+    println!("Configuration written to {}", path.display());
+    Ok(())
+}
+
+fn write_starter(path: &Path, force: bool) -> Result<(), Error> {
+    if path.exists() && !force {
+        return Err(Error::Config(format!(
+            "{} already exists; pass --force to overwrite it",
+            path.display()
+        )));
+    }
+    fs::write(path, starter()).map_err(|source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn starter() -> String {
+    let mut content = String::from(
+        "# Configuration of pcc. Options given on the command line take precedence,\n\
+         # and a .pcc_config.toml in the project overrides the keys it sets.\n\
+         # Uncomment a key to change it.\n\n[default]\n",
+    );
+    for key in KEYS {
+        content.push_str(&format!("# {}\n", key.description));
+        // A run needs an action
+        let comment = if key.name == "action" { "" } else { "# " };
+        content.push_str(&format!("{}{} = {}\n\n", comment, key.name, key.example));
+    }
+    content.pop();
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use serde::de::{self, Deserialize, Visitor};
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    // Records the fields serde expects of a struct, without reading anything
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(
+            self,
+            _: V,
+        ) -> std::result::Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> std::result::Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    fn key_names() -> BTreeSet<&'static str> {
+        KEYS.iter().map(|key| key.name).collect()
+    }
+
+    fn problems(content: &str) -> Vec<(usize, usize, String)> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, content).unwrap();
+        check_file(&path)
+            .unwrap()
+            .into_iter()
+            .map(|problem| (problem.line, problem.column, problem.message))
+            .collect()
+    }
+
+    // The starter file with every key set to its example
+    fn uncommented_starter() -> String {
+        let mut content = starter();
+        for key in KEYS {
+            let line = format!("{} = {}\n", key.name, key.example);
+            content = content.replace(&format!("# {}", line), &line);
+        }
+        content
+    }
+
+    #[test]
+    fn keys_match_the_default_table() {
+        let mut fields: &'static [&'static str] = &[];
+        let _ = config::Default::deserialize(FieldNames(&mut fields));
+        assert_eq!(key_names(), fields.iter().copied().collect());
+    }
+
+    #[test]
+    fn non_string_keys_are_the_keys_without_string_examples() {
+        let non_string: BTreeSet<_> = KEYS
+            .iter()
+            .filter(|key| !key.example.starts_with('"'))
+            .map(|key| key.name)
+            .collect();
+        assert_eq!(
+            non_string,
+            config::NON_STRING_KEYS.iter().copied().collect()
+        );
+        for key in KEYS.iter().filter(|key| !key.default.is_empty()) {
+            assert_eq!(
+                key.default.starts_with('"'),
+                key.example.starts_with('"'),
+                "{}",
+                key.name
+            );
+        }
+    }
+
+    #[test]
+    fn every_key_written_by_init_parses() {
+        let content = uncommented_starter();
+        for key in KEYS {
+            assert!(
+                content.contains(&format!("\n{} = ", key.name)),
+                "{} is not in the starter file",
+                key.name
+            );
+        }
+        let config: Config = toml::from_str(&content).unwrap();
+        assert_eq!(config.default.action.as_deref(), Some("copy"));
+        assert_eq!(config.default.split_tokens, Some(30000));
+        assert!(problems(&content).is_empty());
+        // As written, only the action is set
+        assert!(problems(&starter()).is_empty());
+    }
+
+    #[test]
+    fn init_does_not_overwrite_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".pcc_config.toml");
+        write_starter(&path, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), starter());
+
+        fs::write(&path, "[default]\n").unwrap();
+        assert!(matches!(write_starter(&path, false), Err(Error::Config(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[default]\n");
+        write_starter(&path, true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), starter());
+    }
+
+    #[test]
+    fn check_reports_positions_and_suggestions() {
+        assert_eq!(
+            problems("[default]\ndeps = true\n  actoin = \"copy\"\n\n[defualt]\n"),
+            [
+                (
+                    3,
+                    3,
+                    "unknown key `actoin`; did you mean `action`?".to_string()
+                ),
+                (
+                    5,
+                    1,
+                    "unknown key `defualt`; did you mean `default`?".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            problems("[default]\ndeps = \"yes\"\nclipboard = \"pbcopy\"\n"),
+            [
+                (
+                    2,
+                    8,
+                    "invalid type: string \"yes\", expected a boolean".to_string()
+                ),
+                (
+                    3,
+                    1,
+                    "unknown clipboard `pbcopy`; expected `auto`, `x11`, `wayland`, `osc52` or `command`"
+                        .to_string()
+                ),
+            ]
+        );
+        let found = problems("[default]\nsplit_bytes = 10\ndeps = tru\n");
+        assert_eq!((found[0].0, found[0].1), (3, 8));
+        assert_eq!(problems("[default]\nsplit_bytes = 10\n"), []);
+    }
+
+    #[test]
+    fn check_suggests_environment_variables() {
+        let environment: toml::Table =
+            toml::from_str("dep = true\nsplit_bytes = \"many\"\nzzz = 1\n").unwrap();
+        let found = check_environment(&environment);
+        assert_eq!(found.len(), 3);
+        assert_eq!(
+            found[0],
+            (
+                "PCC_DEP".to_string(),
+                "unknown variable; did you mean `PCC_DEPS`?".to_string()
+            )
+        );
+        assert_eq!(found[1].0, "PCC_SPLIT_BYTES");
+        assert_eq!(
+            found[2],
+            ("PCC_ZZZ".to_string(), "unknown variable".to_string())
+        );
+    }
+
+    #[test]
+    fn show_names_where_each_value_comes_from() {
+        let table = |content: &str| toml::from_str::<toml::Table>(content).unwrap();
+        let layers = [
+            (
+                Source::GlobalFile(PathBuf::from("/home/me/.pcc_config.toml")),
+                table("deps = false\nline_numbers = true\ncache = false\nignore_patterns = [\"*.log\"]"),
+            ),
+            (
+                Source::ProjectFile(PathBuf::from("/app/.pcc_config.toml")),
+                table("line_numbers = false"),
+            ),
+            (Source::Environment, table("cache = true")),
+        ];
+        let args = Args::try_parse_from(["pcc", "--deps", "--ignore", "dist"]).unwrap();
+        let settings = settings(&args, &layers);
+        let setting = |name: &str| {
+            settings
+                .iter()
+                .find(|(key, _, _)| *key == name)
+                .map(|(_, value, source)| (value.as_str(), source.as_str()))
+                .unwrap()
+        };
+
+        assert_eq!(settings.len(), KEYS.len());
+        assert_eq!(setting("deps"), ("true", "command line"));
+        assert_eq!(
+            setting("ignore_patterns"),
+            ("[\"*.log\", \"dist\"]", "command line")
+        );
+        assert_eq!(
+            setting("line_numbers"),
+            ("false", "project file /app/.pcc_config.toml")
+        );
+        assert_eq!(setting("cache"), ("true", "environment PCC_CACHE"));
+        assert_eq!(
+            setting("output_file_name"),
+            ("\"combined_code.txt\"", "default")
+        );
+        assert_eq!(setting("root"), ("(not set)", "default"));
+    }
+}
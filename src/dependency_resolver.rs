@@ -1,19 +1,114 @@
-use serde_json::Value;
 use std::any::Any;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
+use serde::{Deserialize, Serialize};
+
+use crate::cache::ParseCache;
+use crate::stylesheet_resolver::StylesheetResolver;
+use crate::tsconfig::{TsConfig, TsConfigLoader};
 use crate::typescript_resolver::TypeScriptResolver;
+use crate::workspace::Workspace;
 
 #[derive(Debug)]
 pub struct DependencyResolver {
     base_path: PathBuf,
-    alias_map: Option<HashMap<String, String>>,
+    tsconfigs: Option<TsConfigLoader>,
+    workspace: Option<Workspace>,
+    stylesheet_resolver: StylesheetResolver,
+    cache: ParseCache,
     resolved_files: HashSet<PathBuf>,
     dependency_graph: HashMap<PathBuf, HashSet<PathBuf>>,
-    processing_stack: Vec<PathBuf>,
+    import_kinds: HashMap<(PathBuf, PathBuf), ImportKind>,
+    parse_errors: HashMap<PathBuf, Vec<ParseIssue>>,
+    external_files: HashSet<PathBuf>,
+    unresolved: BTreeSet<UnresolvedImport>,
+    package_imports: BTreeSet<(PathBuf, String)>,
+    skip_type_only: bool,
+    skip_dynamic: bool,
+    include_assets: bool,
+}
+
+/// How a file refers to one of its dependencies.
+///
+/// Ordered from strongest to weakest, so that when a file imports the same
+/// module in several ways the strongest kind describes the edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportKind {
+    /// `import`, `export ... from`, `require()` and test mocks
+    Runtime,
+    /// `import()` expressions that load the module lazily
+    Dynamic,
+    /// `import type`, type-only specifiers and triple-slash references
+    TypeOnly,
+}
+
+impl ImportKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportKind::Runtime => "runtime",
+            ImportKind::Dynamic => "dynamic",
+            ImportKind::TypeOnly => "type",
+        }
+    }
+}
+
+/// A syntax error found while extracting imports. Imports after it may be missing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseIssue {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// An import that did not lead to a file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct UnresolvedImport {
+    /// The importing file
+    pub from: PathBuf,
+    pub specifier: String,
+    pub reason: UnresolvedReason,
+}
+
+/// Why an import could not be resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnresolvedReason {
+    /// A relative or absolute path, or a workspace package path, naming no file
+    MissingFile,
+    /// A tsconfig `paths` alias whose targets do not exist, or a specifier
+    /// such as `@/x` or `~/x` that no alias matches
+    UnknownAlias,
+    /// A package that is not installed, or a Node.js built-in module
+    ExternalPackage,
+}
+
+impl UnresolvedReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnresolvedReason::MissingFile => "missing_file",
+            UnresolvedReason::UnknownAlias => "unknown_alias",
+            UnresolvedReason::ExternalPackage => "external_package",
+        }
+    }
+}
+
+/// The imports of a file, along with any syntax errors that made them incomplete.
+#[derive(Debug, Default)]
+pub struct ParsedImports {
+    pub imports: Vec<Import>,
+    pub errors: Vec<ParseIssue>,
+}
+
+/// A module specifier found in a source file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Import {
+    pub specifier: String,
+    pub kind: ImportKind,
 }
 
 pub trait LanguageResolver: Any {
@@ -24,62 +119,82 @@ pub trait LanguageResolver: Any {
         self as &dyn Any
     }
 
-    fn get_imports(&mut self, content: &str) -> Vec<String>;
+    fn get_imports(&mut self, file_path: &Path, content: &str) -> ParsedImports;
 }
 
 impl DependencyResolver {
     pub fn new(project_root: &Path, load_aliases: bool) -> io::Result<Self> {
-        let alias_map = if load_aliases {
-            match Self::load_tsconfig_aliases(&project_root.join("tsconfig.json")) {
-                Ok(aliases) => Some(aliases),
-                Err(_) => None,
-            }
+        let tsconfigs = if load_aliases {
+            Some(TsConfigLoader::new())
         } else {
             None
         };
 
         Ok(Self {
             base_path: project_root.to_path_buf(),
-            alias_map,
+            tsconfigs,
+            workspace: Workspace::discover(project_root),
+            stylesheet_resolver: StylesheetResolver::new(),
+            cache: ParseCache::disabled(),
             resolved_files: HashSet::new(),
             dependency_graph: HashMap::new(),
-            processing_stack: Vec::new(),
+            import_kinds: HashMap::new(),
+            parse_errors: HashMap::new(),
+            external_files: HashSet::new(),
+            unresolved: BTreeSet::new(),
+            package_imports: BTreeSet::new(),
+            skip_type_only: false,
+            skip_dynamic: false,
+            include_assets: false,
         })
     }
 
-    fn load_tsconfig_aliases(tsconfig_path: &Path) -> io::Result<HashMap<String, String>> {
-        if !tsconfig_path.exists() {
-            return Ok(HashMap::new());
-        }
-
-        let content = fs::read_to_string(tsconfig_path)?;
-
-        match serde_json::from_str::<Value>(&content) {
-            Ok(config) => {
-                let mut alias_map = HashMap::new();
-
-                if let Some(compiler_options) = config.get("compilerOptions") {
-                    if let Some(paths) = compiler_options.get("paths") {
-                        if let Some(paths_obj) = paths.as_object() {
-                            for (alias, targets) in paths_obj {
-                                if let Some(target) = targets.get(0) {
-                                    if let Some(target_str) = target.as_str() {
-                                        let clean_alias = alias.trim_end_matches("/*");
-                                        let clean_target = target_str.trim_end_matches("/*");
-                                        alias_map.insert(
-                                            clean_alias.to_string(),
-                                            clean_target.to_string(),
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+    /// Chooses which kinds of imports are not followed when resolving.
+    pub fn set_skipped_imports(&mut self, skip_type_only: bool, skip_dynamic: bool) {
+        self.skip_type_only = skip_type_only;
+        self.skip_dynamic = skip_dynamic;
+    }
 
-                Ok(alias_map)
-            }
-            Err(_) => Ok(HashMap::new()),
+    /// Follows imported stylesheets and other non-script files such as JSON
+    /// or images, which are otherwise left out.
+    pub fn set_include_assets(&mut self, include_assets: bool) {
+        self.include_assets = include_assets;
+    }
+
+    /// Reuses imports and resolutions stored by earlier runs in `cache`.
+    pub fn set_cache(&mut self, cache: ParseCache) {
+        self.cache = cache;
+    }
+
+    /// Writes the cache back to disk, along with the config files its
+    /// resolutions depend on.
+    pub fn save_cache(&mut self) -> io::Result<()> {
+        for config_file in self.config_files() {
+            self.cache.track_config_file(&config_file);
+        }
+
+        self.cache.save()
+    }
+
+    /// The tsconfig files loaded so far and the workspace manifests, which
+    /// decide how imports resolve.
+    pub fn config_files(&self) -> Vec<PathBuf> {
+        let mut config_files = self
+            .tsconfigs
+            .as_ref()
+            .map(TsConfigLoader::loaded_files)
+            .unwrap_or_default();
+        if let Some(workspace) = &self.workspace {
+            config_files.extend(workspace.manifest_files());
+        }
+        config_files
+    }
+
+    fn is_skipped(&self, kind: ImportKind) -> bool {
+        match kind {
+            ImportKind::Runtime => false,
+            ImportKind::Dynamic => self.skip_dynamic,
+            ImportKind::TypeOnly => self.skip_type_only,
         }
     }
 
@@ -88,14 +203,19 @@ impl DependencyResolver {
         entry_file: &Path,
         resolver: &mut T,
     ) -> io::Result<Vec<PathBuf>> {
-        self.processing_stack.clear();
         self.dependency_graph.clear();
+        self.import_kinds.clear();
+        self.parse_errors.clear();
+        self.external_files.clear();
+        self.unresolved.clear();
+        self.package_imports.clear();
         self.resolved_files.clear();
 
-        self.resolve_deps_recursive(entry_file, resolver)?;
+        let entry_file = canonicalize_path(entry_file);
+        self.resolve_deps_from(&entry_file, resolver)?;
 
         let mut all_files: HashSet<PathBuf> = HashSet::new();
-        let mut stack = vec![entry_file.to_path_buf()];
+        let mut stack = vec![entry_file];
 
         while let Some(current) = stack.pop() {
             if all_files.insert(current.clone()) {
@@ -108,50 +228,147 @@ impl DependencyResolver {
         Ok(all_files.into_iter().collect())
     }
 
-    fn resolve_deps_recursive<T: LanguageResolver>(
+    /// Resolves the dependencies of several entry files into one graph, so
+    /// that importers can be looked up across all of them.
+    pub fn resolve_all<T: LanguageResolver>(
         &mut self,
-        current_file: &Path,
+        entry_files: &[PathBuf],
         resolver: &mut T,
     ) -> io::Result<()> {
-        if self.processing_stack.contains(&current_file.to_path_buf()) {
-            println!(
-                "Warning: Circular dependency detected for file: {}",
-                current_file.display()
-            );
-            return Ok(());
+        self.dependency_graph.clear();
+        self.import_kinds.clear();
+        self.parse_errors.clear();
+        self.external_files.clear();
+        self.unresolved.clear();
+        self.package_imports.clear();
+        self.resolved_files.clear();
+
+        for entry_file in entry_files {
+            self.resolve_deps_from(&canonicalize_path(entry_file), resolver)?;
         }
+        Ok(())
+    }
+
+    /// Walks every file reachable from `entry_file` with an explicit work
+    /// list, so that long import chains cannot overflow the stack.
+    fn resolve_deps_from<T: LanguageResolver>(
+        &mut self,
+        entry_file: &Path,
+        resolver: &mut T,
+    ) -> io::Result<()> {
+        let mut pending = vec![entry_file.to_path_buf()];
+        while let Some(current_file) = pending.pop() {
+            // Edges closing a cycle are already recorded in `dependency_graph`;
+            // cycles are reported afterwards by `find_cycles`.
+            if self.resolved_files.insert(current_file.clone()) {
+                pending.extend(self.resolve_file_imports(&current_file, resolver)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the imports of one file and returns the files they lead to.
+    fn resolve_file_imports<T: LanguageResolver>(
+        &mut self,
+        current_file: &Path,
+        resolver: &mut T,
+    ) -> io::Result<Vec<PathBuf>> {
+        let mut dependencies = Vec::new();
+        let is_stylesheet = StylesheetResolver::is_stylesheet(current_file);
 
-        if self.resolved_files.contains(current_file) {
-            return Ok(());
+        // Assets and other files that cannot import anything are leaves
+        let parsed = if is_stylesheet || TypeScriptResolver::is_supported_file(current_file) {
+            match self.cache.cached_imports(current_file) {
+                Some(parsed) => parsed,
+                None => {
+                    // Files that cannot be read are reported when the
+                    // combiner reads them, and import nothing meanwhile
+                    let Ok(content) = fs::read_to_string(current_file) else {
+                        return Ok(dependencies);
+                    };
+                    let parsed = if is_stylesheet {
+                        self.stylesheet_resolver.get_imports(current_file, &content)
+                    } else {
+                        resolver.get_imports(current_file, &content)
+                    };
+                    self.cache.store_imports(current_file, &content, &parsed);
+                    parsed
+                }
+            }
+        } else {
+            ParsedImports::default()
+        };
+
+        if !parsed.errors.is_empty() {
+            self.parse_errors
+                .insert(current_file.to_path_buf(), parsed.errors);
         }
 
-        self.processing_stack.push(current_file.to_path_buf());
+        for import in parsed.imports {
+            if self.is_skipped(import.kind) {
+                continue;
+            }
 
-        let content = fs::read_to_string(current_file)?;
-        let imports = resolver.get_imports(&content);
+            let ts_resolver = resolver.as_any().downcast_ref::<TypeScriptResolver>();
+            let cached_path = self.cache.resolution(current_file, &import.specifier);
+            let resolved_path = if cached_path.is_some() {
+                cached_path
+            } else if is_stylesheet {
+                self.stylesheet_resolver
+                    .resolve_import(&import.specifier, current_file)
+            } else if let Some(ts_resolver) = ts_resolver {
+                ts_resolver.resolve_import_with_resolver(&import.specifier, current_file, self)
+            } else {
+                None
+            };
 
-        for import_path in imports {
-            if let Some(ts_resolver) = resolver.as_any().downcast_ref::<TypeScriptResolver>() {
-                if let Some(resolved_path) =
-                    ts_resolver.resolve_import_with_resolver(&import_path, current_file, self)
-                {
-                    if should_ignore_file(&resolved_path) {
-                        continue;
+            let Some(resolved_path) = resolved_path else {
+                // Stylesheets only list local references
+                let reason = match ts_resolver.filter(|_| !is_stylesheet) {
+                    Some(ts_resolver) => {
+                        ts_resolver.unresolved_reason(&import.specifier, current_file, self)
                     }
+                    None => UnresolvedReason::MissingFile,
+                };
+                if reason == UnresolvedReason::ExternalPackage {
+                    self.package_imports
+                        .insert((current_file.to_path_buf(), import.specifier.clone()));
+                }
+                self.unresolved.insert(UnresolvedImport {
+                    from: current_file.to_path_buf(),
+                    specifier: import.specifier,
+                    reason,
+                });
+                continue;
+            };
+            self.cache
+                .store_resolution(current_file, &import.specifier, &resolved_path);
 
-                    self.dependency_graph
-                        .entry(current_file.to_path_buf())
-                        .or_default()
-                        .insert(resolved_path.clone());
-
-                    self.resolve_deps_recursive(&resolved_path, resolver)?;
+            if should_ignore_file(&resolved_path) {
+                if !is_stylesheet && is_bare_specifier(&import.specifier) {
+                    self.package_imports
+                        .insert((current_file.to_path_buf(), import.specifier));
                 }
+                self.external_files.insert(resolved_path);
+                continue;
+            }
+            if !self.include_assets && !TypeScriptResolver::is_supported_file(&resolved_path) {
+                continue;
             }
+
+            self.dependency_graph
+                .entry(current_file.to_path_buf())
+                .or_default()
+                .insert(resolved_path.clone());
+
+            let edge = (current_file.to_path_buf(), resolved_path.clone());
+            let kind = self.import_kinds.entry(edge).or_insert(import.kind);
+            *kind = (*kind).min(import.kind);
+
+            dependencies.push(resolved_path);
         }
 
-        self.processing_stack.pop();
-        self.resolved_files.insert(current_file.to_path_buf());
-        Ok(())
+        Ok(dependencies)
     }
 
     pub fn get_all_importers(&self, file: &Path) -> HashSet<PathBuf> {
@@ -175,8 +392,205 @@ impl DependencyResolver {
         all_importers
     }
 
-    pub fn get_alias_map(&self) -> Option<&HashMap<String, String>> {
-        self.alias_map.as_ref()
+    /// Files imported directly by `file`, sorted.
+    pub fn get_dependencies(&self, file: &Path) -> Vec<PathBuf> {
+        let mut dependencies: Vec<_> = self
+            .dependency_graph
+            .get(file)
+            .map(|deps| deps.iter().cloned().collect())
+            .unwrap_or_default();
+        dependencies.sort();
+        dependencies
+    }
+
+    /// Files importing `file` directly, sorted.
+    pub fn get_direct_importers(&self, file: &Path) -> Vec<PathBuf> {
+        let mut importers: Vec<_> = self
+            .dependency_graph
+            .iter()
+            .filter(|(_, deps)| deps.contains(file))
+            .map(|(importer, _)| importer.clone())
+            .collect();
+        importers.sort();
+        importers
+    }
+
+    /// Files whose syntax errors may have hidden some of their imports.
+    pub fn get_parse_errors(&self) -> &HashMap<PathBuf, Vec<ParseIssue>> {
+        &self.parse_errors
+    }
+
+    /// Returns how `importer` imports `file`, if it does so directly.
+    pub fn get_import_kind(&self, importer: &Path, file: &Path) -> Option<ImportKind> {
+        self.import_kinds
+            .get(&(importer.to_path_buf(), file.to_path_buf()))
+            .copied()
+    }
+
+    /// Finds circular imports in the current dependency graph.
+    ///
+    /// Each strongly connected component with more than one file (or a file
+    /// importing itself) is reported once, as the shortest cycle path through
+    /// its smallest member. The path starts and ends with the same file.
+    pub fn find_cycles(&self) -> Vec<Vec<PathBuf>> {
+        let mut cycles: Vec<Vec<PathBuf>> = self
+            .strongly_connected_components()
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1
+                    || self
+                        .dependency_graph
+                        .get(&component[0])
+                        .is_some_and(|deps| deps.contains(&component[0]))
+            })
+            .filter_map(|component| self.shortest_cycle(&component))
+            .collect();
+
+        cycles.sort();
+        cycles
+    }
+
+    // Tarjan's algorithm, iterative to stay safe on deep import chains
+    fn strongly_connected_components(&self) -> Vec<Vec<PathBuf>> {
+        let mut nodes: Vec<&PathBuf> = self
+            .dependency_graph
+            .iter()
+            .flat_map(|(file, deps)| std::iter::once(file).chain(deps.iter()))
+            .collect();
+        nodes.sort();
+        nodes.dedup();
+
+        let mut sorted_edges: HashMap<&PathBuf, Vec<&PathBuf>> = HashMap::new();
+        for (file, deps) in &self.dependency_graph {
+            let mut deps: Vec<_> = deps.iter().collect();
+            deps.sort();
+            sorted_edges.insert(file, deps);
+        }
+
+        let mut index_of: HashMap<&PathBuf, usize> = HashMap::new();
+        let mut low_link: HashMap<&PathBuf, usize> = HashMap::new();
+        let mut on_stack: HashSet<&PathBuf> = HashSet::new();
+        let mut stack: Vec<&PathBuf> = Vec::new();
+        let mut components = Vec::new();
+        let mut next_index = 0;
+
+        for &root in &nodes {
+            if index_of.contains_key(root) {
+                continue;
+            }
+
+            // (node, position of the next edge to visit)
+            let mut work: Vec<(&PathBuf, usize)> = vec![(root, 0)];
+            while let Some((node, edge_pos)) = work.pop() {
+                if edge_pos == 0 {
+                    index_of.insert(node, next_index);
+                    low_link.insert(node, next_index);
+                    next_index += 1;
+                    stack.push(node);
+                    on_stack.insert(node);
+                }
+
+                let edges = sorted_edges.get(node).map(Vec::as_slice).unwrap_or(&[]);
+                if let Some(&next) = edges.get(edge_pos) {
+                    work.push((node, edge_pos + 1));
+                    if !index_of.contains_key(next) {
+                        work.push((next, 0));
+                    } else if on_stack.contains(next) {
+                        let low = low_link[node].min(index_of[next]);
+                        low_link.insert(node, low);
+                    }
+                    continue;
+                }
+
+                if low_link[node] == index_of[node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack.remove(member);
+                        component.push(member.clone());
+                        if member == node {
+                            break;
+                        }
+                    }
+                    component.sort();
+                    components.push(component);
+                }
+
+                if let Some(&(parent, _)) = work.last() {
+                    let low = low_link[parent].min(low_link[node]);
+                    low_link.insert(parent, low);
+                }
+            }
+        }
+
+        components
+    }
+
+    fn shortest_cycle(&self, component: &[PathBuf]) -> Option<Vec<PathBuf>> {
+        let start = component.first()?;
+        let members: HashSet<&PathBuf> = component.iter().collect();
+        let mut previous: HashMap<&PathBuf, &PathBuf> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(current) = queue.pop_front() {
+            let mut deps: Vec<_> = self
+                .dependency_graph
+                .get(current)
+                .into_iter()
+                .flatten()
+                .filter(|dep| members.contains(dep))
+                .collect();
+            deps.sort();
+
+            for dep in deps {
+                if dep == start {
+                    let mut chain = Vec::new();
+                    let mut node = current;
+                    while node != start {
+                        chain.push(node.clone());
+                        node = previous[node];
+                    }
+                    chain.reverse();
+
+                    let mut path = vec![start.clone()];
+                    path.extend(chain);
+                    path.push(start.clone());
+                    return Some(path);
+                }
+                if !previous.contains_key(dep) {
+                    previous.insert(dep, current);
+                    queue.push_back(dep);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the tsconfig governing `file`, if alias loading is enabled.
+    pub fn get_tsconfig(&self, file: &Path) -> Option<Rc<TsConfig>> {
+        self.tsconfigs.as_ref()?.config_for_file(file)
+    }
+
+    /// Files in `node_modules` imported by the last resolved files, which are
+    /// never followed.
+    pub fn get_external_files(&self) -> &HashSet<PathBuf> {
+        &self.external_files
+    }
+
+    /// Imports of the last resolved files that could not be resolved, with
+    /// the file importing them.
+    pub fn get_unresolved(&self) -> &BTreeSet<UnresolvedImport> {
+        &self.unresolved
+    }
+
+    /// Imports of installed or missing packages by the last resolved files,
+    /// with the file importing them.
+    pub fn get_package_imports(&self) -> &BTreeSet<(PathBuf, String)> {
+        &self.package_imports
+    }
+
+    pub fn get_workspace(&self) -> Option<&Workspace> {
+        self.workspace.as_ref()
     }
 
     pub fn get_base_path(&self) -> &Path {
@@ -184,6 +598,118 @@ impl DependencyResolver {
     }
 }
 
+/// Resolves symlinks so a file reached through different paths (for example
+/// a workspace package linked into `node_modules`) is only seen once.
+pub fn canonicalize_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
 fn should_ignore_file(path: &Path) -> bool {
     path.to_string_lossy().contains("node_modules")
 }
+
+// Package names, as opposed to relative and absolute paths
+fn is_bare_specifier(specifier: &str) -> bool {
+    !specifier.starts_with('.') && !Path::new(specifier).is_absolute()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver_with_edges(edges: &[(&str, &str)]) -> DependencyResolver {
+        let mut resolver = DependencyResolver::new(Path::new("/nonexistent"), false).unwrap();
+        for (from, to) in edges {
+            resolver
+                .dependency_graph
+                .entry(PathBuf::from(from))
+                .or_default()
+                .insert(PathBuf::from(to));
+        }
+        resolver
+    }
+
+    fn paths(cycles: Vec<Vec<PathBuf>>) -> Vec<Vec<String>> {
+        cycles
+            .into_iter()
+            .map(|cycle| {
+                cycle
+                    .iter()
+                    .map(|file| file.display().to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn acyclic_graphs_have_no_cycles() {
+        let resolver = resolver_with_edges(&[("a", "b"), ("b", "c"), ("a", "c")]);
+        assert!(resolver.find_cycles().is_empty());
+    }
+
+    #[test]
+    fn self_imports_are_cycles() {
+        let resolver = resolver_with_edges(&[("a", "a"), ("a", "b")]);
+        assert_eq!(paths(resolver.find_cycles()), [["a", "a"]]);
+    }
+
+    #[test]
+    fn each_component_is_reported_once_from_its_smallest_file() {
+        let resolver = resolver_with_edges(&[
+            ("c", "a"),
+            ("a", "b"),
+            ("b", "c"),
+            ("c", "d"),
+            ("x", "y"),
+            ("y", "x"),
+            ("d", "x"),
+        ]);
+        assert_eq!(
+            paths(resolver.find_cycles()),
+            vec![vec!["a", "b", "c", "a"], vec!["x", "y", "x"]]
+        );
+    }
+
+    #[test]
+    fn the_shortest_cycle_of_a_component_is_reported() {
+        let resolver = resolver_with_edges(&[("a", "b"), ("b", "c"), ("c", "a"), ("b", "a")]);
+        assert_eq!(paths(resolver.find_cycles()), [["a", "b", "a"]]);
+    }
+
+    #[test]
+    fn long_chains_do_not_overflow_the_stack() {
+        let names: Vec<String> = (0..20_000).map(|i| format!("{:06}", i)).collect();
+        let mut edges: Vec<(&str, &str)> = names
+            .windows(2)
+            .map(|pair| (pair[0].as_str(), pair[1].as_str()))
+            .collect();
+        edges.push((names[names.len() - 1].as_str(), names[0].as_str()));
+        let resolver = resolver_with_edges(&edges);
+
+        let cycles = resolver.find_cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].len(), names.len() + 1);
+    }
+
+    #[test]
+    fn long_import_chains_are_resolved_without_recursion() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let count = 5_000;
+        for i in 0..count {
+            let content = if i + 1 < count {
+                format!("import './m{}';\n", i + 1)
+            } else {
+                "import './m0';\n".to_string()
+            };
+            fs::write(root.join(format!("m{}.ts", i)), content).unwrap();
+        }
+
+        let mut resolver = DependencyResolver::new(&root, false).unwrap();
+        let files = resolver
+            .resolve_deps(&root.join("m0.ts"), &mut TypeScriptResolver::new())
+            .unwrap();
+        assert_eq!(files.len(), count);
+        assert_eq!(resolver.find_cycles().len(), 1);
+    }
+}
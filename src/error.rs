@@ -0,0 +1,287 @@
+use crate::dependency_resolver::UnresolvedImport;
+use serde_json::{json, Value};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Errors returned while combining a project or delivering the output.
+#[derive(Debug)]
+pub enum Error {
+    /// The command line is incomplete in a way its parser cannot tell
+    Usage(String),
+    /// The combiner was configured inconsistently, or the configuration file
+    /// is invalid
+    Config(String),
+    /// An ignore pattern cannot be matched against paths
+    IgnorePattern { pattern: String, message: String },
+    /// A file or directory could not be read
+    Io {
+        /// The path being read, when known
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// A file could not be read as text
+    Parse { path: PathBuf, message: String },
+    /// Imports point to missing files or unknown aliases, with `--strict-deps`
+    Unresolved(Vec<UnresolvedImport>),
+    /// Circular dependencies were found, with `--fail-on-cycles`
+    Cycles(Vec<Vec<PathBuf>>),
+    /// The output file could not be written
+    Write { path: PathBuf, source: io::Error },
+    /// The system clipboard is unavailable or rejected the output
+    Clipboard(String),
+    /// The project could not be serialized to JSON
+    Json(serde_json::Error),
+}
+
+/// The category of an `Error`, which decides the exit status of `pcc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Exit status 1: a check such as `--fail-on-cycles` failed
+    Check,
+    /// Exit status 2, shared with command line parsing errors
+    Usage,
+    /// Exit status 3
+    Config,
+    /// Exit status 4
+    IgnorePattern,
+    /// Exit status 5
+    Io,
+    /// Exit status 6
+    Parse,
+    /// Exit status 7
+    Resolution,
+    /// Exit status 8: writing, copying or serializing the output failed
+    Output,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl ErrorKind {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::Check => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Config => 3,
+            ErrorKind::IgnorePattern => 4,
+            ErrorKind::Io => 5,
+            ErrorKind::Parse => 6,
+            ErrorKind::Resolution => 7,
+            ErrorKind::Output => 8,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Check => "check",
+            ErrorKind::Usage => "usage",
+            ErrorKind::Config => "config",
+            ErrorKind::IgnorePattern => "ignore_pattern",
+            ErrorKind::Io => "io",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Resolution => "resolution",
+            ErrorKind::Output => "output",
+        }
+    }
+}
+
+impl Error {
+    /// An error reading `path`; contents that are not valid UTF-8 are
+    /// reported as a parse error.
+    pub fn io(path: &Path, err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::InvalidData {
+            return Error::Parse {
+                path: path.to_path_buf(),
+                message: err.to_string(),
+            };
+        }
+        Error::Io {
+            path: Some(path.to_path_buf()),
+            source: err,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Usage(_) => ErrorKind::Usage,
+            Error::Config(_) => ErrorKind::Config,
+            Error::IgnorePattern { .. } => ErrorKind::IgnorePattern,
+            Error::Io { .. } => ErrorKind::Io,
+            Error::Parse { .. } => ErrorKind::Parse,
+            Error::Unresolved(_) => ErrorKind::Resolution,
+            Error::Cycles(_) => ErrorKind::Check,
+            Error::Write { .. } | Error::Clipboard(_) | Error::Json(_) => ErrorKind::Output,
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.kind().exit_code()
+    }
+
+    /// The path the error is about, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Io { path, .. } => path.as_deref(),
+            Error::Parse { path, .. } | Error::Write { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Describes the error for tools, as printed by `--error-format json`.
+    pub fn to_json(&self) -> Value {
+        let mut value = json!({
+            "kind": self.kind().as_str(),
+            "exit_code": self.exit_code(),
+            "message": self.to_string(),
+        });
+        if let Some(path) = self.path() {
+            value["path"] = json!(path);
+        }
+        match self {
+            Error::IgnorePattern { pattern, .. } => value["pattern"] = json!(pattern),
+            Error::Unresolved(imports) => value["unresolved"] = json!(imports),
+            Error::Cycles(cycles) => value["cycles"] = json!(cycles),
+            _ => {}
+        }
+        value
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io {
+            path: None,
+            source: err,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(msg) => write!(f, "{}", msg),
+            Error::Config(msg) => write!(f, "Configuration error: {}", msg),
+            Error::IgnorePattern { pattern, message } => {
+                write!(f, "Invalid ignore pattern `{}`: {}", pattern, message)
+            }
+            Error::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "IO error: {}: {}", path.display(), source),
+            Error::Io { path: None, source } => write!(f, "IO error: {}", source),
+            Error::Parse { path, message } => {
+                write!(f, "Cannot read {} as text: {}", path.display(), message)
+            }
+            Error::Unresolved(imports) => {
+                write!(f, "{} imports could not be resolved:", imports.len())?;
+                for import in imports {
+                    write!(
+                        f,
+                        "\n  {}: {} ({})",
+                        import.from.display(),
+                        import.specifier,
+                        import.reason.as_str()
+                    )?;
+                }
+                Ok(())
+            }
+            Error::Cycles(cycles) => write!(f, "{} circular dependencies found.", cycles.len()),
+            Error::Write { path, source } => {
+                write!(f, "Cannot write {}: {}", path.display(), source)
+            }
+            Error::Clipboard(msg) => write!(f, "Clipboard error: {}", msg),
+            Error::Json(err) => write!(f, "JSON error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::Write { source, .. } => Some(source),
+            Error::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dependency_resolver::UnresolvedReason;
+
+    fn every_variant() -> Vec<Error> {
+        vec![
+            Error::Cycles(vec![vec![PathBuf::from("a.ts"), PathBuf::from("a.ts")]]),
+            Error::Usage("no targets".to_string()),
+            Error::Config("bad key".to_string()),
+            Error::IgnorePattern {
+                pattern: "[".to_string(),
+                message: "unclosed class".to_string(),
+            },
+            Error::io(Path::new("a.ts"), io::Error::from(io::ErrorKind::NotFound)),
+            Error::io(
+                Path::new("a.bin"),
+                io::Error::from(io::ErrorKind::InvalidData),
+            ),
+            Error::Unresolved(vec![UnresolvedImport {
+                from: PathBuf::from("src/main.ts"),
+                specifier: "./missing".to_string(),
+                reason: UnresolvedReason::MissingFile,
+            }]),
+            Error::Write {
+                path: PathBuf::from("out.txt"),
+                source: io::Error::from(io::ErrorKind::PermissionDenied),
+            },
+            Error::Clipboard("no clipboard".to_string()),
+            Error::Json(serde_json::from_str::<Value>("{").unwrap_err()),
+        ]
+    }
+
+    #[test]
+    fn each_kind_has_its_exit_code() {
+        let codes: Vec<_> = every_variant().iter().map(Error::exit_code).collect();
+        assert_eq!(codes, [1, 2, 3, 4, 5, 6, 7, 8, 8, 8]);
+        assert_eq!(Error::from(io::Error::other("closed")).exit_code(), 5);
+    }
+
+    #[test]
+    fn json_errors_carry_their_kind_and_details() {
+        let json: Vec<_> = every_variant().iter().map(Error::to_json).collect();
+        for (value, error) in json.iter().zip(every_variant()) {
+            assert_eq!(value["kind"], error.kind().as_str());
+            assert_eq!(value["exit_code"], error.exit_code());
+            assert_eq!(value["message"], error.to_string());
+        }
+
+        assert_eq!(json[0]["cycles"], json!([["a.ts", "a.ts"]]));
+        assert_eq!(json[3]["pattern"], "[");
+        assert_eq!(json[4]["path"], "a.ts");
+        assert_eq!(json[5]["kind"], "parse");
+        assert_eq!(json[5]["path"], "a.bin");
+        assert_eq!(
+            json[6]["unresolved"],
+            json!([{
+                "from": "src/main.ts",
+                "specifier": "./missing",
+                "reason": "missing_file",
+            }])
+        );
+        assert_eq!(json[7]["path"], "out.txt");
+
+        // Only the fields that apply are present
+        let keys = |value: &Value| {
+            let mut keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&json[1]), ["exit_code", "kind", "message"]);
+        assert_eq!(keys(&json[4]), ["exit_code", "kind", "message", "path"]);
+    }
+}
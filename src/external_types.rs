@@ -0,0 +1,478 @@
+use crate::dependency_resolver::canonicalize_path;
+use crate::project::LineRange;
+use crate::selection::excerpt;
+use crate::symbols::{self, Definition};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The type declarations of an imported package, included with
+/// `--external-types`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalTypes {
+    /// The name of the package providing the declarations, e.g. `lodash` or
+    /// `@types/lodash`
+    pub package: String,
+    pub version: Option<String>,
+    /// The declaration file
+    pub path: PathBuf,
+    pub imported_by: Vec<PathBuf>,
+    /// The imported names the declarations were trimmed to, with the
+    /// declarations they reference; empty when the whole file is included
+    pub symbols: Vec<String>,
+    pub content: String,
+}
+
+// The imports of one declaration file, gathered across importers
+struct Imported {
+    manifest: PathBuf,
+    importers: BTreeSet<PathBuf>,
+    /// `None` once an importer uses the whole module
+    names: Option<BTreeSet<String>>,
+}
+
+/// Finds the declaration files of the packages imported by `imports`, given
+/// as importing files and specifiers, and reads them, trimmed to the imported
+/// names where every one of them can be located. Packages without
+/// declarations are left out.
+pub(crate) fn collect(imports: &BTreeSet<(PathBuf, String)>) -> Vec<ExternalTypes> {
+    let mut declarations: BTreeMap<PathBuf, Imported> = BTreeMap::new();
+    for (importer, specifier) in imports {
+        let Some((declaration, manifest)) = find_declarations(specifier, importer) else {
+            continue;
+        };
+        let names = fs::read_to_string(importer)
+            .ok()
+            .and_then(|content| imported_names(importer, &content, specifier));
+
+        let entry = declarations.entry(declaration).or_insert_with(|| Imported {
+            manifest,
+            importers: BTreeSet::new(),
+            names: Some(BTreeSet::new()),
+        });
+        entry.importers.insert(importer.clone());
+        entry.names = match (entry.names.take(), names) {
+            (Some(mut all), Some(names)) => {
+                all.extend(names);
+                Some(all)
+            }
+            _ => None,
+        };
+    }
+
+    declarations
+        .into_iter()
+        .filter_map(|(path, imported)| {
+            let content = fs::read_to_string(&path).ok()?;
+            // Side-effect imports use no declarations
+            if imported.names.as_ref().is_some_and(BTreeSet::is_empty) {
+                return None;
+            }
+            let (content, symbols) = match imported
+                .names
+                .and_then(|names| trim(&path, &content, &names))
+            {
+                Some((trimmed, symbols)) => (trimmed, symbols),
+                None => (content, Vec::new()),
+            };
+            let (package, version) = package_info(&imported.manifest);
+
+            Some(ExternalTypes {
+                package,
+                version,
+                path,
+                imported_by: imported.importers.into_iter().collect(),
+                symbols,
+                content,
+            })
+        })
+        .collect()
+}
+
+// Splits `lodash/fp` into `lodash` and `fp`, keeping the scope of
+// `@scope/pkg/sub`
+fn split_specifier(specifier: &str) -> (&str, Option<&str>) {
+    let separator = if specifier.starts_with('@') {
+        specifier.match_indices('/').nth(1).map(|(index, _)| index)
+    } else {
+        specifier.find('/')
+    };
+    match separator {
+        Some(index) => (&specifier[..index], Some(&specifier[index + 1..])),
+        None => (specifier, None),
+    }
+}
+
+// Bundled declarations first, then those from DefinitelyTyped, then the
+// Node.js built-in modules, returning the declaration file and the manifest
+// of the package holding it
+fn find_declarations(specifier: &str, importer: &Path) -> Option<(PathBuf, PathBuf)> {
+    let (name, subpath) = match specifier.strip_prefix("node:") {
+        Some(builtin) => ("node", Some(builtin)),
+        None => split_specifier(specifier),
+    };
+    let types_name = match name.strip_prefix('@') {
+        Some(scoped) => format!("@types/{}", scoped.replacen('/', "__", 1)),
+        None => format!("@types/{}", name),
+    };
+
+    let importer = canonicalize_path(importer);
+    let candidates = [
+        (name.to_string(), subpath),
+        (types_name, subpath),
+        ("@types/node".to_string(), Some(specifier)),
+    ];
+    for (package, subpath) in candidates {
+        for directory in importer.ancestors().skip(1) {
+            let package_dir = directory.join("node_modules").join(&package);
+            if !package_dir.is_dir() {
+                continue;
+            }
+            if let Some(declaration) = declaration_entry(&package_dir, subpath) {
+                return Some((
+                    canonicalize_path(&declaration),
+                    package_dir.join("package.json"),
+                ));
+            }
+            // The nearest installation is the one that is imported
+            break;
+        }
+    }
+    None
+}
+
+fn declaration_entry(package_dir: &Path, subpath: Option<&str>) -> Option<PathBuf> {
+    let candidates = match subpath {
+        Some(subpath) => {
+            let subpath = subpath.trim_end_matches(".js");
+            vec![
+                package_dir.join(format!("{}.d.ts", subpath)),
+                package_dir.join(subpath).join("index.d.ts"),
+            ]
+        }
+        None => {
+            let manifest = read_manifest(&package_dir.join("package.json"));
+            let declared = ["types", "typings"]
+                .iter()
+                .find_map(|field| manifest.as_ref()?.get(field)?.as_str())
+                .map(|entry| package_dir.join(entry));
+            declared
+                .into_iter()
+                .chain([package_dir.join("index.d.ts")])
+                .collect()
+        }
+    };
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.is_file() && candidate.to_string_lossy().ends_with(".d.ts"))
+}
+
+fn read_manifest(path: &Path) -> Option<Value> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn package_info(manifest_path: &Path) -> (String, Option<String>) {
+    let manifest = read_manifest(manifest_path);
+    let field = |name: &str| {
+        manifest
+            .as_ref()
+            .and_then(|manifest| manifest.get(name)?.as_str())
+            .map(str::to_string)
+    };
+    let package = field("name").unwrap_or_else(|| {
+        manifest_path
+            .parent()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    (package, field("version"))
+}
+
+// The names `importer` takes from `specifier` through `import { a, b as c }`
+// and `export { a } from`, or `None` when it uses the module as a whole
+// through a default, namespace or unrecognized import
+fn imported_names(importer: &Path, content: &str, specifier: &str) -> Option<BTreeSet<String>> {
+    let tree = symbols::parse(importer, content)?;
+    let root = tree.root_node();
+    let text = |node: tree_sitter::Node| node.utf8_text(content.as_bytes()).ok();
+
+    let mut names = BTreeSet::new();
+    let mut found = false;
+    let mut cursor = root.walk();
+    for statement in root.children(&mut cursor) {
+        if !matches!(statement.kind(), "import_statement" | "export_statement") {
+            continue;
+        }
+        let Some(source) = statement.child_by_field_name("source") else {
+            continue;
+        };
+        if text(source).map(|source| source.trim_matches(['"', '\'', '`'])) != Some(specifier) {
+            continue;
+        }
+        found = true;
+
+        let mut statement_cursor = statement.walk();
+        for child in statement.children(&mut statement_cursor) {
+            let specifiers = match child.kind() {
+                "import_clause" => {
+                    let mut clause_cursor = child.walk();
+                    let mut named = None;
+                    for part in child.children(&mut clause_cursor) {
+                        match part.kind() {
+                            "named_imports" => named = Some(part),
+                            "," => {}
+                            // Default and namespace imports
+                            _ => return None,
+                        }
+                    }
+                    named
+                }
+                "export_clause" => Some(child),
+                // `export * from`
+                "*" => return None,
+                _ => None,
+            };
+            let Some(specifiers) = specifiers else {
+                continue;
+            };
+            let mut specifier_cursor = specifiers.walk();
+            for entry in specifiers.named_children(&mut specifier_cursor) {
+                if let Some(name) = entry.child_by_field_name("name").and_then(text) {
+                    names.insert(name.to_string());
+                }
+            }
+        }
+    }
+
+    // `require()` and `import()` calls are not statements
+    found.then_some(names)
+}
+
+// The top-level declarations named `names`, with those they reference in the
+// same file, or `None` when one of the names is not declared at the top
+fn trim(path: &Path, content: &str, names: &BTreeSet<String>) -> Option<(String, Vec<String>)> {
+    let mut definitions: BTreeMap<String, Vec<Definition>> = BTreeMap::new();
+    for definition in symbols::top_level_definitions(path, content) {
+        definitions
+            .entry(definition.name.clone())
+            .or_default()
+            .push(definition);
+    }
+    if names.iter().any(|name| !definitions.contains_key(name)) {
+        return None;
+    }
+
+    let mut included: BTreeSet<&str> = BTreeSet::new();
+    let mut pending: VecDeque<&str> = names.iter().map(String::as_str).collect();
+    while let Some(name) = pending.pop_front() {
+        let Some((name, overloads)) = definitions.get_key_value(name) else {
+            continue;
+        };
+        if !included.insert(name) {
+            continue;
+        }
+        for definition in overloads {
+            pending.extend(
+                symbols::referenced_names(path, content, definition.bytes.clone())
+                    .into_iter()
+                    .filter_map(|referenced| {
+                        definitions
+                            .get_key_value(&referenced)
+                            .map(|(name, _)| name.as_str())
+                    }),
+            );
+        }
+    }
+
+    let mut ranges: Vec<_> = included
+        .iter()
+        .flat_map(|name| &definitions[*name])
+        .map(|definition| definition.lines)
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+
+    // Adjacent declarations, such as overloads, stay together
+    let mut merged: Vec<LineRange> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end + 1 => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    let trimmed = merged
+        .into_iter()
+        .map(|range| excerpt(content, range))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Some((trimmed, included.into_iter().map(str::to_string).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        root: PathBuf,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let root = fs::canonicalize(dir.path()).unwrap();
+            Self { _dir: dir, root }
+        }
+
+        fn write(&self, path: &str, content: &str) -> PathBuf {
+            let path = self.root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            path
+        }
+
+        fn collect(&self, imports: &[(&Path, &str)]) -> Vec<ExternalTypes> {
+            let imports = imports
+                .iter()
+                .map(|(importer, specifier)| (importer.to_path_buf(), specifier.to_string()))
+                .collect();
+            collect(&imports)
+        }
+    }
+
+    const DECLARATIONS: &str = "\
+export interface Options {
+  deep: boolean;
+}
+
+export declare function clone<T>(value: T, options?: Options): T;
+
+export declare function merge(a: object, b: object): object;
+";
+
+    #[test]
+    fn bundled_declarations_are_found_through_types_and_typings() {
+        let fixture = Fixture::new();
+        fixture.write(
+            "node_modules/clone/package.json",
+            r#"{ "name": "clone", "version": "2.1.0", "types": "lib/clone.d.ts" }"#,
+        );
+        let declaration = fixture.write("node_modules/clone/lib/clone.d.ts", DECLARATIONS);
+        fixture.write(
+            "node_modules/legacy/package.json",
+            r#"{ "name": "legacy", "typings": "legacy.d.ts" }"#,
+        );
+        fixture.write(
+            "node_modules/legacy/legacy.d.ts",
+            "export type Old = string;\n",
+        );
+        let importer = fixture.write(
+            "src/main.ts",
+            "import clone from 'clone';\nimport * as legacy from 'legacy';\n",
+        );
+
+        let types = fixture.collect(&[(&importer, "clone"), (&importer, "legacy")]);
+        assert_eq!(types.len(), 2);
+        assert_eq!(types[0].package, "clone");
+        assert_eq!(types[0].version.as_deref(), Some("2.1.0"));
+        assert_eq!(types[0].path, declaration);
+        assert_eq!(types[0].imported_by, [importer]);
+        // Default imports use the whole module
+        assert_eq!(types[0].content, DECLARATIONS);
+        assert!(types[0].symbols.is_empty());
+        assert_eq!(types[1].package, "legacy");
+        assert_eq!(types[1].version, None);
+    }
+
+    #[test]
+    fn definitely_typed_packages_are_used_without_bundled_declarations() {
+        let fixture = Fixture::new();
+        fixture.write(
+            "node_modules/left-pad/package.json",
+            r#"{ "name": "left-pad" }"#,
+        );
+        fixture.write("node_modules/left-pad/index.js", "");
+        fixture.write(
+            "node_modules/@types/left-pad/package.json",
+            r#"{ "name": "@types/left-pad", "version": "1.2.0" }"#,
+        );
+        fixture.write("node_modules/@types/left-pad/index.d.ts", DECLARATIONS);
+        fixture.write(
+            "node_modules/@types/acme__ui/index.d.ts",
+            "export type Button = string;\n",
+        );
+        let importer = fixture.write(
+            "src/main.ts",
+            "import pad from 'left-pad';\nimport ui from '@acme/ui';\n",
+        );
+
+        let types = fixture.collect(&[(&importer, "left-pad"), (&importer, "@acme/ui")]);
+        let packages: Vec<_> = types.iter().map(|types| types.package.as_str()).collect();
+        // Without a manifest the directory names the package
+        assert_eq!(packages, ["acme__ui", "@types/left-pad"]);
+        assert_eq!(types[1].version.as_deref(), Some("1.2.0"));
+    }
+
+    #[test]
+    fn subpaths_have_entries_of_their_own() {
+        let fixture = Fixture::new();
+        fixture.write(
+            "node_modules/lodash/package.json",
+            r#"{ "name": "lodash" }"#,
+        );
+        fixture.write("node_modules/lodash/index.d.ts", DECLARATIONS);
+        let fp = fixture.write("node_modules/lodash/fp.d.ts", "export type Fp = 1;\n");
+        let array = fixture.write(
+            "node_modules/lodash/array/index.d.ts",
+            "export type Chunk = 1;\n",
+        );
+        let importer = fixture.write(
+            "src/main.ts",
+            "import fp from 'lodash/fp.js';\nimport array from 'lodash/array';\n",
+        );
+
+        let types = fixture.collect(&[(&importer, "lodash/fp.js"), (&importer, "lodash/array")]);
+        let paths: Vec<_> = types.iter().map(|types| types.path.clone()).collect();
+        assert_eq!(paths, [array, fp]);
+        assert!(types.iter().all(|types| types.package == "lodash"));
+    }
+
+    #[test]
+    fn declarations_are_trimmed_to_the_imported_names() {
+        let fixture = Fixture::new();
+        fixture.write("node_modules/clone/index.d.ts", DECLARATIONS);
+        let first = fixture.write("src/a.ts", "import { clone } from 'clone';\n");
+        let second = fixture.write("src/b.ts", "export { clone as copy } from 'clone';\n");
+
+        let types = fixture.collect(&[(&first, "clone"), (&second, "clone")]);
+        assert_eq!(types.len(), 1);
+        assert_eq!(types[0].imported_by, [first, second]);
+        // `Options` comes along, as `clone` references it
+        assert_eq!(types[0].symbols, ["Options", "clone"]);
+        assert_eq!(
+            types[0].content,
+            "export interface Options {\n  deep: boolean;\n}\n\n\
+             export declare function clone<T>(value: T, options?: Options): T;"
+        );
+    }
+
+    #[test]
+    fn unknown_names_and_side_effect_imports_keep_or_drop_everything() {
+        let fixture = Fixture::new();
+        fixture.write("node_modules/clone/index.d.ts", DECLARATIONS);
+        let unknown = fixture.write("src/a.ts", "import { deepClone } from 'clone';\n");
+        let types = fixture.collect(&[(&unknown, "clone")]);
+        assert_eq!(types[0].content, DECLARATIONS);
+        assert!(types[0].symbols.is_empty());
+
+        let side_effect = fixture.write("src/b.ts", "import 'clone';\n");
+        assert!(fixture.collect(&[(&side_effect, "clone")]).is_empty());
+        // Packages without declarations are left out
+        fixture.write("node_modules/plain/index.js", "");
+        let plain = fixture.write("src/c.ts", "import plain from 'plain';\n");
+        assert!(fixture.collect(&[(&plain, "plain")]).is_empty());
+    }
+}
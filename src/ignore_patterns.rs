@@ -0,0 +1,85 @@
+use regex::Regex;
+use std::path::Path;
+
+/// Turns user patterns into the newline-separated form matched by `is_ignored`,
+/// expanding `dir/` to everything below it.
+pub fn convert_ignore_patterns(patterns: &[String]) -> String {
+    patterns
+        .iter()
+        .map(|pattern| {
+            if pattern.ends_with('/') {
+                format!("{}**/*", pattern)
+            } else {
+                pattern.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn is_ignored(file_path: &Path, ignore_patterns: &str) -> bool {
+    matching_pattern(file_path, ignore_patterns).is_some()
+}
+
+/// The first pattern that ignores `file_path`, if any.
+pub fn matching_pattern<'a>(file_path: &Path, ignore_patterns: &'a str) -> Option<&'a str> {
+    let path_str = file_path.to_string_lossy();
+
+    ignore_patterns
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .find(|pattern| {
+            let regex_pattern = convert_ignore_pattern_to_regex(pattern);
+            match Regex::new(&regex_pattern) {
+                Ok(regex) => regex.is_match(&path_str),
+                Err(_) => false,
+            }
+        })
+}
+
+/// The first pattern that cannot be turned into a regular expression, with
+/// the reason. Such patterns never match.
+pub fn invalid_ignore_pattern(ignore_patterns: &str) -> Option<(&str, regex::Error)> {
+    ignore_patterns
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .find_map(|pattern| {
+            Regex::new(&convert_ignore_pattern_to_regex(pattern))
+                .err()
+                .map(|err| (pattern, err))
+        })
+}
+
+fn convert_ignore_pattern_to_regex(pattern: &str) -> String {
+    let mut regex_pattern = String::new();
+
+    if pattern.ends_with('/') {
+        regex_pattern.push_str(".*");
+    }
+
+    let mut in_bracket = false;
+    for c in pattern.chars() {
+        match c {
+            '*' if !in_bracket => regex_pattern.push_str(".*"),
+            '?' if !in_bracket => regex_pattern.push('.'),
+            '[' => {
+                in_bracket = true;
+                regex_pattern.push(c);
+            }
+            ']' => {
+                in_bracket = false;
+                regex_pattern.push(c);
+            }
+            '!' if in_bracket => regex_pattern.push('^'),
+            '/' => regex_pattern.push_str("\\/"),
+            '.' => regex_pattern.push_str("\\."),
+            _ => regex_pattern.push(c),
+        }
+    }
+
+    if !pattern.contains('/') {
+        format!("(?:^|.*/){}$", regex_pattern)
+    } else {
+        format!("^{}$", regex_pattern)
+    }
+}
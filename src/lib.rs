@@ -0,0 +1,56 @@
+//! Combines project source files, and optionally everything they import,
+//! into a single XML or JSON document.
+//!
+//! ```no_run
+//! use pcc::{Combiner, Format, Sink};
+//!
+//! let project = Combiner::builder()
+//!     .path("src/main.ts")
+//!     .deps(true)
+//!     .ignore("*.test.ts")
+//!     .format(Format::Json)
+//!     .sink(Sink::File("combined.json".into()))
+//!     .build()?
+//!     .run()?;
+//! println!("{} dependencies", project.dependencies.len());
+//! # Ok::<(), pcc::Error>(())
+//! ```
+
+mod cache;
+mod clipboard;
+mod combiner;
+mod dependency_resolver;
+mod error;
+mod external_types;
+mod ignore_patterns;
+mod listing;
+mod mcp;
+mod project;
+mod report;
+mod selection;
+mod server;
+mod session;
+mod sfc;
+mod split;
+mod stylesheet_resolver;
+mod symbols;
+mod tsconfig;
+mod typescript_resolver;
+mod watch;
+mod workspace;
+
+pub use clipboard::{copy_to_clipboard, Clipboard, ClipboardBackend};
+pub use combiner::{Combiner, CombinerBuilder, ErrorPolicy, FileSection, Format, PathStyle, Sink};
+pub use dependency_resolver::{ImportKind, ParseIssue, UnresolvedImport, UnresolvedReason};
+pub use error::{Error, ErrorKind, Result};
+pub use external_types::ExternalTypes;
+pub use listing::{FileListing, ListedFile, SkipReason, SkippedFile};
+pub use mcp::McpServer;
+pub use project::{
+    estimate_tokens, CombinedProject, Dependency, Importer, LineRange, ProjectStats, RenderOptions,
+    SourceFile,
+};
+pub use report::{RunReport, SectionStats, Timings};
+pub use server::HttpServer;
+pub use split::{part_path, SplitLimit};
+pub use watch::WatchRun;
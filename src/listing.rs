@@ -0,0 +1,221 @@
+use crate::combiner::{display_path, Combiner, Resolvers};
+use crate::error::Result;
+use crate::project::{estimate_tokens, CombinedProject, SourceFile};
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// What a run would combine, and what it would leave out, without rendering
+/// the document.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FileListing {
+    pub targets: Vec<ListedFile>,
+    pub references: Vec<ListedFile>,
+    pub files: Vec<ListedFile>,
+    pub dependencies: Vec<ListedFile>,
+    pub external: Vec<ListedFile>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// A file that would be included, and why.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListedFile {
+    pub path: PathBuf,
+    /// The size of the included content, or of the file for binary assets
+    pub bytes: u64,
+    pub estimated_tokens: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// Why a file that was found is left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind", content = "pattern")]
+pub enum SkipReason {
+    /// Matches the ignore pattern
+    Ignored(String),
+    /// Already included in another section
+    Duplicate,
+    Binary,
+    /// An installed package, whose files are never included
+    NodeModules,
+    /// Could not be read, with the `skip` error policy or in a dry run
+    Unreadable,
+    /// Outside the root of `pcc serve`
+    OutsideRoot,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Ignored(pattern) => write!(f, "ignore rule `{}`", pattern),
+            SkipReason::Duplicate => write!(f, "already included"),
+            SkipReason::Binary => write!(f, "binary file"),
+            SkipReason::NodeModules => write!(f, "in node_modules"),
+            SkipReason::Unreadable => write!(f, "cannot be read"),
+            SkipReason::OutsideRoot => write!(f, "outside the project root"),
+        }
+    }
+}
+
+impl FileListing {
+    /// Every included file, section by section.
+    pub fn included(&self) -> impl Iterator<Item = &ListedFile> {
+        self.targets
+            .iter()
+            .chain(&self.references)
+            .chain(&self.files)
+            .chain(&self.dependencies)
+            .chain(&self.external)
+    }
+}
+
+impl Combiner {
+    /// Finds and resolves the files like `combine`, and lists them with the
+    /// reason each one is included or skipped, along with their sizes.
+    /// Files that a run would abort on are listed as skipped because they
+    /// cannot be read.
+    pub fn dry_run(&self) -> Result<FileListing> {
+        let combiner = self.skipping_unreadable();
+        let assembly = if self.options().deps {
+            let mut resolvers = Resolvers::new(self.options())?;
+            combiner.assemble(Some(&mut resolvers))?
+        } else {
+            combiner.assemble(None)?
+        };
+        let project = &assembly.project;
+
+        Ok(FileListing {
+            targets: listed(&project.targets, |_| "--target".to_string()),
+            references: listed(&project.references, |_| "--reference".to_string()),
+            files: listed(&project.files, |file| self.file_reason(file)),
+            dependencies: self.dependencies(project),
+            external: project
+                .external
+                .iter()
+                .map(|types| ListedFile {
+                    path: types.path.clone(),
+                    bytes: types.content.len() as u64,
+                    estimated_tokens: estimate_tokens(&types.content),
+                    reason: format!("types of {}", types.package),
+                })
+                .collect(),
+            skipped: assembly.skipped,
+        })
+    }
+
+    fn file_reason(&self, file: &SourceFile) -> String {
+        let options = self.options();
+        if let Some(lines) = file.lines.filter(|_| !options.symbols.is_empty()) {
+            return format!("symbol, lines {}-{}", lines.start, lines.end);
+        }
+
+        let given = options.paths.iter().find(|path| {
+            path.as_path() == file.path
+                || display_path(path, options).ok().as_deref() == Some(file.path.as_path())
+        });
+        if given.is_some() {
+            return "given".to_string();
+        }
+
+        options
+            .paths
+            .iter()
+            .filter(|path| path.is_dir())
+            .map(|dir| display_path(dir, options).unwrap_or_else(|_| dir.clone()))
+            .find(|dir| file.path.starts_with(dir) || dir == Path::new("."))
+            .map_or_else(
+                || "given".to_string(),
+                |dir| format!("under {}", dir.display()),
+            )
+    }
+
+    fn dependencies(&self, project: &CombinedProject) -> Vec<ListedFile> {
+        project
+            .dependencies
+            .iter()
+            .map(|dependency| {
+                let importer = match dependency.imported_by.as_slice() {
+                    [] => String::new(),
+                    [importer] => format!(" by {}", importer.path.display()),
+                    [importer, others @ ..] => {
+                        format!(" by {} and {} more", importer.path.display(), others.len())
+                    }
+                };
+                let reason = match (&dependency.content, dependency.lines, &dependency.error) {
+                    (_, _, Some(error)) => {
+                        format!("imported{}, cannot be read: {}", importer, error)
+                    }
+                    (None, _, None) => format!("asset imported{}", importer),
+                    (Some(_), Some(lines), None) => {
+                        format!("lines {}-{} referenced{}", lines.start, lines.end, importer)
+                    }
+                    (Some(_), None, None) => format!("imported{}", importer),
+                };
+
+                ListedFile {
+                    path: dependency.path.clone(),
+                    bytes: dependency.bytes,
+                    estimated_tokens: dependency.content.as_deref().map_or(0, estimate_tokens),
+                    reason,
+                }
+            })
+            .collect()
+    }
+}
+
+fn listed(files: &[SourceFile], reason: impl Fn(&SourceFile) -> String) -> Vec<ListedFile> {
+    files
+        .iter()
+        .map(|file| ListedFile {
+            path: file.path.clone(),
+            bytes: file.content.len() as u64,
+            estimated_tokens: estimate_tokens(&file.content),
+            reason: match &file.error {
+                Some(error) => format!("cannot be read: {}", error),
+                None => reason(file),
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combiner::PathStyle;
+    use std::fs;
+
+    #[test]
+    fn dry_run_lists_unreadable_files_as_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/good.txt"), "good").unwrap();
+        fs::write(root.join("src/bad.txt"), b"\xff\xfe bad").unwrap();
+
+        let combiner = Combiner::builder()
+            .path(root.join("src"))
+            .path_style(PathStyle::RootRelative)
+            .root(&root)
+            .cache(false)
+            .build()
+            .unwrap();
+        assert!(combiner.combine().is_err());
+
+        let listing = combiner.dry_run().unwrap();
+        let files: Vec<_> = listing.files.iter().map(|file| &file.path).collect();
+        assert_eq!(files, [Path::new("src/good.txt")]);
+        assert_eq!(
+            listing.skipped,
+            [SkippedFile {
+                path: PathBuf::from("src/bad.txt"),
+                reason: SkipReason::Unreadable,
+            }]
+        );
+    }
+}
@@ -1,26 +1,23 @@
 mod config;
-mod dependency_resolver;
-mod typescript_resolver;
+mod config_command;
 
-use crate::dependency_resolver::DependencyResolver;
-use crate::typescript_resolver::TypeScriptResolver;
-use clap::Parser;
-use clipboard::{ClipboardContext, ClipboardProvider};
+use clap::{Parser, Subcommand, ValueEnum};
 use config::Config;
-use config::ProcessingOptions;
-use ignore::Walk;
-use regex::Regex;
-use std::collections::HashMap;
-use std::collections::HashSet;
+use pcc::{
+    Clipboard, CombinedProject, Combiner, Error, ErrorPolicy, FileListing, FileSection, HttpServer,
+    ListedFile, McpServer, PathStyle, RunReport, SectionStats, Sink, UnresolvedReason,
+};
+use std::collections::BTreeMap;
 use std::env;
-use std::fs;
-use std::io;
+use std::io::{self, BufRead};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Target files or directories to process
+    /// Target files or directories to process; narrow a file with `file:120-220` or `file#fn:name`
     #[arg(required = false)]
     targets: Vec<PathBuf>,
 
@@ -32,6 +29,10 @@ struct Args {
     #[arg(long)]
     save: bool,
 
+    /// How to copy: `auto`, `x11`, `wayland`, `osc52` or `command` (the configured `clipboard_command`)
+    #[arg(long, value_name = "BACKEND")]
+    clipboard: Option<String>,
+
     /// Output file path
     #[arg(long)]
     output_path: Option<String>,
@@ -44,10 +45,26 @@ struct Args {
     #[arg(long = "ignore", value_name = "PATTERN")]
     ignore_patterns: Vec<String>,
 
-    /// Use relative paths
-    #[arg(long, default_value_t = true)]
+    /// How paths are shown: `relative` to the working directory, `absolute`, or `root-relative` to --root
+    #[arg(long = "paths", value_name = "STYLE")]
+    path_style: Option<PathStyle>,
+
+    /// Show paths relative to the working directory, like `--paths relative` (default)
+    #[arg(long, overrides_with = "no_relative", conflicts_with = "path_style")]
     relative: bool,
 
+    /// Show absolute paths, like `--paths absolute`
+    #[arg(long, overrides_with = "relative", conflicts_with = "path_style")]
+    no_relative: bool,
+
+    /// The directory root-relative paths start from (default: the nearest directory with .git, else the working directory)
+    #[arg(long, value_name = "DIR")]
+    root: Option<PathBuf>,
+
+    /// Prepended to every relative path, e.g. `frontend/`, to tell repositories apart
+    #[arg(long, value_name = "PREFIX")]
+    path_prefix: Option<String>,
+
     /// Resolve dependencies
     #[arg(long, default_value_t = false)]
     deps: bool,
@@ -59,376 +76,664 @@ struct Args {
     /// Reference files for context
     #[arg(long = "reference")]
     reference_files: Vec<PathBuf>,
-}
 
-#[derive(Debug)]
-enum AppError {
-    IoError(io::Error),
-    ConfigError(String),
-    ClipboardError(String),
-}
+    /// Do not follow type-only imports when resolving dependencies
+    #[arg(long, default_value_t = false)]
+    skip_type_imports: bool,
 
-impl From<io::Error> for AppError {
-    fn from(err: io::Error) -> Self {
-        AppError::IoError(err)
-    }
+    /// Do not follow dynamic import() expressions when resolving dependencies
+    #[arg(long, default_value_t = false)]
+    skip_dynamic_imports: bool,
+
+    /// Include imported stylesheets and assets in the dependencies section
+    #[arg(long, default_value_t = false)]
+    assets: bool,
+
+    /// Include the type declarations of imported packages in an <external> section
+    #[arg(long, default_value_t = false)]
+    external_types: bool,
+
+    /// Exit with a non-zero status when circular dependencies are found
+    #[arg(long, default_value_t = false)]
+    fail_on_cycles: bool,
+
+    /// Exit with a non-zero status when imports point to missing files or unknown aliases
+    #[arg(long, default_value_t = false)]
+    strict_deps: bool,
+
+    /// Parse and resolve every file again instead of using the on-disk cache
+    #[arg(long, default_value_t = false)]
+    no_cache: bool,
+
+    /// Also reuse cached import resolutions; faster, but files added where an import would now resolve are missed
+    #[arg(long, default_value_t = false)]
+    cache_resolutions: bool,
+
+    /// Skip files that cannot be read, with a warning, instead of aborting the run
+    #[arg(long, default_value_t = false)]
+    keep_going: bool,
+
+    /// What to do with files that cannot be read: `abort`, `skip` or `embed`, for every section or one, e.g. `dependencies=embed`
+    #[arg(long = "on-error", value_name = "[SECTION=]POLICY", value_parser = parse_error_policy)]
+    on_error: Vec<(Option<FileSection>, ErrorPolicy)>,
+
+    /// Include only this definition, e.g. `src/main.ts::processFiles` or `src/main.ts::Combiner::run`
+    #[arg(long = "symbol", value_name = "PATH::NAME")]
+    symbols: Vec<String>,
+
+    /// Also include the definitions the symbols reference, up to this many levels deep
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    symbol_depth: usize,
+
+    /// Prefix every line of the output with its line number
+    #[arg(long, default_value_t = false)]
+    line_numbers: bool,
+
+    /// Split the output into parts of at most this many estimated tokens
+    #[arg(long, value_name = "N", conflicts_with = "split_bytes")]
+    split_tokens: Option<usize>,
+
+    /// Split the output into parts of at most this many bytes
+    #[arg(long, value_name = "N")]
+    split_bytes: Option<usize>,
+
+    /// List the files that would be combined, and those skipped, without combining them
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Print errors as text, or as a JSON object for tools
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+
+    /// Write the run statistics to this file as JSON
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-impl From<Box<dyn std::error::Error>> for AppError {
-    fn from(err: Box<dyn std::error::Error>) -> Self {
-        AppError::ClipboardError(err.to_string())
-    }
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ErrorFormat {
+    Text,
+    Json,
 }
 
-impl From<String> for AppError {
-    fn from(err: String) -> Self {
-        AppError::ConfigError(err)
-    }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Report circular dependencies and exit with a non-zero status if any exist
+    Cycles {
+        /// Files or directories to check
+        #[arg(required = true)]
+        targets: Vec<PathBuf>,
+    },
+    /// Serve the combiner to coding agents over the Model Context Protocol on stdin/stdout
+    Mcp,
+    /// Re-combine the targets whenever they, their dependencies or the config files change
+    Watch {
+        /// Files or directories to combine
+        #[arg(required = true)]
+        targets: Vec<PathBuf>,
+
+        /// Milliseconds to wait for further changes before re-combining
+        #[arg(long, default_value_t = 300)]
+        debounce: u64,
+    },
+    /// Serve a JSON API for editor and browser integrations on localhost
+    Serve {
+        /// Port to listen on, on 127.0.0.1 only
+        #[arg(long, default_value_t = 7878)]
+        port: u16,
+
+        /// Project root; requests cannot reach files outside of it
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+    },
+    /// List the files that would be combined, and those skipped, like --dry-run
+    Ls {
+        /// Files or directories to list
+        targets: Vec<PathBuf>,
+    },
+    /// Validate, show or create the configuration files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
 }
 
-impl From<&str> for AppError {
-    fn from(err: &str) -> Self {
-        AppError::ConfigError(err.to_string())
-    }
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Check the configuration files for syntax errors, unknown keys and invalid values
+    Check,
+    /// Print the configuration a run would use, and where each value comes from
+    Show,
+    /// Write a commented starter configuration file to the home directory
+    Init {
+        /// Write .pcc_config.toml to the working directory instead
+        #[arg(long)]
+        project: bool,
+
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+    },
 }
 
-impl std::fmt::Display for AppError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            AppError::IoError(err) => write!(f, "IO error: {}", err),
-            AppError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
-            AppError::ClipboardError(msg) => write!(f, "Clipboard error: {}", msg),
+impl Args {
+    // `--relative` or `--no-relative`, whichever was given last
+    fn relative_flag(&self) -> Option<bool> {
+        match (self.relative, self.no_relative) {
+            (true, _) => Some(true),
+            (false, true) => Some(false),
+            (false, false) => None,
         }
     }
 }
 
-// Add new struct to track processed files and dependencies
-struct FileProcessor {
-    processed_files: HashSet<PathBuf>,
-    dependency_map: HashMap<PathBuf, HashSet<PathBuf>>,
-    combined_source_code: String,
-}
+fn main() {
+    let args = Args::parse();
 
-impl FileProcessor {
-    fn new() -> Self {
-        Self {
-            processed_files: HashSet::new(),
-            dependency_map: HashMap::new(),
-            combined_source_code: String::from(
-                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<project>\n",
-            ),
+    if let Err(err) = dispatch(&args) {
+        match args.error_format {
+            ErrorFormat::Text => eprintln!("Error: {}", err),
+            ErrorFormat::Json => eprintln!("{}", err.to_json()),
         }
+        std::process::exit(err.exit_code());
     }
+}
 
-    // Process a single file and its dependencies
-    fn process_file_with_deps(
-        &mut self,
-        file_path: &Path,
-        options: &ProcessingOptions,
-        deps_resolver: &mut DependencyResolver,
-        ts_resolver: &mut TypeScriptResolver,
-    ) -> Result<(), AppError> {
-        // Skip if already processed
-        if self.processed_files.contains(file_path) {
-            return Ok(());
+fn dispatch(args: &Args) -> Result<(), Error> {
+    match &args.command {
+        Some(Command::Mcp) => return run_mcp(args),
+        Some(Command::Watch { targets, debounce }) => {
+            return run_watch(targets, args, Duration::from_millis(*debounce));
         }
-
-        // Process main file
-        let file_source_code = process_single_file(file_path, options)?;
-        self.combined_source_code.push_str(&file_source_code);
-        self.processed_files.insert(file_path.to_path_buf());
-
-        // Process dependencies
-        let resolved_files = deps_resolver.resolve_deps(file_path, ts_resolver)?;
-
-        for dep_file in resolved_files {
-            if !is_ignored(&dep_file, &options.ignore_patterns) && &dep_file != file_path {
-                let all_importers = deps_resolver.get_all_importers(&dep_file);
-                self.dependency_map.insert(dep_file, all_importers);
+        Some(Command::Serve { port, root }) => return run_serve(args, root, *port),
+        Some(Command::Cycles { targets }) => {
+            let cycles = run_cycles(targets, args)?;
+            if cycles.is_empty() {
+                println!("No circular dependencies found.");
+                return Ok(());
             }
+            println!("Found {} circular dependencies:", cycles.len());
+            for (index, cycle) in cycles.iter().enumerate() {
+                println!("  {}. {}", index + 1, format_cycle(cycle));
+            }
+            // The listing above already reports the cycles as text, so only
+            // the exit status is left; tools asking for JSON still get the error
+            let err = Error::Cycles(cycles);
+            if matches!(args.error_format, ErrorFormat::Text) {
+                std::process::exit(err.exit_code());
+            }
+            return Err(err);
         }
-
-        Ok(())
+        Some(Command::Config { command }) => {
+            return match command {
+                ConfigCommand::Check => config_command::check(),
+                ConfigCommand::Show => config_command::show(args),
+                ConfigCommand::Init { project, force } => config_command::init(*project, *force),
+            };
+        }
+        Some(Command::Ls { .. }) | None => {}
     }
 
-    // Add dependencies section to output
-    fn add_dependencies_section(&mut self, options: &ProcessingOptions) -> Result<(), AppError> {
-        if !self.dependency_map.is_empty() {
-            self.combined_source_code.push_str("  <dependencies>\n");
-
-            // Sort dependencies to ensure consistent output
-            let mut deps: Vec<_> = self.dependency_map.iter().collect();
-            deps.sort_by(|a, b| a.0.cmp(b.0));
-
-            for (dep_file, importers) in deps {
-                // Skip if already processed in main section
-                if !self.processed_files.contains(dep_file) {
-                    let mut file_source_code =
-                        process_single_file_with_importers(dep_file, options, importers)?;
-                    // Add additional indentation for dependencies section
-                    file_source_code = file_source_code
-                        .lines()
-                        .map(|line| format!("  {}", line))
-                        .collect::<Vec<_>>()
-                        .join("\n");
-                    self.combined_source_code.push_str(&file_source_code);
-                    self.combined_source_code.push('\n');
-                    self.processed_files.insert(dep_file.clone());
-                }
-            }
-            self.combined_source_code.push_str("  </dependencies>\n");
-        }
+    let targets = match &args.command {
+        Some(Command::Ls { targets }) => targets,
+        _ => &args.targets,
+    };
+    if targets.is_empty()
+        && args.target_files.is_empty()
+        && args.reference_files.is_empty()
+        && args.symbols.is_empty()
+    {
+        return Err(Error::Usage(
+            "Either <TARGETS> or --target/--reference/--symbol must be specified.".to_string(),
+        ));
+    }
 
-        Ok(())
+    if args.dry_run || matches!(args.command, Some(Command::Ls { .. })) {
+        return run_ls(targets, args);
     }
 
-    // Finalize and return the combined source code
-    fn finalize(mut self) -> String {
-        self.combined_source_code.push_str("</project>\n");
-        self.combined_source_code
+    let project = run(&args.targets, args)?;
+    println!("Project code combined successfully.");
+    if args.fail_on_cycles && !project.cycles.is_empty() {
+        return Err(Error::Cycles(project.cycles));
+    }
+    let broken: Vec<_> = project
+        .unresolved
+        .into_iter()
+        .filter(|unresolved| unresolved.reason != UnresolvedReason::ExternalPackage)
+        .collect();
+    if args.strict_deps && !broken.is_empty() {
+        return Err(Error::Unresolved(broken));
     }
+    Ok(())
 }
 
-fn main() {
-    let args = Args::parse();
-
-    if args.targets.is_empty() && args.target_files.is_empty() && args.reference_files.is_empty() {
-        eprintln!("Error: Either <TARGETS> or --target/--reference must be specified.");
-        std::process::exit(1);
+fn parse_error_policy(value: &str) -> Result<(Option<FileSection>, ErrorPolicy), String> {
+    match value.split_once('=') {
+        Some((section, policy)) => Ok((Some(section.parse()?), policy.parse()?)),
+        None => Ok((None, value.parse()?)),
     }
+}
 
-    match run(&args.targets, &args) {
-        Ok(()) => println!("Project code combined successfully."),
-        Err(err) => eprintln!("Error: {}", err),
+fn run(target_paths: &[PathBuf], args: &Args) -> Result<CombinedProject, Error> {
+    let config = Config::load()?;
+    let sink = select_sink(args, &config)?;
+    let builder = config.combiner_builder(args, target_paths)?;
+
+    // The clipboard holds one part at a time, so parts are copied on request,
+    // and saved instead when no clipboard works
+    let (project, report) = if sink == Sink::Clipboard {
+        let clipboard = config.clipboard(args)?;
+        let combiner = builder.build()?;
+        let (project, report) = combiner.run_with_report()?;
+        for warning in &project.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        let parts = combiner.render_parts(&project)?;
+        match copy_parts(&parts, &clipboard) {
+            Err(Error::Clipboard(reason)) => {
+                eprintln!("Warning: cannot copy to the clipboard: {}", reason);
+                save_parts(&parts, &get_output_path(args, &config)?)?;
+            }
+            result => result?,
+        }
+        (project, report)
+    } else {
+        let combiner = builder.sink(sink.clone()).build()?;
+        let (project, report) = combiner.run_with_report()?;
+        report_delivery(&combiner, &project, &sink)?;
+        (project, report)
+    };
+
+    print_report(&report);
+    if let Some(path) = &args.report {
+        std::fs::write(path, report.to_json()?).map_err(|source| Error::Write {
+            path: path.clone(),
+            source,
+        })?;
     }
+    Ok(project)
 }
 
-fn run(target_paths: &[PathBuf], args: &Args) -> Result<(), AppError> {
-    let config = load_config()?;
-    let options = ProcessingOptions::new(args, &config)?;
+// On stderr, so that it stays out of output piped from stdout
+fn print_report(report: &RunReport) {
+    eprintln!("Summary:");
+    let sections = [
+        ("Targets", &report.targets),
+        ("References", &report.references),
+        ("Files", &report.files),
+        ("Dependencies", &report.dependencies),
+        ("External", &report.external),
+    ];
+    for (title, stats) in sections {
+        if stats.files > 0 {
+            eprintln!("  {:<12}  {}", title, section_summary(stats));
+        }
+    }
+    eprintln!(
+        "  {:<12}  {:>9}  {:>9}  {:>9} lines  {:>14}",
+        "Output",
+        plural(report.parts, "part"),
+        format_size(report.output.bytes as u64),
+        report.output.lines,
+        format!("~{} tokens", report.output.estimated_tokens)
+    );
 
-    let combined_source_code = process_files(target_paths, &options)?;
+    if !report.skipped.is_empty() {
+        let reasons: Vec<_> = report
+            .skipped
+            .iter()
+            .map(|(reason, count)| format!("{} {}", reason, count))
+            .collect();
+        eprintln!(
+            "  {:<12}  {} ({})",
+            "Skipped",
+            report.skipped.values().sum::<usize>(),
+            reasons.join(", ")
+        );
+    }
+    if !report.unresolved.is_empty() {
+        let mut reasons: BTreeMap<&str, usize> = BTreeMap::new();
+        for unresolved in &report.unresolved {
+            *reasons.entry(unresolved.reason.as_str()).or_default() += 1;
+        }
+        let reasons: Vec<_> = reasons
+            .iter()
+            .map(|(reason, count)| format!("{} {}", reason, count))
+            .collect();
+        eprintln!(
+            "  {:<12}  {} ({})",
+            "Unresolved",
+            plural(report.unresolved.len(), "import"),
+            reasons.join(", ")
+        );
+    }
 
-    execute_action(args, &config, combined_source_code)
+    let timings = &report.timings;
+    eprintln!(
+        "  {:<12}  collect {} ms, resolve {} ms, render {} ms, deliver {} ms, total {} ms",
+        "Time",
+        timings.collect_ms,
+        timings.resolve_ms,
+        timings.render_ms,
+        timings.deliver_ms,
+        timings.total_ms
+    );
 }
 
-fn load_config() -> io::Result<Config> {
-    Config::load()
+fn section_summary(stats: &SectionStats) -> String {
+    format!(
+        "{:>9}  {:>9}  {:>9} lines  {:>14}",
+        plural(stats.files, "file"),
+        format_size(stats.bytes),
+        stats.lines,
+        format!("~{} tokens", stats.estimated_tokens)
+    )
 }
 
-fn process_files(
-    target_paths: &[PathBuf],
-    options: &ProcessingOptions,
-) -> Result<String, AppError> {
-    let mut processor = FileProcessor::new();
-
-    // Process target files
-    if !options.target_files.is_empty() {
-        processor.combined_source_code.push_str("  <targets>\n");
-        for file_path in &options.target_files {
-            let file_source_code = process_single_file(file_path, options)?;
-            processor.combined_source_code.push_str(&file_source_code);
-            processor.processed_files.insert(file_path.clone());
+fn copy_parts(parts: &[String], clipboard: &Clipboard) -> Result<(), Error> {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    for (index, part) in parts.iter().enumerate() {
+        if index > 0 {
+            println!(
+                "Press Enter to copy part {} of {}, or Ctrl+C to stop.",
+                index + 1,
+                parts.len()
+            );
+            if lines.next().transpose()?.is_none() {
+                break;
+            }
         }
-        processor.combined_source_code.push_str("  </targets>\n");
-    }
-
-    // Process reference files
-    if !options.reference_files.is_empty() {
-        processor.combined_source_code.push_str("  <references>\n");
-        for file_path in &options.reference_files {
-            let file_source_code = process_single_file(file_path, options)?;
-            processor.combined_source_code.push_str(&file_source_code);
-            processor.processed_files.insert(file_path.clone());
+        clipboard.copy(part)?;
+        if parts.len() == 1 {
+            println!("Combined code copied to clipboard.");
+        } else {
+            println!("Part {} of {} copied to clipboard.", index + 1, parts.len());
         }
-        processor.combined_source_code.push_str("  </references>\n");
     }
+    Ok(())
+}
 
-    // Initialize resolvers
-    let mut resolver = if options.deps {
-        Some(DependencyResolver::new(&env::current_dir()?, true)?)
-    } else {
-        None
-    };
-
-    let mut ts_resolver = if options.deps {
-        Some(TypeScriptResolver::new())
-    } else {
-        None
+fn save_parts(parts: &[String], output_path: &Path) -> Result<(), Error> {
+    let paths: Vec<_> = match parts.len() {
+        1 => vec![output_path.to_path_buf()],
+        count => (1..=count)
+            .map(|index| pcc::part_path(output_path, index))
+            .collect(),
     };
+    for (path, part) in paths.iter().zip(parts) {
+        std::fs::write(path, part).map_err(|source| Error::Write {
+            path: path.clone(),
+            source,
+        })?;
+        println!("Combined code saved to file instead: {}", path.display());
+    }
+    Ok(())
+}
 
-    // Process main files and their dependencies
-    for target_path in target_paths {
-        if target_path.is_file() {
-            if options.target_files.contains(target_path)
-                || options.reference_files.contains(target_path)
-            {
-                continue;
-            }
-
-            if options.deps
-                && TypeScriptResolver::is_supported_file(target_path)
-                && resolver.is_some()
-                && ts_resolver.is_some()
-            {
-                processor.process_file_with_deps(
-                    target_path,
-                    options,
-                    resolver.as_mut().unwrap(),
-                    ts_resolver.as_mut().unwrap(),
-                )?;
+fn report_delivery(
+    combiner: &Combiner,
+    project: &CombinedProject,
+    sink: &Sink,
+) -> Result<(), Error> {
+    for warning in &project.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    match sink {
+        Sink::Clipboard => println!("Combined code copied to clipboard."),
+        Sink::File(output_path) => {
+            let parts = combiner.render_parts(project)?.len();
+            if parts == 1 {
+                println!("Combined code saved to file: {}", output_path.display());
             } else {
-                let file_source_code = process_single_file(target_path, options)?;
-                processor.combined_source_code.push_str(&file_source_code);
-                processor.processed_files.insert(target_path.to_path_buf());
-            }
-        } else if target_path.is_dir() {
-            for entry in Walk::new(target_path).filter_map(Result::ok) {
-                let path = entry.path();
-                if path.is_file()
-                    && !is_ignored(path, &options.ignore_patterns)
-                    && !options.target_files.contains(&path.to_path_buf())
-                    && !options.reference_files.contains(&path.to_path_buf())
-                    && !processor.processed_files.contains(path)
-                {
-                    if options.deps
-                        && TypeScriptResolver::is_supported_file(path)
-                        && resolver.is_some()
-                        && ts_resolver.is_some()
-                    {
-                        processor.process_file_with_deps(
-                            path,
-                            options,
-                            resolver.as_mut().unwrap(),
-                            ts_resolver.as_mut().unwrap(),
-                        )?;
-                    } else {
-                        let file_source_code = process_single_file(path, options)?;
-                        processor.combined_source_code.push_str(&file_source_code);
-                        processor.processed_files.insert(path.to_path_buf());
-                    }
+                println!("Combined code saved to {} files:", parts);
+                for index in 1..=parts {
+                    println!("  {}", pcc::part_path(output_path, index).display());
                 }
             }
         }
+        Sink::None | Sink::Stdout => {}
     }
-
-    // Add dependencies section
-    processor.add_dependencies_section(options)?;
-
-    Ok(processor.finalize())
+    Ok(())
 }
 
-fn process_single_file_with_importers(
-    file_path: &Path,
-    options: &ProcessingOptions,
-    importers: &HashSet<PathBuf>,
-) -> Result<String, AppError> {
-    if is_ignored(file_path, &options.ignore_patterns) {
-        return Ok(String::new());
-    }
+// Nothing is written or copied, so no action needs to be configured
+fn run_ls(target_paths: &[PathBuf], args: &Args) -> Result<(), Error> {
+    let config = Config::load()?;
+    let listing = config
+        .combiner_builder(args, target_paths)?
+        .build()?
+        .dry_run()?;
+    print_listing(&listing);
+    Ok(())
+}
 
-    let file_content = fs::read_to_string(file_path)?;
-    let path_to_display = if options.use_relative_paths {
-        match file_path.strip_prefix(env::current_dir()?) {
-            Ok(relative) => relative.to_path_buf(),
-            Err(_) => file_path.to_path_buf(),
+fn print_listing(listing: &FileListing) {
+    let width = listing
+        .included()
+        .map(|file| file.path.display().to_string().len())
+        .chain(
+            listing
+                .skipped
+                .iter()
+                .map(|file| file.path.display().to_string().len()),
+        )
+        .max()
+        .unwrap_or(0);
+
+    let sections = [
+        ("Targets", &listing.targets),
+        ("References", &listing.references),
+        ("Files", &listing.files),
+        ("Dependencies", &listing.dependencies),
+        ("External", &listing.external),
+    ];
+    for (title, files) in sections {
+        if files.is_empty() {
+            continue;
         }
-    } else {
-        file_path.to_path_buf()
-    };
-
-    let mut output = format!("  <file name=\"{}\">\n", path_to_display.display());
+        println!("{}:", title);
+        for file in files {
+            print_listed_file(file, width);
+        }
+    }
 
-    // Add importers section
-    if !importers.is_empty() {
-        output.push_str("    <imported_by>\n");
-        for importer in importers {
-            output.push_str(&format!(
-                "      <importer>{}</importer>\n",
-                importer.display()
-            ));
+    if !listing.skipped.is_empty() {
+        println!("Skipped:");
+        for file in &listing.skipped {
+            println!(
+                "  {:<width$}  {}",
+                file.path.display(),
+                file.reason,
+                width = width
+            );
         }
-        output.push_str("    </imported_by>\n");
     }
 
-    // Add file content
-    output.push_str(
-        &file_content
-            .lines()
-            .map(|line| format!("    {}", line))
-            .collect::<Vec<_>>()
-            .join("\n"),
+    let included: Vec<_> = listing.included().collect();
+    println!(
+        "Total: {}, {}, ~{} tokens; {} skipped",
+        plural(included.len(), "file"),
+        format_size(included.iter().map(|file| file.bytes).sum()),
+        included
+            .iter()
+            .map(|file| file.estimated_tokens)
+            .sum::<usize>(),
+        listing.skipped.len()
+    );
+}
+
+fn print_listed_file(file: &ListedFile, width: usize) {
+    println!(
+        "  {:<width$}  {:>9}  {:>14}  {}",
+        file.path.display(),
+        format_size(file.bytes),
+        format!("~{} tokens", file.estimated_tokens),
+        file.reason,
+        width = width
     );
-    output.push_str("\n  </file>\n");
+}
 
-    Ok(output)
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
 }
 
-fn process_single_file(file_path: &Path, options: &ProcessingOptions) -> Result<String, AppError> {
-    if is_ignored(file_path, &options.ignore_patterns) {
-        return Ok(String::new());
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
     }
+}
+
+// Runs until interrupted. A change to the configuration file rebuilds the
+// combiner from it, unless the new configuration cannot be loaded.
+fn run_watch(target_paths: &[PathBuf], args: &Args, debounce: Duration) -> Result<(), Error> {
+    let config_paths: Vec<_> = Config::path()
+        .ok()
+        .into_iter()
+        .chain([Config::project_path()?])
+        .map(|path| canonicalize_or_keep(&path))
+        .collect();
+    let current_dir = env::current_dir()?;
+    let mut config = Config::load()?;
+
+    loop {
+        let sink = select_sink(args, &config)?;
+        let combiner = config
+            .combiner_builder(args, target_paths)?
+            .sink(sink.clone())
+            .clipboard(config.clipboard(args)?)
+            .build()?;
+
+        let mut config_changed = false;
+        combiner.watch(debounce, &config_paths, |run| {
+            if !run.changed.is_empty() {
+                let changed: Vec<_> = run
+                    .changed
+                    .iter()
+                    .map(|path| {
+                        path.strip_prefix(&current_dir)
+                            .unwrap_or(path)
+                            .display()
+                            .to_string()
+                    })
+                    .collect();
+                println!("Changed: {}", changed.join(", "));
+            }
+            match &run.result {
+                Ok(project) => {
+                    if let Err(err) = report_delivery(&combiner, project, &sink) {
+                        eprintln!("Error: {}", err);
+                    }
+                }
+                Err(err) => eprintln!("Error: {}", err),
+            }
 
-    let file_content = fs::read_to_string(file_path)?;
-    let path_to_display = if options.use_relative_paths {
-        match file_path.strip_prefix(env::current_dir()?) {
-            Ok(relative) => relative.to_path_buf(),
-            Err(_) => file_path.to_path_buf(),
+            config_changed = config_paths.iter().any(|path| run.changed.contains(path));
+            if config_changed {
+                return ControlFlow::Break(());
+            }
+            if run.changed.is_empty() {
+                println!("Watching for changes. Press Ctrl+C to stop.");
+            }
+            ControlFlow::Continue(())
+        })?;
+
+        if !config_changed {
+            return Ok(());
         }
-    } else {
-        file_path.to_path_buf()
-    };
+        match Config::load() {
+            Ok(reloaded) => {
+                println!("Configuration changed, reloading.");
+                config = reloaded;
+            }
+            Err(err) => eprintln!("Error: keeping the previous configuration: {}", err),
+        }
+    }
+}
 
-    Ok(format_file_content(&path_to_display, &file_content))
+fn canonicalize_or_keep(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
 }
 
-fn format_file_content(file_path: &Path, file_content: &str) -> String {
-    format!(
-        "  <file name=\"{}\">\n{}\n  </file>\n",
-        file_path.display(),
-        file_content
-            .lines()
-            .map(|line| format!("    {}", line))
-            .collect::<Vec<_>>()
-            .join("\n")
-    )
+// Resolve the dependencies of every supported file under the targets and
+// list each circular import chain
+fn run_cycles(target_paths: &[PathBuf], args: &Args) -> Result<Vec<Vec<PathBuf>>, Error> {
+    let config = Config::load()?;
+    config
+        .combiner_builder(args, target_paths)?
+        .build()?
+        .find_cycles()
+}
+
+fn format_cycle(cycle: &[PathBuf]) -> String {
+    cycle
+        .iter()
+        .map(|file| file.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+// Stdout carries the protocol, so nothing else may be printed there
+fn run_mcp(args: &Args) -> Result<(), Error> {
+    let config = Config::load()?;
+    let stdin = io::stdin();
+    McpServer::new(config.combiner_builder(args, &[])?).serve(stdin.lock(), io::stdout().lock())
 }
 
-fn execute_action(
-    args: &Args,
-    config: &Config,
-    combined_source_code: String,
-) -> Result<(), AppError> {
+fn run_serve(args: &Args, root: &Path, port: u16) -> Result<(), Error> {
+    // Paths in responses are shown relative to the working directory, and
+    // the project configuration file is the one of the root
+    env::set_current_dir(root)?;
+    let config = Config::load()?;
+
+    let mut server = HttpServer::new(config.combiner_builder(args, &[])?, Path::new("."), port)?;
+    println!(
+        "Serving {} on http://127.0.0.1:{}",
+        env::current_dir()?.display(),
+        port
+    );
+    server.serve()
+}
+
+fn select_sink(args: &Args, config: &Config) -> Result<Sink, Error> {
     if args.copy {
-        copy_to_clipboard(combined_source_code)
+        Ok(Sink::Clipboard)
     } else if args.save {
-        let output_path = get_output_path(args, config)?;
-        save_to_file(combined_source_code, &output_path)
+        Ok(Sink::File(get_output_path(args, config)?))
     } else if let Some(action) = &config.default.action {
         match action.as_str() {
-            "copy" => copy_to_clipboard(combined_source_code),
-            "save" => {
-                let output_path = get_output_path(args, config)?;
-                save_to_file(combined_source_code, &output_path)
-            }
-            _ => {
-                eprintln!("Unknown action: {}", action);
-                std::process::exit(1);
-            }
+            "copy" => Ok(Sink::Clipboard),
+            "save" => Ok(Sink::File(get_output_path(args, config)?)),
+            _ => Err(Error::Config(format!(
+                "unknown action `{}`; expected `copy` or `save`",
+                action
+            ))),
         }
     } else {
-        eprintln!("No action specified");
-        std::process::exit(1);
+        Err(Error::Usage(
+            "No action specified; pass --copy or --save, or set `action` in the configuration file."
+                .to_string(),
+        ))
     }
 }
 
-fn get_output_path(args: &Args, config: &Config) -> io::Result<PathBuf> {
+fn get_output_path(args: &Args, config: &Config) -> Result<PathBuf, Error> {
     if let Some(path) = &args.output_path {
-        return Ok(expand_tilde(path));
+        return expand_tilde(path);
     }
 
     if let Some(path) = &config.default.output_path {
-        return Ok(expand_tilde(path));
+        return expand_tilde(path);
     }
 
     let current_dir = env::current_dir()?;
@@ -440,87 +745,13 @@ fn get_output_path(args: &Args, config: &Config) -> io::Result<PathBuf> {
     Ok(current_dir.join("combined_code.txt"))
 }
 
-fn copy_to_clipboard(combined_code: String) -> Result<(), AppError> {
-    let mut ctx: ClipboardContext = ClipboardProvider::new()?;
-    ctx.set_contents(combined_code)?;
-    println!("Combined code copied to clipboard.");
-    Ok(())
-}
-
-fn save_to_file(combined_code: String, output_path: &Path) -> Result<(), AppError> {
-    write_combined_code(output_path, &combined_code)?;
-    println!("Combined code saved to file: {}", output_path.display());
-    Ok(())
-}
-
-fn write_combined_code(
-    output_file_path: &Path,
-    combined_source_code: &str,
-) -> Result<(), AppError> {
-    fs::write(output_file_path, combined_source_code)?;
-    Ok(())
-}
-
-fn is_ignored(file_path: &Path, ignore_patterns: &str) -> bool {
-    let path_str = file_path.to_string_lossy();
-
-    ignore_patterns
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .any(|pattern| {
-            let regex_pattern = convert_ignore_pattern_to_regex(pattern);
-            match Regex::new(&regex_pattern) {
-                Ok(regex) => regex.is_match(&path_str),
-                Err(_) => false,
-            }
-        })
-}
-
-fn convert_ignore_pattern_to_regex(pattern: &str) -> String {
-    let mut regex_pattern = String::new();
-
-    if pattern.ends_with('/') {
-        regex_pattern.push_str(".*");
-    }
-
-    let mut in_bracket = false;
-    for c in pattern.chars() {
-        match c {
-            '*' if !in_bracket => regex_pattern.push_str(".*"),
-            '?' if !in_bracket => regex_pattern.push_str("."),
-            '[' => {
-                in_bracket = true;
-                regex_pattern.push(c);
-            }
-            ']' => {
-                in_bracket = false;
-                regex_pattern.push(c);
-            }
-            '!' if in_bracket => regex_pattern.push('^'),
-            '/' => regex_pattern.push_str("\\/"),
-            '.' => regex_pattern.push_str("\\."),
-            _ => regex_pattern.push(c),
-        }
-    }
-
-    if !pattern.contains('/') {
-        format!("(?:^|.*/){}$", regex_pattern)
-    } else {
-        format!("^{}$", regex_pattern)
-    }
-}
-
-fn expand_tilde(path: &str) -> PathBuf {
+fn expand_tilde(path: &str) -> Result<PathBuf, Error> {
     if !path.starts_with('~') {
-        return PathBuf::from(path);
+        return Ok(PathBuf::from(path));
     }
 
-    let home_dir = env::var("HOME")
-        .or_else(|_| env::var("USERPROFILE"))
-        .expect("Failed to get home directory");
-
     let stripped_path = path.strip_prefix("~/").unwrap_or(path);
-    let mut expanded_path = PathBuf::from(home_dir);
+    let mut expanded_path = config::home_dir()?;
     expanded_path.push(stripped_path);
-    expanded_path
+    Ok(expanded_path)
 }
@@ -0,0 +1,365 @@
+use crate::combiner::CombinerBuilder;
+use crate::error::Result;
+use crate::session::{CombineRequest, Session};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// Tool failures are reported to the model as text, not as protocol errors
+type ToolResult = std::result::Result<String, String>;
+
+/// A Model Context Protocol server exposing the combiner as tools, speaking
+/// newline-delimited JSON-RPC.
+///
+/// The resolvers are kept between tool calls, so tsconfig, workspace and
+/// module resolution caches stay warm for the whole session, until a tsconfig
+/// file or workspace manifest they read changes.
+pub struct McpServer {
+    session: Session,
+}
+
+impl McpServer {
+    /// `template` provides the defaults of every tool call: ignore rules,
+    /// import filters, path display and the cache.
+    pub fn new(template: CombinerBuilder) -> Self {
+        Self {
+            session: Session::new(template),
+        }
+    }
+
+    /// Answers requests read from `input` until it is closed.
+    pub fn serve<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(response) = self.handle_message(&line) {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_message(&mut self, message: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(message) {
+            Ok(request) => request,
+            Err(err) => return Some(error_response(Value::Null, PARSE_ERROR, &err.to_string())),
+        };
+
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            // Responses to requests we never send are ignored
+            if request.get("result").is_some() || request.get("error").is_some() {
+                return None;
+            }
+            let id = request.get("id").cloned().unwrap_or(Value::Null);
+            return Some(error_response(id, INVALID_REQUEST, "missing method"));
+        };
+
+        // Notifications such as `notifications/initialized` get no response
+        let id = request.get("id").cloned()?;
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(initialize_result(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            "tools/call" => self.call_tool(&params),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn call_tool(&mut self, params: &Value) -> std::result::Result<Value, (i64, String)> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or((INVALID_PARAMS, "missing tool name".to_string()))?;
+        let arguments = params
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+
+        let output = match name {
+            "combine" => self.combine(&arguments),
+            "list_files" => self.list_files(&arguments),
+            "dependency_graph" => self.dependency_graph(&arguments),
+            "importers" => self.importers(&arguments),
+            _ => return Err((INVALID_PARAMS, format!("unknown tool: {}", name))),
+        };
+
+        let (text, is_error) = match output {
+            Ok(text) => (text, false),
+            Err(message) => (message, true),
+        };
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error,
+        }))
+    }
+
+    fn combine(&mut self, arguments: &Value) -> ToolResult {
+        let request: CombineRequest =
+            serde_json::from_value(arguments.clone()).map_err(|err| err.to_string())?;
+        self.session.combine(&request).map(|(output, _)| output)
+    }
+
+    fn list_files(&mut self, arguments: &Value) -> ToolResult {
+        let path = arguments.get("path").and_then(Value::as_str).unwrap_or(".");
+        Ok(self.session.list_files(Path::new(path))?.join("\n"))
+    }
+
+    fn dependency_graph(&mut self, arguments: &Value) -> ToolResult {
+        let file = required_file(arguments, "file")?;
+        to_pretty_json(&self.session.dependency_graph(&file)?)
+    }
+
+    fn importers(&mut self, arguments: &Value) -> ToolResult {
+        let file = required_file(arguments, "file")?;
+        let root = arguments.get("root").and_then(Value::as_str).unwrap_or(".");
+        let transitive = arguments
+            .get("transitive")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        to_pretty_json(&self.session.importers(&file, Path::new(root), transitive)?)
+    }
+}
+
+fn initialize_result(params: &Value) -> Value {
+    json!({
+        "protocolVersion": params
+            .get("protocolVersion")
+            .and_then(Value::as_str)
+            .unwrap_or(PROTOCOL_VERSION),
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "pcc", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn tool_definitions() -> Value {
+    json!([
+        {
+            "name": "combine",
+            "description": "Combine source files, and optionally everything they import, into one XML or JSON document.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "targets": { "type": "array", "items": { "type": "string" }, "description": "Files or directories to combine" },
+                    "references": { "type": "array", "items": { "type": "string" }, "description": "Files to include in the <references> section" },
+                    "deps": { "type": "boolean", "description": "Follow imports and include the dependencies" },
+                    "format": { "type": "string", "enum": ["xml", "json"] },
+                    "max_tokens": { "type": "integer", "description": "Fail instead of returning more than about this many tokens" }
+                }
+            }
+        },
+        {
+            "name": "list_files",
+            "description": "List the files under a path that would be combined, honoring the ignore rules.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Directory to list, the working directory by default" }
+                }
+            }
+        },
+        {
+            "name": "dependency_graph",
+            "description": "Resolve the imports of a file and return every file it reaches with its direct imports.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "file": { "type": "string" }
+                },
+                "required": ["file"]
+            }
+        },
+        {
+            "name": "importers",
+            "description": "Find the files under the project root that import a file.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "file": { "type": "string" },
+                    "root": { "type": "string", "description": "Directory whose files are searched, the working directory by default" },
+                    "transitive": { "type": "boolean", "description": "Also include files importing it indirectly" }
+                },
+                "required": ["file"]
+            }
+        }
+    ])
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn required_file(arguments: &Value, key: &str) -> std::result::Result<PathBuf, String> {
+    let path = arguments
+        .get(key)
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .ok_or_else(|| format!("`{}` is required", key))?;
+    if !path.is_file() {
+        return Err(format!("File not found: {}", path.display()));
+    }
+    Ok(path)
+}
+
+fn to_pretty_json<T: Serialize>(value: &T) -> ToolResult {
+    serde_json::to_string_pretty(value).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combiner::Combiner;
+    use std::fs;
+
+    fn server() -> McpServer {
+        McpServer::new(Combiner::builder().cache(false))
+    }
+
+    fn call(server: &mut McpServer, name: &str, arguments: Value) -> Value {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments },
+        });
+        server.handle_message(&request.to_string()).unwrap()
+    }
+
+    #[test]
+    fn initialize_echoes_the_protocol_version() {
+        let response = server()
+            .handle_message(
+                r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26"}}"#,
+            )
+            .unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(response["result"]["serverInfo"]["name"], "pcc");
+
+        let response = server()
+            .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"initialize"}"#)
+            .unwrap();
+        assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn notifications_and_responses_get_no_response() {
+        let mut server = server();
+        assert_eq!(
+            server.handle_message(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#),
+            None
+        );
+        assert_eq!(
+            server.handle_message(r#"{"jsonrpc":"2.0","id":3,"result":{}}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_and_unknown_requests_are_errors() {
+        let mut server = server();
+        let response = server.handle_message("{not json").unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        assert_eq!(response["id"], Value::Null);
+
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":4}"#)
+            .unwrap();
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":5,"method":"resources/list"}"#)
+            .unwrap();
+        assert_eq!(response["id"], 5);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(
+            response["error"]["message"],
+            "unknown method: resources/list"
+        );
+    }
+
+    #[test]
+    fn unknown_tools_are_protocol_errors() {
+        let response = call(&mut server(), "format_disk", json!({}));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert_eq!(response["error"]["message"], "unknown tool: format_disk");
+    }
+
+    #[test]
+    fn tool_failures_are_reported_as_results() {
+        let mut server = server();
+        let response = call(&mut server, "dependency_graph", json!({}));
+        assert_eq!(response["result"]["isError"], true);
+        assert_eq!(
+            response["result"]["content"][0]["text"],
+            "`file` is required"
+        );
+
+        let response = call(
+            &mut server,
+            "importers",
+            json!({ "file": "/nonexistent/a.ts" }),
+        );
+        assert_eq!(response["result"]["isError"], true);
+        assert_eq!(
+            response["result"]["content"][0]["text"],
+            "File not found: /nonexistent/a.ts"
+        );
+
+        let response = call(&mut server, "combine", json!({ "targets": 3 }));
+        assert_eq!(response["result"]["isError"], true);
+    }
+
+    #[test]
+    fn tools_answer_over_the_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("a.ts"), "export const a = 1;").unwrap();
+
+        let list = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "list_files", "arguments": { "path": root } },
+        });
+        let input = format!(
+            "{}\n\n{}\n",
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#, list
+        );
+        let mut output = Vec::new();
+        server().serve(input.as_bytes(), &mut output).unwrap();
+
+        let lines: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["result"]["isError"], false);
+        let text = lines[0]["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.ends_with("a.ts"), "{}", text);
+    }
+}
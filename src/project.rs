@@ -0,0 +1,406 @@
+use crate::dependency_resolver::{ImportKind, ParseIssue, UnresolvedImport};
+use crate::external_types::ExternalTypes;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The files of a combined project and what was learned about them while
+/// resolving their dependencies.
+///
+/// Paths, importers included, are the ones shown in the output: relative to
+/// the working directory unless another `PathStyle` is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CombinedProject {
+    /// Files to be modified, passed with `--target`
+    pub targets: Vec<SourceFile>,
+    /// Files included for context, passed with `--reference`
+    pub references: Vec<SourceFile>,
+    /// Files found under the combined paths
+    pub files: Vec<SourceFile>,
+    /// Files reached only by following imports, and definitions reached by
+    /// following the references of symbols
+    pub dependencies: Vec<Dependency>,
+    /// Type declarations of the imported packages, with `--external-types`
+    #[serde(default)]
+    pub external: Vec<ExternalTypes>,
+    /// Circular import chains, each ending with its first file
+    pub cycles: Vec<Vec<PathBuf>>,
+    /// Files whose imports may be incomplete because of syntax errors
+    pub parse_errors: BTreeMap<PathBuf, Vec<ParseIssue>>,
+    /// Imports that did not lead to a file, sorted by importing file
+    #[serde(default)]
+    pub unresolved: Vec<UnresolvedImport>,
+    /// Problems that did not prevent combining, e.g. an unwritable cache
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub content: String,
+    /// The lines `content` was taken from, when only part of the file was
+    /// requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines: Option<LineRange>,
+    /// Why the file could not be read, when an `<error>` element is rendered
+    /// in place of its content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A 1-based, inclusive range of lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Choices that change how a project is rendered as XML.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Prefixes every line of content with its line number in the file
+    pub line_numbers: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    pub path: PathBuf,
+    pub imported_by: Vec<Importer>,
+    /// `None` for binary assets, which are listed by name and size only
+    pub content: Option<String>,
+    pub bytes: u64,
+    /// The lines `content` was taken from, for definitions reached by
+    /// following the references of a symbol
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines: Option<LineRange>,
+    /// Why the file could not be read, when an `<error>` element is rendered
+    /// in place of its content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Importer {
+    pub path: PathBuf,
+    /// How the file is imported, or `None` when it is only reached through
+    /// other dependencies
+    pub kind: Option<ImportKind>,
+}
+
+/// Size figures of a combined project and its rendered output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectStats {
+    /// Targets, references and files found under the combined paths
+    pub files: usize,
+    pub dependencies: usize,
+    pub bytes: usize,
+    pub lines: usize,
+    pub estimated_tokens: usize,
+    pub cycles: usize,
+    pub parse_errors: usize,
+}
+
+/// Roughly estimates how many LLM tokens `text` takes, assuming about four
+/// bytes per token as is typical for source code.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+impl CombinedProject {
+    /// Renders the project in the XML layout written by the `pcc` command.
+    pub fn to_xml(&self) -> String {
+        self.to_xml_with(RenderOptions::default())
+    }
+
+    pub fn to_xml_with(&self, options: RenderOptions) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<project>\n");
+
+        if !self.targets.is_empty() {
+            xml.push_str("  <targets>\n");
+            for file in &self.targets {
+                xml.push_str(&format_file_content(file, options));
+            }
+            xml.push_str("  </targets>\n");
+        }
+
+        if !self.references.is_empty() {
+            xml.push_str("  <references>\n");
+            for file in &self.references {
+                xml.push_str(&format_file_content(file, options));
+            }
+            xml.push_str("  </references>\n");
+        }
+
+        for file in &self.files {
+            xml.push_str(&format_file_content(file, options));
+        }
+
+        if !self.dependencies.is_empty() {
+            xml.push_str("  <dependencies>\n");
+            for dependency in &self.dependencies {
+                xml.push_str(&format_dependency_entry(dependency, options));
+            }
+            xml.push_str("  </dependencies>\n");
+        }
+
+        if !self.external.is_empty() {
+            xml.push_str("  <external>\n");
+            for types in &self.external {
+                xml.push_str(&format_external_entry(types, options));
+            }
+            xml.push_str("  </external>\n");
+        }
+        xml.push_str(&self.cycles_section());
+        xml.push_str(&self.parse_errors_section());
+        xml.push_str(&self.unresolved_section());
+
+        xml.push_str("</project>\n");
+        xml
+    }
+
+    /// Measures the project, given its rendered `output`.
+    pub fn stats(&self, output: &str) -> ProjectStats {
+        ProjectStats {
+            files: self.targets.len() + self.references.len() + self.files.len(),
+            dependencies: self.dependencies.len(),
+            bytes: output.len(),
+            lines: output.lines().count(),
+            estimated_tokens: estimate_tokens(output),
+            cycles: self.cycles.len(),
+            parse_errors: self.parse_errors.len(),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// The `<cycles>` section, empty when there are no cycles.
+    pub(crate) fn cycles_section(&self) -> String {
+        let mut xml = String::new();
+        if self.cycles.is_empty() {
+            return xml;
+        }
+
+        xml.push_str("  <cycles>\n");
+        for cycle in &self.cycles {
+            xml.push_str("    <cycle>\n");
+            for file in cycle {
+                xml.push_str(&format!("      <file>{}</file>\n", file.display()));
+            }
+            xml.push_str("    </cycle>\n");
+        }
+        xml.push_str("  </cycles>\n");
+        xml
+    }
+
+    /// The `<parse_errors>` section, empty when every file parsed.
+    pub(crate) fn parse_errors_section(&self) -> String {
+        let mut xml = String::new();
+        if self.parse_errors.is_empty() {
+            return xml;
+        }
+
+        xml.push_str("  <parse_errors>\n");
+        for (file, errors) in &self.parse_errors {
+            xml.push_str(&format!("    <file name=\"{}\">\n", file.display()));
+            for error in errors {
+                xml.push_str(&format!(
+                    "      <error line=\"{}\" column=\"{}\">{}</error>\n",
+                    error.line,
+                    error.column,
+                    escape_xml(&error.message)
+                ));
+            }
+            xml.push_str("    </file>\n");
+        }
+        xml.push_str("  </parse_errors>\n");
+        xml
+    }
+
+    /// The `<unresolved>` section, empty when every import was resolved.
+    pub(crate) fn unresolved_section(&self) -> String {
+        let mut xml = String::new();
+        if self.unresolved.is_empty() {
+            return xml;
+        }
+
+        xml.push_str("  <unresolved>\n");
+        for unresolved in &self.unresolved {
+            xml.push_str(&format!(
+                "    <import from=\"{}\" reason=\"{}\">{}</import>\n",
+                unresolved.from.display(),
+                unresolved.reason.as_str(),
+                escape_xml(&unresolved.specifier)
+            ));
+        }
+        xml.push_str("  </unresolved>\n");
+        xml
+    }
+}
+
+/// One entry of the `<dependencies>` section, indented to sit inside it.
+pub(crate) fn format_dependency_entry(dependency: &Dependency, options: RenderOptions) -> String {
+    let file_xml = match (&dependency.content, &dependency.error) {
+        (_, Some(error)) => format_error(&dependency.path, error),
+        (Some(content), None) => format_dependency(dependency, content, options),
+        // Images, fonts and other binary assets are listed by name only
+        (None, None) => format!(
+            "  <asset name=\"{}\" bytes=\"{}\" />\n",
+            dependency.path.display(),
+            dependency.bytes
+        ),
+    };
+    // Add additional indentation for dependencies section
+    let mut xml = file_xml
+        .lines()
+        .map(|line| format!("  {}", line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    xml.push('\n');
+    xml
+}
+
+fn format_dependency(dependency: &Dependency, content: &str, options: RenderOptions) -> String {
+    let mut output = format!(
+        "  <file name=\"{}\"{}>\n",
+        dependency.path.display(),
+        lines_attribute(dependency.lines)
+    );
+
+    // Add importers section
+    if !dependency.imported_by.is_empty() {
+        output.push_str("    <imported_by>\n");
+        for importer in &dependency.imported_by {
+            // Runtime and indirect importers keep the plain form
+            let kind_attribute = match importer.kind {
+                Some(kind) if kind != ImportKind::Runtime => {
+                    format!(" kind=\"{}\"", kind.as_str())
+                }
+                _ => String::new(),
+            };
+            output.push_str(&format!(
+                "      <importer{}>{}</importer>\n",
+                kind_attribute,
+                importer.path.display()
+            ));
+        }
+        output.push_str("    </imported_by>\n");
+    }
+
+    // Add file content
+    let first_line = dependency.lines.map_or(1, |range| range.start);
+    output.push_str(&indent_content(content, first_line, options));
+    output.push_str("\n  </file>\n");
+
+    output
+}
+
+/// One entry of the `<external>` section, indented to sit inside it.
+pub(crate) fn format_external_entry(types: &ExternalTypes, options: RenderOptions) -> String {
+    let version = types
+        .version
+        .as_ref()
+        .map(|version| format!(" version=\"{}\"", version))
+        .unwrap_or_default();
+    let symbols = if types.symbols.is_empty() {
+        String::new()
+    } else {
+        format!(" symbols=\"{}\"", types.symbols.join(", "))
+    };
+    let mut output = format!(
+        "    <package name=\"{}\"{} file=\"{}\"{}>\n",
+        types.package,
+        version,
+        types.path.display(),
+        symbols
+    );
+
+    output.push_str("      <imported_by>\n");
+    for importer in &types.imported_by {
+        output.push_str(&format!(
+            "        <importer>{}</importer>\n",
+            importer.display()
+        ));
+    }
+    output.push_str("      </imported_by>\n");
+
+    // Trimmed declarations no longer start at line 1, so they are never numbered
+    let options = if types.symbols.is_empty() {
+        options
+    } else {
+        RenderOptions::default()
+    };
+    let content = indent_content(&types.content, 1, options)
+        .lines()
+        .map(|line| format!("  {}", line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    output.push_str(&content);
+    output.push_str("\n    </package>\n");
+    output
+}
+
+pub(crate) fn format_file_content(file: &SourceFile, options: RenderOptions) -> String {
+    if let Some(error) = &file.error {
+        return format_error(&file.path, error);
+    }
+    let first_line = file.lines.map_or(1, |range| range.start);
+
+    format!(
+        "  <file name=\"{}\"{}>\n{}\n  </file>\n",
+        file.path.display(),
+        lines_attribute(file.lines),
+        indent_content(&file.content, first_line, options)
+    )
+}
+
+// Stands in for a file that could not be read
+fn format_error(path: &Path, error: &str) -> String {
+    format!(
+        "  <error name=\"{}\">{}</error>\n",
+        path.display(),
+        escape_xml(error)
+    )
+}
+
+fn lines_attribute(lines: Option<LineRange>) -> String {
+    match lines {
+        Some(range) => format!(" lines=\"{}-{}\"", range.start, range.end),
+        None => String::new(),
+    }
+}
+
+// Numbers are right-aligned to the widest one, so the code stays aligned
+fn indent_content(content: &str, first_line: usize, options: RenderOptions) -> String {
+    let width = (first_line + content.lines().count())
+        .saturating_sub(1)
+        .to_string()
+        .len();
+
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            if options.line_numbers {
+                format!(
+                    "    {:>width$} | {}",
+                    first_line + index,
+                    line,
+                    width = width
+                )
+            } else {
+                format!("    {}", line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,234 @@
+use crate::combiner::{Assembly, Combiner, Resolvers};
+use crate::dependency_resolver::UnresolvedImport;
+use crate::error::Result;
+use crate::listing::SkipReason;
+use crate::project::{estimate_tokens, CombinedProject, ProjectStats};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// What a run combined, what it left out and how long each phase took, as
+/// written by `--report`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunReport {
+    pub targets: SectionStats,
+    pub references: SectionStats,
+    pub files: SectionStats,
+    pub dependencies: SectionStats,
+    pub external: SectionStats,
+    /// The rendered output, all parts together
+    pub output: ProjectStats,
+    pub parts: usize,
+    /// Files left out, counted by reason: `ignored`, `duplicate`, `binary`,
+    /// `node_modules` or `unreadable`
+    pub skipped: BTreeMap<String, usize>,
+    /// Imports that did not lead to a file
+    pub unresolved: Vec<UnresolvedImport>,
+    pub timings: Timings,
+}
+
+/// Sizes of the files in one section of the output, before rendering.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionStats {
+    pub files: usize,
+    /// Of the included content, or of the file for binary assets
+    pub bytes: u64,
+    pub lines: usize,
+    pub estimated_tokens: usize,
+}
+
+/// Milliseconds spent in each phase of a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timings {
+    /// Finding and reading the files
+    pub collect_ms: u64,
+    /// Loading the resolvers, resolving dependencies and following symbol
+    /// references
+    pub resolve_ms: u64,
+    pub render_ms: u64,
+    /// Writing or copying the output
+    pub deliver_ms: u64,
+    pub total_ms: u64,
+}
+
+impl SkipReason {
+    /// The name the report counts this reason under.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SkipReason::Ignored(_) => "ignored",
+            SkipReason::Duplicate => "duplicate",
+            SkipReason::Binary => "binary",
+            SkipReason::NodeModules => "node_modules",
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::OutsideRoot => "outside_root",
+        }
+    }
+}
+
+impl RunReport {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl Combiner {
+    /// Like `run`, also measuring what was combined and how long it took.
+    pub fn run_with_report(&self) -> Result<(CombinedProject, RunReport)> {
+        let started = Instant::now();
+        let (assembly, setup) = if self.options().deps {
+            let mut resolvers = Resolvers::new(self.options())?;
+            let setup = started.elapsed();
+            (self.assemble(Some(&mut resolvers))?, setup)
+        } else {
+            (self.assemble(None)?, Duration::ZERO)
+        };
+        let resolving = setup + assembly.resolve_time;
+        let assembled = started.elapsed();
+
+        let rendering = Instant::now();
+        let parts = self.render_parts(&assembly.project)?;
+        let rendered = rendering.elapsed();
+
+        let delivering = Instant::now();
+        let output = parts.concat();
+        let part_count = parts.len();
+        self.write_parts(parts)?;
+        let delivered = delivering.elapsed();
+
+        let mut report = report(&assembly, &output);
+        report.parts = part_count;
+        report.timings = Timings {
+            collect_ms: millis(assembled.saturating_sub(resolving)),
+            resolve_ms: millis(resolving),
+            render_ms: millis(rendered),
+            deliver_ms: millis(delivered),
+            total_ms: millis(started.elapsed()),
+        };
+        Ok((assembly.project, report))
+    }
+}
+
+fn report(assembly: &Assembly, output: &str) -> RunReport {
+    let project = &assembly.project;
+    let mut skipped = BTreeMap::new();
+    for file in &assembly.skipped {
+        *skipped.entry(file.reason.kind().to_string()).or_default() += 1;
+    }
+
+    RunReport {
+        targets: section(
+            project
+                .targets
+                .iter()
+                .map(|file| (file.content.as_str(), 0)),
+        ),
+        references: section(
+            project
+                .references
+                .iter()
+                .map(|file| (file.content.as_str(), 0)),
+        ),
+        files: section(project.files.iter().map(|file| (file.content.as_str(), 0))),
+        dependencies: section(project.dependencies.iter().map(
+            |dependency| match &dependency.content {
+                Some(content) => (content.as_str(), 0),
+                None => ("", dependency.bytes),
+            },
+        )),
+        external: section(
+            project
+                .external
+                .iter()
+                .map(|types| (types.content.as_str(), 0)),
+        ),
+        output: project.stats(output),
+        parts: 1,
+        skipped,
+        unresolved: project.unresolved.clone(),
+        timings: Timings::default(),
+    }
+}
+
+// Sums files given as their content, plus the size of those listed by size
+// only
+fn section<'a>(files: impl Iterator<Item = (&'a str, u64)>) -> SectionStats {
+    let mut stats = SectionStats::default();
+    for (content, size) in files {
+        stats.files += 1;
+        stats.bytes += content.len() as u64 + size;
+        stats.lines += content.lines().count();
+        stats.estimated_tokens += estimate_tokens(content);
+    }
+    stats
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combiner::PathStyle;
+    use serde_json::{json, Value};
+    use std::fs;
+
+    #[test]
+    fn reports_serialize_sections_skipped_files_and_unresolved_imports() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        let main = "import { b } from './b';\nimport './missing';\n";
+        let b = "export const b = 1;\n";
+        let notes = "# Notes\n\nNothing yet.\n";
+        fs::write(root.join("src/main.ts"), main).unwrap();
+        fs::write(root.join("src/b.ts"), b).unwrap();
+        fs::write(root.join("docs/notes.md"), notes).unwrap();
+        fs::write(root.join("docs/debug.log"), "noise\n").unwrap();
+        fs::write(root.join("docs/logo.png"), [0x89, b'P', b'N', b'G', 0, 0]).unwrap();
+
+        let combiner = Combiner::builder()
+            .path(root.join("src/main.ts"))
+            .path(root.join("docs"))
+            .ignore("*.log")
+            .deps(true)
+            .cache(false)
+            .path_style(PathStyle::RootRelative)
+            .root(&root)
+            .build()
+            .unwrap();
+        let mut resolvers = Resolvers::new(combiner.options()).unwrap();
+        let assembly = combiner.assemble(Some(&mut resolvers)).unwrap();
+        let output = combiner.render_parts(&assembly.project).unwrap().concat();
+        let json: Value =
+            serde_json::from_str(&report(&assembly, &output).to_json().unwrap()).unwrap();
+
+        let stats = |contents: &[&str]| {
+            json!({
+                "files": contents.len(),
+                "bytes": contents.iter().map(|content| content.len()).sum::<usize>(),
+                "lines": contents.iter().map(|content| content.lines().count()).sum::<usize>(),
+                "estimated_tokens": contents.iter().map(|content| estimate_tokens(content)).sum::<usize>(),
+            })
+        };
+        assert_eq!(json["files"], stats(&[main, notes]));
+        assert_eq!(json["dependencies"], stats(&[b]));
+        assert_eq!(json["targets"], stats(&[]));
+        assert_eq!(json["references"], stats(&[]));
+        assert_eq!(json["output"]["bytes"], output.len());
+        assert_eq!(json["output"]["dependencies"], 1);
+        assert_eq!(json["parts"], 1);
+
+        assert_eq!(json["skipped"], json!({ "binary": 1, "ignored": 1 }));
+        assert_eq!(
+            json["unresolved"],
+            json!([{
+                "from": "src/main.ts",
+                "specifier": "./missing",
+                "reason": "missing_file",
+            }])
+        );
+        assert!(json["timings"]["total_ms"].is_u64());
+    }
+}
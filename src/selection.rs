@@ -0,0 +1,243 @@
+use crate::error::{Error, Result};
+use crate::project::LineRange;
+use crate::symbols::{self, SymbolKind};
+use std::path::{Path, PathBuf};
+
+/// The part of a file named by a target such as `src/main.ts:120-220` or
+/// `src/main.ts#fn:processFiles`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Selection {
+    Lines(LineRange),
+    Symbol { kind: SymbolKind, name: String },
+}
+
+/// Splits a target into the file it names and the part of it requested.
+/// Paths that exist as given are never split.
+pub(crate) fn parse_target(target: &Path) -> Result<(PathBuf, Option<Selection>)> {
+    let Some(text) = target.to_str().filter(|_| !target.exists()) else {
+        return Ok((target.to_path_buf(), None));
+    };
+
+    if let Some((file, symbol)) = text.rsplit_once('#') {
+        let (kind, name) = symbol.split_once(':').ok_or_else(|| {
+            Error::Config(format!(
+                "expected `{}#kind:name`, e.g. `{}#fn:main`",
+                file, file
+            ))
+        })?;
+        let kind = SymbolKind::parse(kind).ok_or_else(|| {
+            Error::Config(format!(
+                "unknown symbol kind `{}` in {}; expected fn, class, struct, impl, interface, trait, type or enum",
+                kind, text
+            ))
+        })?;
+        let selection = Selection::Symbol {
+            kind,
+            name: name.to_string(),
+        };
+        return Ok((PathBuf::from(file), Some(selection)));
+    }
+
+    let Some((file, range)) = text.rsplit_once(':') else {
+        return Ok((target.to_path_buf(), None));
+    };
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) else {
+        return Ok((target.to_path_buf(), None));
+    };
+    if start == 0 || end < start {
+        return Err(Error::Config(format!(
+            "invalid line range `{}` in {}; lines are numbered from 1",
+            range, text
+        )));
+    }
+
+    Ok((
+        PathBuf::from(file),
+        Some(Selection::Lines(LineRange { start, end })),
+    ))
+}
+
+/// Splits a `--symbol` such as `src/combiner.ts::Combiner::run` into the
+/// file and the names leading to the definition.
+pub(crate) fn parse_symbol(symbol: &str) -> Result<(PathBuf, Vec<String>)> {
+    let (file, names) = symbol
+        .split_once("::")
+        .filter(|(file, names)| !file.is_empty() && !names.is_empty())
+        .ok_or_else(|| {
+            Error::Config(format!(
+                "expected `path::Name` for symbol `{}`, e.g. `src/main.ts::processFiles`",
+                symbol
+            ))
+        })?;
+    Ok((
+        PathBuf::from(file),
+        names.split("::").map(str::to_string).collect(),
+    ))
+}
+
+impl Selection {
+    /// Extracts the selected lines from `content`, returning them along with
+    /// their range. Ranges running past the end of the file are cut short.
+    pub fn extract(&self, file_path: &Path, content: &str) -> Result<(String, LineRange)> {
+        let total = content.lines().count();
+        let range = match self {
+            Selection::Lines(range) => {
+                if range.start > total {
+                    return Err(Error::Config(format!(
+                        "{} has {} lines, so line {} does not exist",
+                        file_path.display(),
+                        total,
+                        range.start
+                    )));
+                }
+                LineRange {
+                    start: range.start,
+                    end: range.end.min(total),
+                }
+            }
+            Selection::Symbol { kind, name } => {
+                if !symbols::is_supported_file(file_path) {
+                    return Err(unsupported_file(file_path, name));
+                }
+                let (start, end) = symbols::find_definition(file_path, content, *kind, name)
+                    .ok_or_else(|| {
+                        Error::Config(format!(
+                            "no {} named `{}` in {}",
+                            kind.as_str(),
+                            name,
+                            file_path.display()
+                        ))
+                    })?;
+                LineRange { start, end }
+            }
+        };
+
+        Ok((excerpt(content, range), range))
+    }
+}
+
+/// The lines of `content` within `range`.
+pub(crate) fn excerpt(content: &str, range: LineRange) -> String {
+    content
+        .lines()
+        .skip(range.start - 1)
+        .take(range.end + 1 - range.start)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub(crate) fn unsupported_file(file_path: &Path, name: &str) -> Error {
+    Error::Config(format!(
+        "cannot look up `{}` in {}: symbols can only be found in TypeScript, JavaScript, Python, PHP and Rust files",
+        name,
+        file_path.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn parsed(target: &str) -> (PathBuf, Option<Selection>) {
+        parse_target(Path::new(target)).unwrap()
+    }
+
+    fn lines(start: usize, end: usize) -> Option<Selection> {
+        Some(Selection::Lines(LineRange { start, end }))
+    }
+
+    #[test]
+    fn line_ranges_are_split_from_the_file() {
+        assert_eq!(parsed("src/a.ts:10-20"), ("src/a.ts".into(), lines(10, 20)));
+        assert_eq!(parsed("src/a.ts:7"), ("src/a.ts".into(), lines(7, 7)));
+        assert_eq!(parsed("src/a.ts"), ("src/a.ts".into(), None));
+        // Only numbers make a range
+        assert_eq!(parsed("src/a:b.ts"), ("src/a:b.ts".into(), None));
+    }
+
+    #[test]
+    fn empty_and_reversed_ranges_are_rejected() {
+        for target in ["src/a.ts:0-5", "src/a.ts:20-10"] {
+            assert!(
+                matches!(parse_target(Path::new(target)), Err(Error::Config(_))),
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn symbols_are_split_from_the_file() {
+        let symbol = |kind, name: &str| {
+            Some(Selection::Symbol {
+                kind,
+                name: name.to_string(),
+            })
+        };
+        assert_eq!(
+            parsed("src/a.ts#fn:processFiles"),
+            (
+                "src/a.ts".into(),
+                symbol(SymbolKind::Function, "processFiles")
+            )
+        );
+        assert_eq!(
+            parsed("src/lib.rs#trait:Resolver"),
+            (
+                "src/lib.rs".into(),
+                symbol(SymbolKind::Interface, "Resolver")
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_symbol_kinds_are_rejected() {
+        let Err(Error::Config(message)) = parse_target(Path::new("src/a.ts#func:main")) else {
+            panic!("`func` is not a symbol kind");
+        };
+        assert!(
+            message.contains("unknown symbol kind `func`"),
+            "{}",
+            message
+        );
+
+        assert!(matches!(
+            parse_target(Path::new("src/a.ts#main")),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn windows_drive_letters_are_not_ranges() {
+        assert_eq!(
+            parsed(r"C:\project\src\a.ts"),
+            (r"C:\project\src\a.ts".into(), None)
+        );
+        assert_eq!(
+            parsed(r"C:\project\src\a.ts:3-4"),
+            (r"C:\project\src\a.ts".into(), lines(3, 4))
+        );
+    }
+
+    #[test]
+    fn existing_paths_are_never_split() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes:1-2");
+        fs::write(&file, "one\ntwo\n").unwrap();
+        assert_eq!(parse_target(&file).unwrap(), (file, None));
+    }
+
+    #[test]
+    fn ranges_past_the_end_are_cut_short() {
+        let content = "one\ntwo\nthree\n";
+        let selection = Selection::Lines(LineRange { start: 2, end: 10 });
+        let (excerpt, range) = selection.extract(Path::new("a.txt"), content).unwrap();
+        assert_eq!(excerpt, "two\nthree");
+        assert_eq!(range, LineRange { start: 2, end: 3 });
+
+        let selection = Selection::Lines(LineRange { start: 4, end: 5 });
+        assert!(selection.extract(Path::new("a.txt"), content).is_err());
+    }
+}
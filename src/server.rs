@@ -0,0 +1,492 @@
+use crate::combiner::{CombinerBuilder, Format};
+use crate::error::{Error, Result};
+use crate::selection::parse_target;
+use crate::session::{CombineRequest, Session};
+use crate::tsconfig::normalize_path;
+use serde::Serialize;
+use serde_json::json;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// Request bodies only carry a few paths and flags
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+
+/// A response before it is written to the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HttpResponse {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl HttpResponse {
+    fn json<T: Serialize>(value: &T) -> Self {
+        match serde_json::to_string_pretty(value) {
+            Ok(body) => Self {
+                status: 200,
+                content_type: "application/json",
+                body,
+            },
+            Err(err) => Self::error(500, &err.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: json!({ "error": message }).to_string(),
+        }
+    }
+}
+
+/// A localhost-only HTTP/JSON API over a project root, for editor and
+/// browser integrations that would otherwise start `pcc` for every request.
+///
+/// Every path in a request is relative to the root, and requests naming a
+/// path outside of it, directly or through a symlink, are rejected. Imports
+/// leading outside the root are left out of combined dependencies. Like
+/// `McpServer`, the resolvers stay warm between requests.
+pub struct HttpServer {
+    session: Session,
+    root: PathBuf,
+    port: u16,
+}
+
+impl HttpServer {
+    /// `template` provides the defaults of every request: ignore rules,
+    /// import filters, `deps` and the cache.
+    pub fn new(template: CombinerBuilder, root: &Path, port: u16) -> Result<Self> {
+        let root = fs::canonicalize(root)?;
+        Ok(Self {
+            session: Session::new(template.confine_to(root.clone())),
+            root,
+            port,
+        })
+    }
+
+    /// Listens on `127.0.0.1` and answers requests until the process ends.
+    pub fn serve(&mut self) -> Result<()> {
+        let server = Server::http(("127.0.0.1", self.port)).map_err(|err| {
+            Error::Config(format!("cannot listen on port {}: {}", self.port, err))
+        })?;
+
+        for mut request in server.incoming_requests() {
+            let response = match read_body(&mut request) {
+                Ok(body) => self.handle(
+                    request.method(),
+                    request.url(),
+                    host_header(&request).as_deref(),
+                    &body,
+                ),
+                Err(response) => response,
+            };
+            respond(request, response);
+        }
+        Ok(())
+    }
+
+    /// Routes one request:
+    ///
+    /// - `GET /tree?path=src` lists the files that would be combined
+    /// - `GET /graph?file=src/main.ts` returns the dependency graph of a file
+    /// - `GET /importers?file=src/a.ts&transitive=true` lists its importers
+    /// - `POST /combine` combines the files of a JSON body such as
+    ///   `{"targets": ["src"], "deps": true, "format": "json"}`
+    /// - `POST /stats` takes the same body and returns only the sizes
+    fn handle(
+        &mut self,
+        method: &Method,
+        url: &str,
+        host: Option<&str>,
+        body: &str,
+    ) -> HttpResponse {
+        // Browsers send the name they resolved, so this stops other sites
+        // from reaching the server through DNS rebinding
+        if !host.is_some_and(|host| self.is_local_host(host)) {
+            return HttpResponse::error(403, "Host must be localhost");
+        }
+
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let query = match parse_query(query) {
+            Some(query) => query,
+            None => return HttpResponse::error(400, "Malformed query string"),
+        };
+        let param = |name: &str| {
+            query
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let result = match (method, path) {
+            (Method::Get, "/tree") => self.tree(param("path").unwrap_or(".")),
+            (Method::Get, "/graph") => self.graph(param("file")),
+            (Method::Get, "/importers") => {
+                self.importers(param("file"), param("transitive") == Some("true"))
+            }
+            (Method::Post, "/combine") => self.combine(body, false),
+            (Method::Post, "/stats") => self.combine(body, true),
+            (_, "/tree" | "/graph" | "/importers" | "/combine" | "/stats") => {
+                Err(HttpResponse::error(405, "Method not allowed"))
+            }
+            _ => Err(HttpResponse::error(404, "Not found")),
+        };
+
+        result.unwrap_or_else(|response| response)
+    }
+
+    fn tree(&mut self, path: &str) -> std::result::Result<HttpResponse, HttpResponse> {
+        let path = self.resolve_in_root(path)?;
+        let files = self
+            .session
+            .list_files(&path)
+            .map_err(|err| HttpResponse::error(500, &err))?;
+        Ok(HttpResponse::json(&json!({ "files": files })))
+    }
+
+    fn graph(&mut self, file: Option<&str>) -> std::result::Result<HttpResponse, HttpResponse> {
+        let file = self.resolve_file(file)?;
+        let graph = self
+            .session
+            .dependency_graph(&file)
+            .map_err(|err| HttpResponse::error(500, &err))?;
+        Ok(HttpResponse::json(&graph))
+    }
+
+    fn importers(
+        &mut self,
+        file: Option<&str>,
+        transitive: bool,
+    ) -> std::result::Result<HttpResponse, HttpResponse> {
+        let file = self.resolve_file(file)?;
+        let root = self.root.clone();
+        let importers = self
+            .session
+            .importers(&file, &root, transitive)
+            .map_err(|err| HttpResponse::error(500, &err))?;
+        Ok(HttpResponse::json(&importers))
+    }
+
+    fn combine(
+        &mut self,
+        body: &str,
+        stats_only: bool,
+    ) -> std::result::Result<HttpResponse, HttpResponse> {
+        let mut request: CombineRequest = serde_json::from_str(body)
+            .map_err(|err| HttpResponse::error(400, &format!("Invalid request body: {}", err)))?;
+        request.targets = request
+            .targets
+            .iter()
+            .map(|target| self.resolve_target(&target.to_string_lossy()))
+            .collect::<std::result::Result<_, _>>()?;
+        request.references = request
+            .references
+            .iter()
+            .map(|reference| self.resolve_target(&reference.to_string_lossy()))
+            .collect::<std::result::Result<_, _>>()?;
+
+        let (output, project) = self
+            .session
+            .combine(&request)
+            .map_err(|err| HttpResponse::error(500, &err))?;
+
+        if stats_only {
+            return Ok(HttpResponse::json(&project.stats(&output)));
+        }
+        Ok(HttpResponse {
+            status: 200,
+            content_type: match request.format {
+                Format::Xml => "application/xml",
+                Format::Json => "application/json",
+            },
+            body: output,
+        })
+    }
+
+    fn resolve_file(&self, file: Option<&str>) -> std::result::Result<PathBuf, HttpResponse> {
+        let file = file.ok_or_else(|| HttpResponse::error(400, "Missing `file` parameter"))?;
+        let path = self.resolve_in_root(file)?;
+        if !path.is_file() {
+            return Err(HttpResponse::error(400, &format!("Not a file: {}", file)));
+        }
+        Ok(path)
+    }
+
+    // Targets such as `src/a.ts:10-20` and `src/a.ts#fn:main` name part of a
+    // file; only the file is confined to the root, and the part is kept
+    fn resolve_target(&self, requested: &str) -> std::result::Result<PathBuf, HttpResponse> {
+        let joined = self.root.join(requested);
+        let (file, selection) =
+            parse_target(&joined).map_err(|err| HttpResponse::error(400, &err.to_string()))?;
+        if selection.is_none() {
+            return self.resolve_in_root(requested);
+        }
+
+        // The selection is what follows the file at the end of the target
+        let selection_len = joined.as_os_str().len() - file.as_os_str().len();
+        let (file_part, selection_part) = requested.split_at(requested.len() - selection_len);
+        let mut resolved = self.resolve_in_root(file_part)?.into_os_string();
+        resolved.push(selection_part);
+        Ok(PathBuf::from(resolved))
+    }
+
+    // Checked once without touching the filesystem, so nothing is revealed
+    // about paths outside the root, and again after following symlinks
+    fn resolve_in_root(&self, requested: &str) -> std::result::Result<PathBuf, HttpResponse> {
+        let outside = || {
+            HttpResponse::error(
+                403,
+                &format!("Path is outside the project root: {}", requested),
+            )
+        };
+
+        let candidate = normalize_path(&self.root.join(requested));
+        if requested.contains('\0') || !candidate.starts_with(&self.root) {
+            return Err(outside());
+        }
+
+        let resolved = fs::canonicalize(&candidate)
+            .map_err(|_| HttpResponse::error(404, &format!("Not found: {}", requested)))?;
+        if !resolved.starts_with(&self.root) {
+            return Err(outside());
+        }
+        Ok(resolved)
+    }
+
+    fn is_local_host(&self, host: &str) -> bool {
+        // The port follows the last colon, unless that colon is inside an IPv6 address
+        let (name, port) = match host.rfind(':') {
+            Some(index) if !host[index..].contains(']') => {
+                (&host[..index], Some(&host[index + 1..]))
+            }
+            _ => (host, None),
+        };
+        port.is_none_or(|port| port == self.port.to_string())
+            && matches!(name, "localhost" | "127.0.0.1" | "[::1]")
+    }
+}
+
+fn read_body(request: &mut Request) -> std::result::Result<String, HttpResponse> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_BYTES + 1)
+        .read_to_string(&mut body)
+        .map_err(|_| HttpResponse::error(400, "Request body must be UTF-8"))?;
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return Err(HttpResponse::error(413, "Request body is too large"));
+    }
+    Ok(body)
+}
+
+fn host_header(request: &Request) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Host"))
+        .map(|header| header.value.as_str().to_string())
+}
+
+fn respond(request: Request, response: HttpResponse) {
+    let content_type = Header::from_bytes(&b"Content-Type"[..], response.content_type.as_bytes())
+        .expect("content types are valid header values");
+    let response = Response::from_string(response.body)
+        .with_status_code(response.status)
+        .with_header(content_type);
+
+    // The client may have gone away; there is no one left to tell
+    let _ = request.respond(response);
+}
+
+fn parse_query(query: &str) -> Option<Vec<(String, String)>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((percent_decode(key)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut input = text.bytes();
+    while let Some(byte) = input.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let high = (input.next()? as char).to_digit(16)?;
+                let low = (input.next()? as char).to_digit(16)?;
+                bytes.push((high * 16 + low) as u8);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PORT: u16 = 4000;
+
+    // A server over a root holding `src/a.ts`, next to a directory outside it
+    fn fixture() -> (TempDir, HttpServer) {
+        let dir = tempfile::tempdir().unwrap();
+        let base = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(base.join("root/src")).unwrap();
+        fs::create_dir(base.join("outside")).unwrap();
+        fs::write(base.join("root/src/a.ts"), "export function main() {}\n").unwrap();
+        fs::write(base.join("outside/secret.ts"), "secret\n").unwrap();
+
+        let template = crate::Combiner::builder().cache(false);
+        let server = HttpServer::new(template, &base.join("root"), PORT).unwrap();
+        (dir, server)
+    }
+
+    fn status(result: std::result::Result<PathBuf, HttpResponse>) -> u16 {
+        result.map_or_else(|response| response.status, |_| 200)
+    }
+
+    #[test]
+    fn paths_inside_the_root_resolve() {
+        let (_dir, server) = fixture();
+        assert_eq!(
+            server.resolve_in_root("src/./a.ts").unwrap(),
+            server.root.join("src/a.ts")
+        );
+        assert_eq!(
+            server.resolve_in_root("src/../src/a.ts").unwrap(),
+            server.root.join("src/a.ts")
+        );
+        assert_eq!(status(server.resolve_in_root("src/missing.ts")), 404);
+    }
+
+    #[test]
+    fn paths_outside_the_root_are_rejected() {
+        let (_dir, server) = fixture();
+        assert_eq!(status(server.resolve_in_root("../outside/secret.ts")), 403);
+        assert_eq!(status(server.resolve_in_root("src/../../outside")), 403);
+        assert_eq!(status(server.resolve_in_root("/etc/passwd")), 403);
+        assert_eq!(status(server.resolve_in_root("src/a.ts\0")), 403);
+        // Rejected before the filesystem is asked whether the path exists
+        assert_eq!(status(server.resolve_in_root("../missing")), 403);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_leading_outside_the_root_are_rejected() {
+        let (_dir, server) = fixture();
+        let outside = server.root.parent().unwrap().join("outside");
+        std::os::unix::fs::symlink(&outside, server.root.join("src/link")).unwrap();
+        assert_eq!(status(server.resolve_in_root("src/link")), 403);
+        assert_eq!(status(server.resolve_in_root("src/link/secret.ts")), 403);
+    }
+
+    #[test]
+    fn percent_encoded_traversal_is_rejected() {
+        let (_dir, mut server) = fixture();
+        let host = format!("localhost:{}", PORT);
+        for url in [
+            "/tree?path=%2e%2e",
+            "/tree?path=%2E%2E%2Foutside",
+            "/graph?file=src%2f..%2f..%2foutside%2fsecret.ts",
+        ] {
+            let response = server.handle(&Method::Get, url, Some(&host), "");
+            assert_eq!(response.status, 403, "{}", url);
+        }
+        let response = server.handle(&Method::Get, "/tree?path=%zz", Some(&host), "");
+        assert_eq!(response.status, 400);
+    }
+
+    #[test]
+    fn only_the_file_of_a_target_is_confined() {
+        let (_dir, server) = fixture();
+        let resolved = server.resolve_target("src/a.ts:1-1").unwrap();
+        assert_eq!(
+            resolved,
+            PathBuf::from(format!("{}:1-1", server.root.join("src/a.ts").display()))
+        );
+        let resolved = server.resolve_target("src/a.ts#fn:main").unwrap();
+        assert_eq!(
+            resolved,
+            PathBuf::from(format!(
+                "{}#fn:main",
+                server.root.join("src/a.ts").display()
+            ))
+        );
+
+        assert_eq!(
+            status(server.resolve_target("../outside/secret.ts:1-1")),
+            403
+        );
+        assert_eq!(
+            status(server.resolve_target("../outside/secret.ts#fn:main")),
+            403
+        );
+        assert_eq!(status(server.resolve_target("../outside")), 403);
+        assert_eq!(status(server.resolve_target("src/a.ts:0-1")), 400);
+    }
+
+    #[test]
+    fn only_local_hosts_on_the_server_port_are_accepted() {
+        let (_dir, mut server) = fixture();
+        for host in [
+            "localhost",
+            "localhost:4000",
+            "127.0.0.1:4000",
+            "[::1]",
+            "[::1]:4000",
+        ] {
+            assert!(server.is_local_host(host), "{}", host);
+        }
+        for host in [
+            "evil.com",
+            "evil.com:4000",
+            "localhost.evil.com",
+            "localhost:4001",
+            "127.0.0.2",
+            "[::1]:4001",
+            "[::2]",
+            "",
+        ] {
+            assert!(!server.is_local_host(host), "{}", host);
+        }
+
+        let response = server.handle(&Method::Get, "/tree", Some("evil.com"), "");
+        assert_eq!(response.status, 403);
+        let response = server.handle(&Method::Get, "/tree", None, "");
+        assert_eq!(response.status, 403);
+    }
+
+    #[test]
+    fn files_created_between_requests_are_combined() {
+        let (_dir, mut server) = fixture();
+        let host = format!("localhost:{}", PORT);
+        fs::write(
+            server.root.join("src/main.ts"),
+            "import { b } from './b';\n",
+        )
+        .unwrap();
+        let body = r#"{"targets": ["src/main.ts"], "deps": true, "format": "json"}"#;
+
+        let response = server.handle(&Method::Post, "/combine", Some(&host), body);
+        assert_eq!(response.status, 200);
+        assert!(!response.body.contains("b.ts"));
+
+        fs::write(server.root.join("src/b.ts"), "export const b = 1;\n").unwrap();
+        let response = server.handle(&Method::Post, "/combine", Some(&host), body);
+        assert_eq!(response.status, 200);
+        assert!(response.body.contains("export const b = 1;"));
+
+        let response = server.handle(&Method::Get, "/importers?file=src/b.ts", Some(&host), "");
+        assert_eq!(response.status, 200);
+        assert!(response.body.contains("src/main.ts"));
+    }
+}
@@ -0,0 +1,422 @@
+use crate::combiner::{display_path, Combiner, CombinerBuilder, Format, Resolvers};
+use crate::dependency_resolver::canonicalize_path;
+use crate::project::{estimate_tokens, CombinedProject};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Session failures are shown to the caller as plain messages
+pub(crate) type SessionResult<T> = std::result::Result<T, String>;
+
+/// The parameters of one combine call made to a long-running server.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct CombineRequest {
+    /// Files or directories to combine
+    pub targets: Vec<PathBuf>,
+    pub references: Vec<PathBuf>,
+    /// Overrides the server's `--deps` default
+    pub deps: Option<bool>,
+    pub format: Format,
+    /// Rejects outputs estimated above this many tokens
+    pub max_tokens: Option<usize>,
+}
+
+/// A file and how it is imported by, or imports, the file in question.
+#[derive(Debug, Serialize)]
+pub(crate) struct Edge {
+    pub path: String,
+    /// `None` for files that are only reached indirectly
+    pub kind: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct GraphNode {
+    pub path: String,
+    pub imports: Vec<Edge>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct DependencyGraph {
+    pub file: String,
+    pub files: Vec<GraphNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Importers {
+    pub file: String,
+    pub importers: Vec<Edge>,
+}
+
+/// State shared by the requests of a long-running server: the defaults of
+/// every call and the resolvers, whose tsconfig, workspace and resolution
+/// caches stay warm between calls until a tsconfig file or workspace
+/// manifest they read changes.
+pub(crate) struct Session {
+    template: CombinerBuilder,
+    resolvers: Option<Resolvers>,
+    /// When each config file read by the resolvers was last modified, as of
+    /// the end of the last call
+    config_files: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl Session {
+    pub fn new(template: CombinerBuilder) -> Self {
+        Self {
+            template,
+            resolvers: None,
+            config_files: HashMap::new(),
+        }
+    }
+
+    /// Combines the requested files and renders them, returning the output
+    /// along with the project it was rendered from.
+    pub fn combine(
+        &mut self,
+        request: &CombineRequest,
+    ) -> SessionResult<(String, CombinedProject)> {
+        let mut builder = self
+            .template
+            .clone()
+            .paths(request.targets.iter().cloned())
+            .format(request.format);
+        for reference in &request.references {
+            builder = builder.reference(reference.clone());
+        }
+        if let Some(deps) = request.deps {
+            builder = builder.deps(deps);
+        }
+
+        let combiner = builder.build().map_err(|err| err.to_string())?;
+        let resolvers = if combiner.options().deps {
+            Some(self.resolvers(&combiner)?)
+        } else {
+            None
+        };
+        let project = combiner.combine_with(resolvers);
+        self.stamp_config_files();
+        let project = project.map_err(|err| err.to_string())?;
+        let output = combiner.render(&project).map_err(|err| err.to_string())?;
+
+        if let Some(max_tokens) = request.max_tokens {
+            let tokens = estimate_tokens(&output);
+            if tokens > max_tokens {
+                return Err(format!(
+                    "The combined output is about {} tokens, more than max_tokens ({}). Narrow the targets or disable deps.",
+                    tokens, max_tokens
+                ));
+            }
+        }
+
+        Ok((output, project))
+    }
+
+    pub fn list_files(&self, path: &Path) -> SessionResult<Vec<String>> {
+        let files = self
+            .build(path)?
+            .list_files()
+            .map_err(|err| err.to_string())?;
+
+        Ok(files
+            .iter()
+            .map(|file| file.display().to_string())
+            .collect())
+    }
+
+    /// Resolves the imports of `file` and lists every file it reaches with
+    /// its direct imports.
+    pub fn dependency_graph(&mut self, file: &Path) -> SessionResult<DependencyGraph> {
+        let file = canonicalize_path(file);
+        let combiner = self.build(&file)?;
+
+        let options = combiner.options();
+        let resolvers = self.resolvers(&combiner)?;
+        let mut files = resolvers
+            .deps
+            .resolve_deps(&file, &mut resolvers.ts)
+            .map_err(|err| err.to_string())?;
+        // Like combined dependencies, files outside a server's root are not
+        // shown, not even by path
+        files.retain(|current| options.is_within_root(current));
+        files.sort();
+
+        let mut graph = Vec::new();
+        for current in &files {
+            let imports = resolvers
+                .deps
+                .get_dependencies(current)
+                .iter()
+                .filter(|dependency| options.is_within_root(dependency))
+                .map(|dependency| {
+                    Ok(Edge {
+                        path: display(&combiner, dependency)?,
+                        kind: resolvers
+                            .deps
+                            .get_import_kind(current, dependency)
+                            .map(|kind| kind.as_str()),
+                    })
+                })
+                .collect::<SessionResult<Vec<_>>>()?;
+            graph.push(GraphNode {
+                path: display(&combiner, current)?,
+                imports,
+            });
+        }
+        resolvers.save_cache();
+        self.stamp_config_files();
+
+        Ok(DependencyGraph {
+            file: display(&combiner, &file)?,
+            files: graph,
+        })
+    }
+
+    /// Finds the files under `root` that import `file`, directly or, with
+    /// `transitive`, through other files.
+    pub fn importers(
+        &mut self,
+        file: &Path,
+        root: &Path,
+        transitive: bool,
+    ) -> SessionResult<Importers> {
+        let file = canonicalize_path(file);
+        let combiner = self.build(root)?;
+        let entry_files = combiner.supported_files();
+
+        let resolvers = self.resolvers(&combiner)?;
+        resolvers
+            .deps
+            .resolve_all(&entry_files, &mut resolvers.ts)
+            .map_err(|err| err.to_string())?;
+
+        let mut importers = if transitive {
+            resolvers
+                .deps
+                .get_all_importers(&file)
+                .into_iter()
+                .collect()
+        } else {
+            resolvers.deps.get_direct_importers(&file)
+        };
+        importers.retain(|importer| combiner.options().is_within_root(importer));
+        importers.sort();
+
+        let importers = importers
+            .iter()
+            .map(|importer| {
+                Ok(Edge {
+                    path: display(&combiner, importer)?,
+                    kind: resolvers
+                        .deps
+                        .get_import_kind(importer, &file)
+                        .map(|kind| kind.as_str()),
+                })
+            })
+            .collect::<SessionResult<Vec<_>>>()?;
+        resolvers.save_cache();
+        self.stamp_config_files();
+
+        Ok(Importers {
+            file: display(&combiner, &file)?,
+            importers,
+        })
+    }
+
+    // Stamps the config files first read by the last call; the others keep
+    // their earlier time, so a change made during the call is still noticed
+    fn stamp_config_files(&mut self) {
+        let Some(resolvers) = &self.resolvers else {
+            return;
+        };
+        for file in resolvers.deps.config_files() {
+            self.config_files
+                .entry(file)
+                .or_insert_with_key(|file| modified_time(file));
+        }
+    }
+
+    fn build(&self, path: &Path) -> SessionResult<Combiner> {
+        self.template
+            .clone()
+            .path(path)
+            .build()
+            .map_err(|err| err.to_string())
+    }
+
+    // The first call creates the resolvers; later calls only update their
+    // import filters and forget the files they saw, unless a config file
+    // they read has changed since
+    fn resolvers(&mut self, combiner: &Combiner) -> SessionResult<&mut Resolvers> {
+        let changed = self
+            .config_files
+            .iter()
+            .any(|(file, modified)| modified_time(file) != *modified);
+        if changed {
+            // Cached tsconfig and workspace data would be stale
+            self.resolvers = None;
+            self.config_files.clear();
+        }
+
+        let resolvers = match self.resolvers.take() {
+            Some(resolvers) => resolvers,
+            None => Resolvers::new(combiner.options()).map_err(|err| err.to_string())?,
+        };
+        let resolvers = self.resolvers.insert(resolvers);
+        resolvers.configure(combiner.options());
+        resolvers.forget_files();
+        Ok(resolvers)
+    }
+}
+
+fn modified_time(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn display(combiner: &Combiner, path: &Path) -> SessionResult<String> {
+    display_path(path, combiner.options())
+        .map(|path| path.display().to_string())
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combiner::PathStyle;
+    use std::fs;
+
+    #[test]
+    fn graph_and_importers_leave_out_files_outside_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = fs::canonicalize(dir.path()).unwrap();
+        let root = base.join("app");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir(base.join("shared")).unwrap();
+        fs::write(
+            root.join("src/main.ts"),
+            "import { a } from './a';\nimport { s } from '../../shared/s';\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/a.ts"), "export const a = 1;\n").unwrap();
+        fs::write(
+            base.join("shared/s.ts"),
+            "import { a } from '../app/src/a';\nexport const s = a;\n",
+        )
+        .unwrap();
+
+        let template = Combiner::builder()
+            .path_style(PathStyle::RootRelative)
+            .root(&root)
+            .cache(false)
+            .confine_to(root.clone());
+        let mut session = Session::new(template);
+
+        let graph = session.dependency_graph(&root.join("src/main.ts")).unwrap();
+        let paths: Vec<_> = graph.files.iter().map(|node| node.path.as_str()).collect();
+        assert_eq!(paths, ["src/a.ts", "src/main.ts"]);
+        let imports: Vec<_> = graph.files[1]
+            .imports
+            .iter()
+            .map(|edge| edge.path.as_str())
+            .collect();
+        assert_eq!(imports, ["src/a.ts"]);
+
+        let importers = session
+            .importers(&root.join("src/a.ts"), &root, true)
+            .unwrap();
+        let paths: Vec<_> = importers
+            .importers
+            .iter()
+            .map(|edge| edge.path.as_str())
+            .collect();
+        assert_eq!(paths, ["src/main.ts"]);
+    }
+
+    #[test]
+    fn resolvers_are_rebuilt_when_a_tsconfig_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::create_dir(root.join("lib")).unwrap();
+        fs::write(root.join("src/main.ts"), "import { a } from '@/a';\n").unwrap();
+        fs::write(root.join("src/a.ts"), "export const a = 1;\n").unwrap();
+        fs::write(root.join("lib/a.ts"), "export const a = 2;\n").unwrap();
+        let tsconfig = root.join("tsconfig.json");
+        let write_alias = |target: &str, modified: SystemTime| {
+            fs::write(
+                &tsconfig,
+                format!(
+                    r#"{{ "compilerOptions": {{ "paths": {{ "@/*": ["{}/*"] }} }} }}"#,
+                    target
+                ),
+            )
+            .unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&tsconfig)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let paths = |graph: DependencyGraph| -> Vec<String> {
+            graph.files.into_iter().map(|node| node.path).collect()
+        };
+
+        write_alias("src", SystemTime::UNIX_EPOCH);
+        let template = Combiner::builder()
+            .path_style(PathStyle::RootRelative)
+            .root(&root)
+            .cache(false)
+            .confine_to(root.clone());
+        let mut session = Session::new(template);
+        let main = root.join("src/main.ts");
+        assert_eq!(
+            paths(session.dependency_graph(&main).unwrap()),
+            ["src/a.ts", "src/main.ts"]
+        );
+
+        write_alias("lib", SystemTime::now());
+        assert_eq!(
+            paths(session.dependency_graph(&main).unwrap()),
+            ["lib/a.ts", "src/main.ts"]
+        );
+    }
+
+    #[test]
+    fn files_created_between_calls_are_resolved() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/a.ts"), "import { b } from './b';\n").unwrap();
+
+        let template = Combiner::builder()
+            .path_style(PathStyle::RootRelative)
+            .root(&root)
+            .cache(false);
+        let mut session = Session::new(template);
+        let file = root.join("src/a.ts");
+        let graph = session.dependency_graph(&file).unwrap();
+        assert!(graph.files[0].imports.is_empty());
+
+        fs::write(root.join("src/b.ts"), "export const b = 1;\n").unwrap();
+        let graph = session.dependency_graph(&file).unwrap();
+        let imports: Vec<_> = graph
+            .files
+            .iter()
+            .find(|node| node.path == "src/a.ts")
+            .unwrap()
+            .imports
+            .iter()
+            .map(|edge| (edge.path.as_str(), edge.kind))
+            .collect();
+        assert_eq!(imports, [("src/b.ts", Some("runtime"))]);
+
+        fs::remove_file(root.join("src/b.ts")).unwrap();
+        let graph = session.dependency_graph(&file).unwrap();
+        let paths: Vec<_> = graph.files.iter().map(|node| node.path.as_str()).collect();
+        assert_eq!(paths, ["src/a.ts"]);
+    }
+}
@@ -0,0 +1,129 @@
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// A script embedded in a single-file component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptBlock {
+    pub content: String,
+    /// Zero-based line of the component file where `content` starts
+    pub line_offset: usize,
+    /// Byte column of that line where `content` starts, which only applies
+    /// to the first line of `content`
+    pub column_offset: usize,
+    /// The `lang` attribute, e.g. `ts` or `tsx`
+    pub lang: Option<String>,
+    /// The `src` attribute of `<script src="./setup.ts">`
+    pub src: Option<String>,
+}
+
+/// Returns true for Vue, Svelte and Astro component files.
+pub fn is_component_file(file_path: &Path) -> bool {
+    matches!(
+        file_path
+            .extension()
+            .and_then(|extension| extension.to_str()),
+        Some("vue") | Some("svelte") | Some("astro")
+    )
+}
+
+/// Extracts every `<script>` block of a component, plus the frontmatter of
+/// Astro files, which holds the component's imports.
+pub fn extract_script_blocks(file_path: &Path, content: &str) -> Vec<ScriptBlock> {
+    let mut blocks = Vec::new();
+
+    if file_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        == Some("astro")
+    {
+        if let Some(frontmatter) = extract_frontmatter(content) {
+            blocks.push(frontmatter);
+        }
+    }
+
+    for captures in script_pattern().captures_iter(content) {
+        let attributes = captures.get(1).map_or("", |attributes| attributes.as_str());
+        let body = captures.get(2).unwrap();
+
+        blocks.push(ScriptBlock {
+            content: body.as_str().to_string(),
+            line_offset: line_of(content, body.start()),
+            column_offset: column_of(content, body.start()),
+            lang: attribute(attributes, "lang"),
+            src: attribute(attributes, "src"),
+        });
+    }
+
+    blocks
+}
+
+// Astro frontmatter is fenced by `---` lines at the top of the file
+fn extract_frontmatter(content: &str) -> Option<ScriptBlock> {
+    let trimmed = content.trim_start();
+    let rest = trimmed.strip_prefix("---")?;
+    let fence_start = content.len() - trimmed.len();
+    let body_start = fence_start + 3;
+    let body_end = rest.find("\n---").map(|index| body_start + index + 1)?;
+
+    Some(ScriptBlock {
+        content: content[body_start..body_end].to_string(),
+        line_offset: line_of(content, body_start),
+        column_offset: column_of(content, body_start),
+        lang: Some("ts".to_string()),
+        src: None,
+    })
+}
+
+fn script_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?is)<script\b([^>]*)>(.*?)</script\s*>").unwrap())
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let pattern = Regex::new(&format!(r#"\b{}\s*=\s*["']([^"']*)["']"#, name)).ok()?;
+    pattern
+        .captures(attributes)
+        .map(|captures| captures[1].to_string())
+}
+
+fn line_of(content: &str, byte_offset: usize) -> usize {
+    content[..byte_offset].matches('\n').count()
+}
+
+fn column_of(content: &str, byte_offset: usize) -> usize {
+    byte_offset
+        - content[..byte_offset]
+            .rfind('\n')
+            .map_or(0, |index| index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_keep_the_position_where_they_start() {
+        let content = "<template><p /></template>\n<script setup lang=\"ts\">import a from './a';\n</script>\n";
+        let blocks = extract_script_blocks(Path::new("App.vue"), content);
+        assert_eq!(
+            blocks,
+            [ScriptBlock {
+                content: "import a from './a';\n".to_string(),
+                line_offset: 1,
+                column_offset: 24,
+                lang: Some("ts".to_string()),
+                src: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn astro_frontmatter_is_a_block() {
+        let content = "---\nimport Card from './Card.astro';\n---\n<Card />\n";
+        let blocks = extract_script_blocks(Path::new("index.astro"), content);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].content, "\nimport Card from './Card.astro';\n");
+        assert_eq!((blocks[0].line_offset, blocks[0].column_offset), (0, 3));
+    }
+}
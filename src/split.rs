@@ -0,0 +1,470 @@
+use crate::project::{
+    format_dependency_entry, format_external_entry, format_file_content, CombinedProject,
+    RenderOptions, SourceFile,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const XML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+
+/// The most each part of a split output may hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitLimit {
+    /// Estimated tokens, counted like `estimate_tokens`
+    Tokens(usize),
+    Bytes(usize),
+}
+
+impl SplitLimit {
+    fn bytes(self) -> usize {
+        match self {
+            // `estimate_tokens` rounds bytes / 4 up, so this many bytes never
+            // estimate above the limit
+            SplitLimit::Tokens(tokens) => tokens.saturating_mul(4),
+            SplitLimit::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+/// The path part `index` (starting at 1) of a split output is written to:
+/// `combined_code.part2.txt` for `combined_code.txt`.
+pub fn part_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}.part{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}.part{}", stem, index),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Whether `path` is a part that `part_path` would name for `output`.
+pub(crate) fn is_part_path(path: &Path, output: &Path) -> bool {
+    if path.parent() != output.parent() {
+        return false;
+    }
+    let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let suffix = match output.extension() {
+        Some(extension) => format!(".{}", extension.to_string_lossy()),
+        None => String::new(),
+    };
+
+    file_name
+        .strip_prefix(&format!("{}.part", stem))
+        .and_then(|rest| rest.strip_suffix(&suffix))
+        .is_some_and(|index| !index.is_empty() && index.bytes().all(|byte| byte.is_ascii_digit()))
+}
+
+// The wrapper a fragment is rendered in; the closing sections carry their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Targets,
+    References,
+    Files,
+    Dependencies,
+    External,
+    Closing,
+}
+
+impl Section {
+    fn tags(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Section::Targets => Some(("  <targets>\n", "  </targets>\n")),
+            Section::References => Some(("  <references>\n", "  </references>\n")),
+            Section::Dependencies => Some(("  <dependencies>\n", "  </dependencies>\n")),
+            Section::External => Some(("  <external>\n", "  </external>\n")),
+            Section::Files | Section::Closing => None,
+        }
+    }
+
+    fn tags_len(self) -> usize {
+        self.tags()
+            .map_or(0, |(open, close)| open.len() + close.len())
+    }
+}
+
+// A piece of the document that only goes into a part whole
+#[derive(Debug)]
+struct Fragment {
+    section: Section,
+    path: Option<PathBuf>,
+    xml: String,
+}
+
+impl CombinedProject {
+    /// Renders the project as XML documents of at most `limit` each, or as
+    /// the single `to_xml` document when it fits.
+    ///
+    /// Files are never divided between parts unless a file alone exceeds
+    /// the limit, in which case it is cut at line boundaries into numbered
+    /// chunks. Every part is wrapped in `<part index="1" of="3">`, and the
+    /// first one also tells the reader to wait for the others and lists the
+    /// part holding each file. The header, instructions and tree of the
+    /// first part count against the limit but are never divided, so when
+    /// they alone exceed it the first part holds no files and is larger
+    /// than the limit. A single line, or the cycles or parse errors section,
+    /// larger than the limit still exceeds it too.
+    pub fn to_xml_parts(&self, limit: SplitLimit, options: RenderOptions) -> Vec<String> {
+        let xml = self.to_xml_with(options);
+        let limit = limit.bytes();
+        if xml.len() <= limit {
+            return vec![xml];
+        }
+
+        // Parts never outnumber the bytes of the document, so numbering them
+        // with as many digits as its length bounds the size of every header
+        let widest = "9".repeat(xml.len().to_string().len());
+        let widest_range = format!("{}-{}", widest, widest);
+        let later_budget = limit.saturating_sub(render_part(&widest, &widest, None, &[]).len());
+        let fragments = split_oversized(self.fragments(options), later_budget);
+
+        let widest_tree = render_tree(&fragments, |_| widest_range.clone());
+        let first_budget =
+            limit.saturating_sub(render_part(&widest, &widest, Some(&widest_tree), &[]).len());
+
+        // Greedy packing, counting the wrapper of a section when it opens
+        let mut parts: Vec<Vec<&Fragment>> = vec![Vec::new()];
+        let mut used = 0;
+        for fragment in &fragments {
+            let first = parts.len() == 1;
+            let budget = if first { first_budget } else { later_budget };
+            let current = parts.last_mut().expect("there is always a current part");
+            let opens_section = current.last().map(|last| last.section) != Some(fragment.section);
+            let cost = fragment.xml.len()
+                + if opens_section {
+                    fragment.section.tags_len()
+                } else {
+                    0
+                };
+
+            // Only the first part, which holds the tree, may carry no files
+            if (!current.is_empty() || first) && used + cost > budget {
+                parts.push(vec![fragment]);
+                used = fragment.xml.len() + fragment.section.tags_len();
+            } else {
+                current.push(fragment);
+                used += cost;
+            }
+        }
+
+        let mut holding: HashMap<&Path, (usize, usize)> = HashMap::new();
+        for (index, part) in parts.iter().enumerate() {
+            for path in part.iter().filter_map(|fragment| fragment.path.as_deref()) {
+                holding
+                    .entry(path)
+                    .and_modify(|range| range.1 = index + 1)
+                    .or_insert((index + 1, index + 1));
+            }
+        }
+        let tree = render_tree(&fragments, |path| match holding[path] {
+            (first, last) if first == last => first.to_string(),
+            (first, last) => format!("{}-{}", first, last),
+        });
+
+        let of = parts.len().to_string();
+        parts
+            .iter()
+            .enumerate()
+            .map(|(index, part)| {
+                let tree = (index == 0).then_some(tree.as_str());
+                render_part(&(index + 1).to_string(), &of, tree, part)
+            })
+            .collect()
+    }
+
+    fn fragments(&self, options: RenderOptions) -> Vec<Fragment> {
+        let source_fragments = |section: Section, files: &[SourceFile]| {
+            files
+                .iter()
+                .map(move |file| Fragment {
+                    section,
+                    path: Some(file.path.clone()),
+                    xml: format_file_content(file, options),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut fragments = source_fragments(Section::Targets, &self.targets);
+        fragments.extend(source_fragments(Section::References, &self.references));
+        fragments.extend(source_fragments(Section::Files, &self.files));
+        fragments.extend(self.dependencies.iter().map(|dependency| Fragment {
+            section: Section::Dependencies,
+            path: Some(dependency.path.clone()),
+            xml: format_dependency_entry(dependency, options),
+        }));
+        fragments.extend(self.external.iter().map(|types| Fragment {
+            section: Section::External,
+            path: Some(types.path.clone()),
+            xml: format_external_entry(types, options),
+        }));
+        fragments.extend(
+            [
+                self.cycles_section(),
+                self.parse_errors_section(),
+                self.unresolved_section(),
+            ]
+            .into_iter()
+            .filter(|xml| !xml.is_empty())
+            .map(|xml| Fragment {
+                section: Section::Closing,
+                path: None,
+                xml,
+            }),
+        );
+        fragments
+    }
+}
+
+// Cuts each file too large for a part of its own into chunks of whole lines,
+// each opening with the file's tag marked `chunk="1" of="2"`
+fn split_oversized(fragments: Vec<Fragment>, budget: usize) -> Vec<Fragment> {
+    let mut result = Vec::new();
+    for fragment in fragments {
+        let budget = budget.saturating_sub(fragment.section.tags_len());
+        if fragment.path.is_none() || fragment.xml.len() <= budget {
+            result.push(fragment);
+            continue;
+        }
+
+        // Only `<file>` elements, with an opening and a closing line, are cut
+        let lines: Vec<&str> = fragment.xml.lines().collect();
+        let open = lines.first().and_then(|line| line.strip_suffix('>'));
+        let (Some(open), true) = (open, lines.len() > 2) else {
+            result.push(fragment);
+            continue;
+        };
+        let (close, body) = (lines[lines.len() - 1], &lines[1..lines.len() - 1]);
+
+        // Room for the chunk attributes, whatever the number of chunks
+        let widest = "9".repeat(fragment.xml.len().to_string().len());
+        let marker_len = format!(" chunk=\"{}\" of=\"{}\"", widest, widest).len();
+        let body_budget = budget.saturating_sub(open.len() + 2 + marker_len + close.len() + 1);
+
+        let mut chunks: Vec<String> = vec![String::new()];
+        for line in body {
+            let chunk = chunks.last_mut().expect("there is always a current chunk");
+            if !chunk.is_empty() && chunk.len() + line.len() + 1 > body_budget {
+                chunks.push(String::new());
+            }
+            let chunk = chunks.last_mut().expect("there is always a current chunk");
+            chunk.push_str(line);
+            chunk.push('\n');
+        }
+
+        let of = chunks.len();
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            result.push(Fragment {
+                section: fragment.section,
+                path: fragment.path.clone(),
+                xml: format!(
+                    "{} chunk=\"{}\" of=\"{}\">\n{}{}\n",
+                    open,
+                    index + 1,
+                    of,
+                    chunk,
+                    close
+                ),
+            });
+        }
+    }
+    result
+}
+
+fn render_tree(fragments: &[Fragment], parts_of: impl Fn(&Path) -> String) -> String {
+    let mut tree = String::from("  <tree>\n");
+    let mut previous: Option<&Path> = None;
+    for path in fragments
+        .iter()
+        .filter_map(|fragment| fragment.path.as_deref())
+    {
+        // Chunks of a file follow each other
+        if previous == Some(path) {
+            continue;
+        }
+        previous = Some(path);
+        tree.push_str(&format!(
+            "    <file part=\"{}\">{}</file>\n",
+            parts_of(path),
+            path.display()
+        ));
+    }
+    tree.push_str("  </tree>\n");
+    tree
+}
+
+fn render_part(index: &str, of: &str, tree: Option<&str>, fragments: &[&Fragment]) -> String {
+    let mut xml = format!(
+        "{}<part index=\"{}\" of=\"{}\">\n<project>\n",
+        XML_HEADER, index, of
+    );
+
+    if let Some(tree) = tree {
+        xml.push_str(&format!(
+            "  <instructions>This project is split into {} parts. Wait until you have received all of them before answering. The tree below lists the part holding each file.</instructions>\n",
+            of
+        ));
+        xml.push_str(tree);
+    }
+
+    let mut open_section: Option<Section> = None;
+    for fragment in fragments {
+        if open_section != Some(fragment.section) {
+            if let Some((_, close)) = open_section.and_then(Section::tags) {
+                xml.push_str(close);
+            }
+            if let Some((open, _)) = fragment.section.tags() {
+                xml.push_str(open);
+            }
+            open_section = Some(fragment.section);
+        }
+        xml.push_str(&fragment.xml);
+    }
+    if let Some((_, close)) = open_section.and_then(Section::tags) {
+        xml.push_str(close);
+    }
+
+    xml.push_str("</project>\n</part>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(files: &[(&str, String)]) -> CombinedProject {
+        CombinedProject {
+            files: files
+                .iter()
+                .map(|(path, content)| SourceFile {
+                    path: PathBuf::from(path),
+                    content: content.clone(),
+                    lines: None,
+                    error: None,
+                })
+                .collect(),
+            ..CombinedProject::default()
+        }
+    }
+
+    fn lines(count: usize) -> String {
+        (1..=count).map(|line| format!("line {}\n", line)).collect()
+    }
+
+    #[test]
+    fn parts_are_named_after_the_output() {
+        let output = Path::new("out/combined_code.txt");
+        assert_eq!(
+            part_path(output, 2),
+            Path::new("out/combined_code.part2.txt")
+        );
+        assert_eq!(
+            part_path(Path::new("combined"), 1),
+            Path::new("combined.part1")
+        );
+        assert!(is_part_path(
+            Path::new("out/combined_code.part12.txt"),
+            output
+        ));
+        assert!(!is_part_path(
+            Path::new("out/combined_code.part.txt"),
+            output
+        ));
+        assert!(!is_part_path(Path::new("combined_code.part1.txt"), output));
+        assert!(!is_part_path(
+            Path::new("out/combined_code.partx.txt"),
+            output
+        ));
+    }
+
+    #[test]
+    fn outputs_within_the_limit_are_not_split() {
+        let project = project(&[("a.ts", lines(3))]);
+        let parts = project.to_xml_parts(SplitLimit::Bytes(10_000), RenderOptions::default());
+        assert_eq!(parts, [project.to_xml()]);
+    }
+
+    #[test]
+    fn every_part_fits_and_files_stay_whole() {
+        let files: Vec<_> = (0..6)
+            .map(|index| (format!("src/file{}.ts", index), lines(20)))
+            .collect();
+        let files: Vec<_> = files
+            .iter()
+            .map(|(path, content)| (path.as_str(), content.clone()))
+            .collect();
+        let project = project(&files);
+        let limit = 1200;
+        let parts = project.to_xml_parts(SplitLimit::Bytes(limit), RenderOptions::default());
+
+        assert!(parts.len() > 1);
+        let of = format!("of=\"{}\"", parts.len());
+        for (index, part) in parts.iter().enumerate() {
+            assert!(
+                part.len() <= limit,
+                "part {} is {} bytes",
+                index + 1,
+                part.len()
+            );
+            assert!(part.contains(&format!("<part index=\"{}\" {}>", index + 1, of)));
+        }
+        assert!(parts[0].contains("<tree>"));
+        assert!(!parts[1].contains("<tree>"));
+
+        for (path, _) in &files {
+            let holding: Vec<_> = (0..parts.len())
+                .filter(|&index| parts[index].contains(&format!("<file name=\"{}\">", path)))
+                .collect();
+            assert_eq!(holding.len(), 1, "{}", path);
+            let listed = format!("<file part=\"{}\">{}</file>", holding[0] + 1, path);
+            assert!(parts[0].contains(&listed), "{}", listed);
+        }
+    }
+
+    #[test]
+    fn files_larger_than_a_part_are_cut_at_line_boundaries() {
+        let project = project(&[("big.ts", lines(200))]);
+        let limit = 1000;
+        let parts = project.to_xml_parts(SplitLimit::Tokens(limit / 4), RenderOptions::default());
+
+        let chunks = parts.len() - 1;
+        assert!(chunks > 1);
+        assert!(parts[0].contains(&format!("<file part=\"2-{}\">big.ts</file>", parts.len())));
+        let mut content = String::new();
+        for (index, part) in parts.iter().enumerate().skip(1) {
+            assert!(
+                part.len() <= limit,
+                "part {} is {} bytes",
+                index + 1,
+                part.len()
+            );
+            let marker = format!(
+                "<file name=\"big.ts\" chunk=\"{}\" of=\"{}\">\n",
+                index, chunks
+            );
+            let start = part.find(&marker).unwrap() + marker.len();
+            let end = part[start..].find("  </file>").unwrap() + start;
+            content.push_str(&part[start..end]);
+        }
+        let whole = project.to_xml();
+        let open = "<file name=\"big.ts\">\n";
+        let start = whole.find(open).unwrap() + open.len();
+        let end = whole[start..].find("  </file>").unwrap() + start;
+        assert_eq!(content, whole[start..end]);
+    }
+
+    #[test]
+    fn the_first_part_only_holds_the_tree_when_nothing_else_fits() {
+        let project = project(&[("a.ts", lines(10)), ("b.ts", lines(10))]);
+        let limit = 300;
+        let parts = project.to_xml_parts(SplitLimit::Bytes(limit), RenderOptions::default());
+
+        assert!(parts[0].len() > limit);
+        assert!(parts[0].contains("<tree>"));
+        assert!(!parts[0].contains("<file name="));
+        for part in &parts[1..] {
+            assert!(part.len() <= limit, "{} bytes", part.len());
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::dependency_resolver::{
+    canonicalize_path, Import, ImportKind, LanguageResolver, ParsedImports,
+};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+const STYLESHEET_EXTENSIONS: [&str; 4] = ["css", "scss", "sass", "less"];
+
+#[derive(Debug)]
+pub struct StylesheetResolver {
+    import_rule: Regex,
+    url_function: Regex,
+    block_comment: Regex,
+}
+
+impl StylesheetResolver {
+    pub fn new() -> Self {
+        Self {
+            // `@import "a", "b";`, `@use "a" as b;`, `@forward "a";` and `@import (reference) "a";`
+            import_rule: Regex::new(
+                r#"@(?:import|use|forward)\s+(?:\([a-z, ]+\)\s*)?((?:["'][^"']+["']\s*,?\s*)+)"#,
+            )
+            .unwrap(),
+            url_function: Regex::new(r#"url\(\s*["']?([^"')]+?)["']?\s*\)"#).unwrap(),
+            block_comment: Regex::new(r"(?s)/\*.*?\*/").unwrap(),
+        }
+    }
+
+    pub fn is_stylesheet(file_path: &Path) -> bool {
+        file_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| STYLESHEET_EXTENSIONS.contains(&extension))
+    }
+
+    /// Resolves an `@import`/`@use` target or `url()` reference relative to
+    /// `current_file`, trying Sass partials (`_name.scss`) and index files.
+    pub fn resolve_import(&self, import_path: &str, current_file: &Path) -> Option<PathBuf> {
+        let current_dir = current_file.parent().unwrap_or(Path::new(""));
+        let base = current_dir.join(import_path.split(['?', '#']).next()?);
+
+        let extension = current_file
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("css");
+        let file_name = base.file_name()?.to_string_lossy().to_string();
+        let parent = base.parent().unwrap_or(Path::new(""));
+
+        let mut candidates = vec![base.clone()];
+        if base.extension().is_none() || !Self::is_stylesheet(&base) {
+            for candidate_extension in [extension, "scss", "sass", "less", "css"] {
+                candidates.push(parent.join(format!("{}.{}", file_name, candidate_extension)));
+                candidates.push(parent.join(format!("_{}.{}", file_name, candidate_extension)));
+                candidates.push(base.join(format!("_index.{}", candidate_extension)));
+                candidates.push(base.join(format!("index.{}", candidate_extension)));
+            }
+        } else {
+            candidates.push(parent.join(format!("_{}", file_name)));
+        }
+
+        candidates
+            .into_iter()
+            .find(|candidate| candidate.is_file())
+            .map(|candidate| canonicalize_path(&candidate))
+    }
+
+    fn is_local_reference(reference: &str) -> bool {
+        !(reference.is_empty()
+            || reference.starts_with("data:")
+            || reference.starts_with('#')
+            || reference.starts_with('/')
+            // webpack's `~` prefix points into node_modules, which is never included
+            || reference.starts_with('~')
+            || reference.starts_with("sass:")
+            || reference.contains("://")
+            || reference.starts_with("//"))
+    }
+}
+
+impl LanguageResolver for StylesheetResolver {
+    fn get_imports(&mut self, _file_path: &Path, content: &str) -> ParsedImports {
+        let content = self.block_comment.replace_all(content, "");
+        let mut imports = Vec::new();
+
+        for captures in self.import_rule.captures_iter(&content) {
+            for target in captures[1].split(',') {
+                let target = target.trim().trim_matches(|c| c == '"' || c == '\'');
+                if Self::is_local_reference(target) {
+                    imports.push(Import {
+                        specifier: target.to_string(),
+                        kind: ImportKind::Runtime,
+                    });
+                }
+            }
+        }
+
+        for captures in self.url_function.captures_iter(&content) {
+            let target = captures[1].trim();
+            if Self::is_local_reference(target) {
+                imports.push(Import {
+                    specifier: target.to_string(),
+                    kind: ImportKind::Runtime,
+                });
+            }
+        }
+
+        ParsedImports {
+            imports,
+            errors: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn specifiers(file: &str, content: &str) -> Vec<String> {
+        StylesheetResolver::new()
+            .get_imports(Path::new(file), content)
+            .imports
+            .into_iter()
+            .map(|import| import.specifier)
+            .collect()
+    }
+
+    #[test]
+    fn import_rules_and_urls_are_extracted() {
+        let content = r#"
+            @import "reset.css", 'theme';
+            @import (reference) "mixins.less";
+            @use "variables" as vars;
+            @forward "src/list";
+            .logo { background: url( "../images/logo.png" ); }
+            .icon { background: url(icons/star.svg?v=2); }
+        "#;
+        assert_eq!(
+            specifiers("a.scss", content),
+            [
+                "reset.css",
+                "theme",
+                "mixins.less",
+                "variables",
+                "src/list",
+                "../images/logo.png",
+                "icons/star.svg?v=2"
+            ]
+        );
+    }
+
+    #[test]
+    fn non_local_references_are_skipped() {
+        let content = r#"
+            @use "sass:math";
+            @import "~bootstrap/scss/bootstrap";
+            @import url("https://fonts.example.com/font.css");
+            /* @import "commented.css"; */
+            .a { background: url(data:image/png;base64,AAAA); }
+            .b { filter: url(#shadow); }
+            .c { background: url(//cdn.example.com/a.png); }
+            .d { background: url(/static/root.png); }
+        "#;
+        assert!(specifiers("a.scss", content).is_empty());
+    }
+
+    #[test]
+    fn sass_partials_and_indexes_are_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("styles/components")).unwrap();
+        for file in [
+            "styles/main.scss",
+            "styles/_variables.scss",
+            "styles/components/_index.scss",
+            "styles/theme.css",
+            "styles/_mixins.scss",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let resolver = StylesheetResolver::new();
+        let main = root.join("styles/main.scss");
+        let resolve = |specifier: &str| resolver.resolve_import(specifier, &main);
+        assert_eq!(
+            resolve("variables"),
+            Some(root.join("styles/_variables.scss"))
+        );
+        assert_eq!(
+            resolve("mixins.scss"),
+            Some(root.join("styles/_mixins.scss"))
+        );
+        assert_eq!(
+            resolve("components"),
+            Some(root.join("styles/components/_index.scss"))
+        );
+        // Other stylesheet languages are tried after the importer's own
+        assert_eq!(resolve("theme"), Some(root.join("styles/theme.css")));
+        assert_eq!(resolve("missing"), None);
+    }
+
+    #[test]
+    fn queries_and_fragments_are_ignored_when_resolving() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("font.woff2"), "").unwrap();
+        fs::write(root.join("main.css"), "").unwrap();
+
+        let resolved = StylesheetResolver::new()
+            .resolve_import("font.woff2?v=3#iefix", &root.join("main.css"));
+        assert_eq!(resolved, Some(root.join("font.woff2")));
+    }
+}
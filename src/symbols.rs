@@ -0,0 +1,575 @@
+use crate::project::LineRange;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tree_sitter::{Language, Node, Parser, Tree};
+
+/// The kind of definition named by a `file#kind:name` target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SymbolKind {
+    Function,
+    Class,
+    Interface,
+    Type,
+    Enum,
+}
+
+impl SymbolKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "fn" | "function" => Some(SymbolKind::Function),
+            "class" => Some(SymbolKind::Class),
+            "struct" | "impl" => Some(SymbolKind::Class),
+            "interface" | "trait" => Some(SymbolKind::Interface),
+            "type" => Some(SymbolKind::Type),
+            "enum" => Some(SymbolKind::Enum),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SymbolKind::Function => "fn",
+            SymbolKind::Class => "class",
+            SymbolKind::Interface => "interface",
+            SymbolKind::Type => "type",
+            SymbolKind::Enum => "enum",
+        }
+    }
+
+    const ALL: [SymbolKind; 5] = [
+        SymbolKind::Function,
+        SymbolKind::Class,
+        SymbolKind::Interface,
+        SymbolKind::Type,
+        SymbolKind::Enum,
+    ];
+
+    // Definition nodes across the TypeScript, JavaScript, Python, PHP and
+    // Rust grammars. Rust structs and their `impl` blocks count as classes.
+    fn node_kinds(&self) -> &'static [&'static str] {
+        match self {
+            SymbolKind::Function => &[
+                "function_declaration",
+                "generator_function_declaration",
+                "function_signature",
+                "method_definition",
+                "function_definition",
+                "method_declaration",
+                "function_item",
+                "function_signature_item",
+            ],
+            SymbolKind::Class => &[
+                "class_declaration",
+                "abstract_class_declaration",
+                "class_definition",
+                "struct_item",
+                "impl_item",
+            ],
+            SymbolKind::Interface => &["interface_declaration", "trait_declaration", "trait_item"],
+            SymbolKind::Type => &["type_alias_declaration", "type_item"],
+            SymbolKind::Enum => &["enum_declaration", "enum_item"],
+        }
+    }
+}
+
+// Values that make `const name = ...` a function definition
+const FUNCTION_VALUES: [&str; 4] = [
+    "arrow_function",
+    "function",
+    "function_expression",
+    "generator_function",
+];
+
+// Wrappers that belong to the definition they hold
+const DEFINITION_WRAPPERS: [&str; 5] = [
+    "export_statement",
+    "ambient_declaration",
+    "decorated_definition",
+    "lexical_declaration",
+    "variable_declaration",
+];
+
+// Nodes between the root and a top-level definition that do not nest it in
+// another scope
+const TOP_LEVEL_CONTAINERS: [&str; 4] =
+    ["program", "module", "namespace_definition", "source_file"];
+
+// Siblings directly above a definition that belong to it: comments, and the
+// attributes of Rust items
+const LEADING_KINDS: [&str; 4] = ["comment", "line_comment", "block_comment", "attribute_item"];
+
+// Identifiers that may name a definition elsewhere; member names such as
+// the `bar` of `foo.bar()` are left out
+const REFERENCE_KINDS: [&str; 3] = ["identifier", "type_identifier", "name"];
+
+/// A definition located in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Definition {
+    pub name: String,
+    /// With its `export` keyword, decorators and the comments directly above
+    pub lines: LineRange,
+    /// The definition itself, whose identifiers may reference others
+    pub bytes: Range<usize>,
+}
+
+/// Returns true for files whose definitions can be located.
+pub(crate) fn is_supported_file(file_path: &Path) -> bool {
+    language(file_path).is_some()
+}
+
+/// Whether definitions in `a` can be referenced from `b`: both are
+/// TypeScript or JavaScript, or both are in the same other language.
+pub(crate) fn same_language(a: &Path, b: &Path) -> bool {
+    let family = |file_path: &Path| match file_path.extension()?.to_str()? {
+        "ts" | "mts" | "cts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some("script"),
+        "py" => Some("python"),
+        "php" => Some("php"),
+        "rs" => Some("rust"),
+        _ => None,
+    };
+    family(a).is_some() && family(a) == family(b)
+}
+
+fn language(file_path: &Path) -> Option<Language> {
+    match file_path.extension()?.to_str()? {
+        "ts" | "mts" | "cts" => Some(tree_sitter_typescript::language_typescript()),
+        "tsx" => Some(tree_sitter_typescript::language_tsx()),
+        "js" | "jsx" | "mjs" | "cjs" => Some(tree_sitter_javascript::language()),
+        "py" => Some(tree_sitter_python::language()),
+        "php" => Some(tree_sitter_php::language()),
+        "rs" => Some(tree_sitter_rust::language()),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse(file_path: &Path, content: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(language(file_path)?).ok()?;
+    parser.parse(content, None)
+}
+
+/// Finds the first definition of `name` and returns the 1-based lines it
+/// spans, including its `export` keyword, decorators and the comments
+/// directly above it.
+pub(crate) fn find_definition(
+    file_path: &Path,
+    content: &str,
+    kind: SymbolKind,
+    name: &str,
+) -> Option<(usize, usize)> {
+    let tree = parse(file_path, content)?;
+    let node = find_node(tree.root_node(), content, &[kind], name)?;
+    let definition = definition(node, name);
+    Some((definition.lines.start, definition.lines.end))
+}
+
+/// Finds a definition by its path of names, e.g. `["Combiner", "run"]` for
+/// the `run` method of the `Combiner` class, whatever their kinds. Each
+/// definition of an outer name is searched in turn, so that a Rust method
+/// is found in whichever `impl` block of its type holds it.
+pub(crate) fn find_path(file_path: &Path, content: &str, names: &[String]) -> Option<Definition> {
+    let tree = parse(file_path, content)?;
+    let last = names.last()?;
+    let node = find_in_scope(tree.root_node(), content, names)?;
+    Some(definition(node, last))
+}
+
+fn find_in_scope<'tree>(
+    scope: Node<'tree>,
+    content: &str,
+    names: &[String],
+) -> Option<Node<'tree>> {
+    let (first, rest) = names.split_first()?;
+    if rest.is_empty() {
+        return find_node(scope, content, &SymbolKind::ALL, first);
+    }
+    find_nodes(scope, content, &SymbolKind::ALL, first)
+        .into_iter()
+        .find_map(|outer| find_in_scope(outer, content, rest))
+}
+
+/// Lists the definitions that are not nested in a function or class, which
+/// are the ones other code can reference by name.
+pub(crate) fn top_level_definitions(file_path: &Path, content: &str) -> Vec<Definition> {
+    let Some(tree) = parse(file_path, content) else {
+        return Vec::new();
+    };
+
+    let mut definitions = Vec::new();
+    let mut pending = vec![tree.root_node()];
+    while let Some(node) = pending.pop() {
+        if let Some(name) = defined_name(node, content, &SymbolKind::ALL) {
+            definitions.push(definition(node, name));
+            continue;
+        }
+
+        let mut cursor = node.walk();
+        pending.extend(node.children(&mut cursor).filter(|child| {
+            DEFINITION_WRAPPERS.contains(&child.kind())
+                || TOP_LEVEL_CONTAINERS.contains(&child.kind())
+                || child.kind() == "variable_declarator"
+                // The body of a namespace holds its definitions
+                || (child.kind() == "compound_statement"
+                    && node.kind() == "namespace_definition")
+                || defined_name(*child, content, &SymbolKind::ALL).is_some()
+        }));
+    }
+    definitions.sort_by_key(|definition| definition.lines.start);
+    definitions
+}
+
+/// The names used within `bytes` of `content` that could refer to
+/// definitions elsewhere.
+pub(crate) fn referenced_names(
+    file_path: &Path,
+    content: &str,
+    bytes: Range<usize>,
+) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    let Some(tree) = parse(file_path, content) else {
+        return names;
+    };
+    let Some(root) = tree
+        .root_node()
+        .descendant_for_byte_range(bytes.start, bytes.end)
+    else {
+        return names;
+    };
+
+    let mut pending = vec![root];
+    while let Some(node) = pending.pop() {
+        // PHP variables hold their name in a `name` node of their own
+        let is_variable = node
+            .parent()
+            .is_some_and(|parent| parent.kind() == "variable_name");
+        if REFERENCE_KINDS.contains(&node.kind()) && !is_variable {
+            if let Ok(text) = node.utf8_text(content.as_bytes()) {
+                names.insert(text.to_string());
+            }
+        }
+        let mut cursor = node.walk();
+        pending.extend(node.children(&mut cursor));
+    }
+    names
+}
+
+/// The top-level definitions of a set of files, looked up by name when
+/// following the references of a definition.
+pub(crate) struct SymbolIndex {
+    definitions: HashMap<String, Vec<(PathBuf, Definition)>>,
+    contents: HashMap<PathBuf, String>,
+}
+
+impl SymbolIndex {
+    /// Parses every supported file of `files`; unreadable files are skipped.
+    pub fn build(files: impl IntoIterator<Item = PathBuf>) -> Self {
+        let mut index = Self {
+            definitions: HashMap::new(),
+            contents: HashMap::new(),
+        };
+        let mut files: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|file| is_supported_file(file))
+            .collect();
+        files.sort();
+
+        for file in files {
+            let Ok(content) = fs::read_to_string(&file) else {
+                continue;
+            };
+            for definition in top_level_definitions(&file, &content) {
+                index
+                    .definitions
+                    .entry(definition.name.clone())
+                    .or_default()
+                    .push((file.clone(), definition));
+            }
+            index.contents.insert(file, content);
+        }
+        index
+    }
+
+    /// The definition `name` refers to when used in `from`: the one in the
+    /// same file if any, or else the first one in a file of the same language.
+    pub fn lookup(&self, name: &str, from: &Path) -> Option<&(PathBuf, Definition)> {
+        let candidates = self.definitions.get(name)?;
+        candidates
+            .iter()
+            .find(|(file, _)| file == from)
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .find(|(file, _)| same_language(file, from))
+            })
+    }
+
+    pub fn content(&self, file_path: &Path) -> Option<&str> {
+        self.contents.get(file_path).map(String::as_str)
+    }
+}
+
+// The first definition of `name` among `kinds` in the subtree of `node`
+fn find_node<'tree>(
+    node: Node<'tree>,
+    content: &str,
+    kinds: &[SymbolKind],
+    name: &str,
+) -> Option<Node<'tree>> {
+    if defined_name(node, content, kinds) == Some(name) {
+        return Some(node);
+    }
+
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.children(&mut cursor).collect();
+    children
+        .into_iter()
+        .find_map(|child| find_node(child, content, kinds, name))
+}
+
+// Every definition of `name` among `kinds` in the subtree of `node`, in
+// order, without looking inside the definitions found
+fn find_nodes<'tree>(
+    node: Node<'tree>,
+    content: &str,
+    kinds: &[SymbolKind],
+    name: &str,
+) -> Vec<Node<'tree>> {
+    if defined_name(node, content, kinds) == Some(name) {
+        return vec![node];
+    }
+
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.children(&mut cursor).collect();
+    children
+        .into_iter()
+        .flat_map(|child| find_nodes(child, content, kinds, name))
+        .collect()
+}
+
+fn defined_name<'content>(
+    node: Node,
+    content: &'content str,
+    kinds: &[SymbolKind],
+) -> Option<&'content str> {
+    let is_definition = kinds.iter().any(|kind| kind.node_kinds().contains(&node.kind()))
+        // `const name = () => ...` defines a function too
+        || (kinds.contains(&SymbolKind::Function)
+            && node.kind() == "variable_declarator"
+            && node
+                .child_by_field_name("value")
+                .is_some_and(|value| FUNCTION_VALUES.contains(&value.kind())));
+    if !is_definition {
+        return None;
+    }
+
+    // An `impl` block is named after its type, without generic arguments or
+    // a module path
+    let mut name = node.child_by_field_name("name");
+    if node.kind() == "impl_item" {
+        name = node.child_by_field_name("type");
+        while let Some(inner) = name.and_then(|name| {
+            name.child_by_field_name("type")
+                .or_else(|| name.child_by_field_name("name"))
+        }) {
+            name = Some(inner);
+        }
+    }
+    name.and_then(|name| name.utf8_text(content.as_bytes()).ok())
+}
+
+fn definition(node: Node, name: &str) -> Definition {
+    let mut outer = node;
+    while let Some(parent) = outer.parent() {
+        if !DEFINITION_WRAPPERS.contains(&parent.kind()) {
+            break;
+        }
+        outer = parent;
+    }
+
+    let mut first = outer;
+    while let Some(previous) = first.prev_sibling() {
+        let adjacent = previous.end_position().row + 1 >= first.start_position().row;
+        if !LEADING_KINDS.contains(&previous.kind()) || !adjacent {
+            break;
+        }
+        first = previous;
+    }
+
+    Definition {
+        name: name.to_string(),
+        lines: LineRange {
+            start: first.start_position().row + 1,
+            end: outer.end_position().row + 1,
+        },
+        bytes: node.byte_range(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::excerpt;
+
+    const SCRIPT: &str = "\
+import { helper } from './helper';
+
+/** Combines the files. */
+export function combine(files: string[]) {
+  return files.join('\\n');
+}
+
+export const render = (text: string) => text;
+
+@sealed
+export class Combiner {
+  run() {
+    return combine([]);
+  }
+}
+
+export interface Options {
+  root: string;
+}
+";
+
+    fn lines(file: &str, content: &str, kind: SymbolKind, name: &str) -> Option<(usize, usize)> {
+        find_definition(Path::new(file), content, kind, name)
+    }
+
+    fn names(path: &[&str]) -> Vec<String> {
+        path.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn definitions_include_their_export_and_comments() {
+        assert_eq!(
+            lines("a.ts", SCRIPT, SymbolKind::Function, "combine"),
+            Some((3, 6))
+        );
+        assert_eq!(
+            lines("a.ts", SCRIPT, SymbolKind::Interface, "Options"),
+            Some((17, 19))
+        );
+    }
+
+    #[test]
+    fn arrow_functions_and_decorated_classes_are_found() {
+        assert_eq!(
+            lines("a.ts", SCRIPT, SymbolKind::Function, "render"),
+            Some((8, 8))
+        );
+        assert_eq!(
+            lines("a.ts", SCRIPT, SymbolKind::Class, "Combiner"),
+            Some((10, 15))
+        );
+    }
+
+    #[test]
+    fn definitions_of_another_kind_are_not_found() {
+        assert_eq!(lines("a.ts", SCRIPT, SymbolKind::Class, "combine"), None);
+        assert_eq!(lines("a.ts", SCRIPT, SymbolKind::Function, "missing"), None);
+        assert_eq!(
+            lines("a.txt", SCRIPT, SymbolKind::Function, "combine"),
+            None
+        );
+    }
+
+    #[test]
+    fn python_definitions_are_found() {
+        let content = "import os\n\n# Reads the files.\ndef read(path):\n    return open(path).read()\n\nclass Reader:\n    def close(self):\n        pass\n";
+        assert_eq!(
+            lines("a.py", content, SymbolKind::Function, "read"),
+            Some((3, 5))
+        );
+        assert_eq!(
+            lines("a.py", content, SymbolKind::Class, "Reader"),
+            Some((7, 9))
+        );
+    }
+
+    #[test]
+    fn paths_lead_through_outer_definitions() {
+        let path = Path::new("a.ts");
+        let run = find_path(path, SCRIPT, &names(&["Combiner", "run"])).unwrap();
+        assert_eq!(run.name, "run");
+        assert_eq!(run.lines, LineRange { start: 12, end: 14 });
+        assert!(SCRIPT[run.bytes].starts_with("run()"));
+
+        let combine = find_path(path, SCRIPT, &names(&["combine"])).unwrap();
+        assert_eq!(combine.lines, LineRange { start: 3, end: 6 });
+    }
+
+    #[test]
+    fn paths_through_the_wrong_scope_are_not_found() {
+        let path = Path::new("a.ts");
+        assert_eq!(find_path(path, SCRIPT, &names(&["Options", "run"])), None);
+        assert_eq!(find_path(path, SCRIPT, &names(&["Combiner", "stop"])), None);
+        assert_eq!(find_path(path, SCRIPT, &[]), None);
+    }
+
+    const RUST: &str = "\
+/// Combines the files.
+#[derive(Debug, Default)]
+pub struct Combiner {
+    root: PathBuf,
+}
+
+impl Combiner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Display for Combiner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, \"combiner\")
+    }
+}
+
+impl<T> Wrapper<T> {
+    /// Runs the wrapped value.
+    #[inline]
+    pub fn run(&self) {}
+}
+";
+
+    #[test]
+    fn rust_methods_are_found_in_any_impl_block() {
+        let path = Path::new("lib.rs");
+        let new = find_path(path, RUST, &names(&["Combiner", "new"])).unwrap();
+        assert_eq!(new.lines, LineRange { start: 8, end: 10 });
+
+        // `fmt` is only in the second block
+        let fmt = find_path(path, RUST, &names(&["Combiner", "fmt"])).unwrap();
+        assert_eq!(fmt.lines, LineRange { start: 14, end: 16 });
+
+        // Generic arguments are not part of the name
+        let run = find_path(path, RUST, &names(&["Wrapper", "run"])).unwrap();
+        assert_eq!(run.lines, LineRange { start: 20, end: 22 });
+    }
+
+    #[test]
+    fn rust_structs_and_impls_are_classes() {
+        assert_eq!(SymbolKind::parse("struct"), Some(SymbolKind::Class));
+        assert_eq!(SymbolKind::parse("impl"), Some(SymbolKind::Class));
+
+        // Both name the first definition of the type, here the struct
+        let kind = SymbolKind::parse("impl").unwrap();
+        assert_eq!(lines("lib.rs", RUST, kind, "Combiner"), Some((1, 5)));
+        assert_eq!(lines("lib.rs", RUST, kind, "Wrapper"), Some((19, 23)));
+    }
+
+    #[test]
+    fn rust_doc_comments_and_attributes_are_included() {
+        let path = Path::new("lib.rs");
+        let combiner = find_path(path, RUST, &names(&["Combiner"])).unwrap();
+        assert_eq!(combiner.lines, LineRange { start: 1, end: 5 });
+        assert!(RUST[combiner.bytes].starts_with("pub struct Combiner"));
+
+        let run = find_path(path, RUST, &names(&["Wrapper", "run"])).unwrap();
+        let included = excerpt(RUST, run.lines);
+        assert!(included.starts_with("    /// Runs the wrapped value.\n    #[inline]\n"));
+    }
+}
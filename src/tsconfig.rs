@@ -0,0 +1,527 @@
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+// Guards against pathological `extends` chains
+const MAX_EXTENDS_DEPTH: usize = 32;
+
+/// The parts of a `tsconfig.json` that affect module resolution, with the
+/// `extends` chain already applied and every path made absolute.
+#[derive(Debug, Clone)]
+pub struct TsConfig {
+    pub path: PathBuf,
+    pub base_url: Option<PathBuf>,
+    pub paths: Vec<(String, Vec<String>)>,
+    pub paths_base: PathBuf,
+    pub references: Vec<PathBuf>,
+    /// This file and every file of its `extends` chain
+    pub sources: Vec<PathBuf>,
+}
+
+/// Loads tsconfig files and finds the one that applies to a source file.
+#[derive(Debug, Default)]
+pub struct TsConfigLoader {
+    configs: RefCell<HashMap<PathBuf, Option<Rc<TsConfig>>>>,
+    nearest: RefCell<HashMap<PathBuf, Option<PathBuf>>>,
+}
+
+impl TsConfig {
+    /// Reads a tsconfig file and every config it extends.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::load_with_depth(path, 0)
+    }
+
+    fn load_with_depth(path: &Path, depth: usize) -> io::Result<Self> {
+        if depth > MAX_EXTENDS_DEPTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("tsconfig extends chain too deep at: {}", path.display()),
+            ));
+        }
+
+        let content = fs::read_to_string(path)?;
+        let json: Value = serde_json::from_str(&strip_jsonc(&content))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        let mut config = TsConfig {
+            path: path.to_path_buf(),
+            base_url: None,
+            paths: Vec::new(),
+            paths_base: directory.clone(),
+            references: Vec::new(),
+            sources: vec![path.to_path_buf()],
+        };
+
+        // Later entries of an `extends` array override earlier ones
+        let extends = match json.get("extends") {
+            Some(Value::String(spec)) => vec![spec.clone()],
+            Some(Value::Array(specs)) => specs
+                .iter()
+                .filter_map(|spec| spec.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+        for spec in extends {
+            let extended_path = resolve_extends(&directory, &spec).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "Cannot find tsconfig '{}' extended by: {}",
+                        spec,
+                        path.display()
+                    ),
+                )
+            })?;
+            let extended = Self::load_with_depth(&extended_path, depth + 1)?;
+            config.sources.extend(extended.sources);
+            if extended.base_url.is_some() {
+                config.base_url = extended.base_url;
+            }
+            if !extended.paths.is_empty() {
+                config.paths = extended.paths;
+                config.paths_base = extended.paths_base;
+            }
+        }
+
+        let compiler_options = json.get("compilerOptions");
+
+        if let Some(base_url) = compiler_options
+            .and_then(|options| options.get("baseUrl"))
+            .and_then(Value::as_str)
+        {
+            config.base_url = Some(normalize_path(&directory.join(base_url)));
+        }
+
+        if let Some(paths) = compiler_options
+            .and_then(|options| options.get("paths"))
+            .and_then(Value::as_object)
+        {
+            config.paths = paths
+                .iter()
+                .map(|(pattern, targets)| {
+                    let targets = targets
+                        .as_array()
+                        .map(|targets| {
+                            targets
+                                .iter()
+                                .filter_map(|target| target.as_str().map(str::to_string))
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    (pattern.clone(), targets)
+                })
+                .collect();
+            config.paths_base = directory.clone();
+        }
+
+        // `paths` targets are relative to `baseUrl` when it is set anywhere in the chain
+        if let Some(base_url) = &config.base_url {
+            config.paths_base = base_url.clone();
+        }
+
+        if let Some(references) = json.get("references").and_then(Value::as_array) {
+            config.references = references
+                .iter()
+                .filter_map(|reference| reference.get("path").and_then(Value::as_str))
+                .map(|reference| {
+                    let reference_path = normalize_path(&directory.join(reference));
+                    if reference_path.is_dir() {
+                        reference_path.join("tsconfig.json")
+                    } else {
+                        reference_path
+                    }
+                })
+                .collect();
+        }
+
+        Ok(config)
+    }
+
+    pub fn directory(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new(""))
+    }
+
+    /// Maps a bare specifier through `paths` and `baseUrl`.
+    ///
+    /// Returns every candidate in the order TypeScript tries them: each
+    /// target of the best matching `paths` entry, then the `baseUrl` lookup.
+    pub fn resolve_path_alias(&self, specifier: &str) -> Vec<PathBuf> {
+        if specifier.starts_with('.') || Path::new(specifier).is_absolute() {
+            return Vec::new();
+        }
+
+        let mut candidates = Vec::new();
+
+        if let Some((targets, matched)) = self.match_paths(specifier) {
+            for target in targets {
+                let substituted = target.replacen('*', matched, 1);
+                candidates.push(normalize_path(&self.paths_base.join(substituted)));
+            }
+        }
+
+        if let Some(base_url) = &self.base_url {
+            candidates.push(normalize_path(&base_url.join(specifier)));
+        }
+
+        candidates
+    }
+
+    /// Whether a `paths` entry matches `specifier`, whatever its targets.
+    pub fn has_path_alias(&self, specifier: &str) -> bool {
+        self.match_paths(specifier).is_some()
+    }
+
+    // Exact keys win; otherwise the pattern with the longest prefix before `*`
+    fn match_paths<'a>(&'a self, specifier: &'a str) -> Option<(&'a [String], &'a str)> {
+        if let Some((_, targets)) = self.paths.iter().find(|(pattern, _)| pattern == specifier) {
+            return Some((targets, ""));
+        }
+
+        self.paths
+            .iter()
+            .filter_map(|(pattern, targets)| {
+                let (prefix, suffix) = pattern.split_once('*')?;
+                if specifier.len() >= prefix.len() + suffix.len()
+                    && specifier.starts_with(prefix)
+                    && specifier.ends_with(suffix)
+                {
+                    let matched = &specifier[prefix.len()..specifier.len() - suffix.len()];
+                    Some((prefix.len(), targets.as_slice(), matched))
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|(prefix_len, _, _)| *prefix_len)
+            .map(|(_, targets, matched)| (targets, matched))
+    }
+}
+
+impl TsConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the tsconfig that governs `file`.
+    ///
+    /// This is the nearest `tsconfig.json` above the file, or the project it
+    /// references whose directory contains the file when the nearest one is a
+    /// solution-style config.
+    pub fn config_for_file(&self, file: &Path) -> Option<Rc<TsConfig>> {
+        let file = std::path::absolute(file).ok()?;
+        let directory = file.parent()?;
+        let nearest = self.config(&self.find_nearest(directory)?)?;
+
+        let referenced = nearest
+            .references
+            .iter()
+            .filter_map(|reference| self.config(reference))
+            .filter(|reference| file.starts_with(reference.directory()))
+            .max_by_key(|reference| reference.directory().components().count());
+
+        Some(referenced.unwrap_or(nearest))
+    }
+
+    /// Returns the tsconfig at `path`, loading it on first use.
+    pub fn config(&self, path: &Path) -> Option<Rc<TsConfig>> {
+        if let Some(config) = self.configs.borrow().get(path) {
+            return config.clone();
+        }
+
+        let config = TsConfig::load(path).ok().map(Rc::new);
+        self.configs
+            .borrow_mut()
+            .insert(path.to_path_buf(), config.clone());
+        config
+    }
+
+    /// Every file read by the configs loaded so far, including extended ones.
+    pub fn loaded_files(&self) -> Vec<PathBuf> {
+        self.configs
+            .borrow()
+            .values()
+            .flatten()
+            .flat_map(|config| config.sources.iter().cloned())
+            .collect()
+    }
+
+    fn find_nearest(&self, directory: &Path) -> Option<PathBuf> {
+        if let Some(found) = self.nearest.borrow().get(directory) {
+            return found.clone();
+        }
+
+        let candidate = directory.join("tsconfig.json");
+        let found = if candidate.is_file() {
+            Some(candidate)
+        } else {
+            directory
+                .parent()
+                .and_then(|parent| self.find_nearest(parent))
+        };
+
+        self.nearest
+            .borrow_mut()
+            .insert(directory.to_path_buf(), found.clone());
+        found
+    }
+}
+
+// Finds the file an `extends` entry points to: a relative or absolute path,
+// or a config shipped in a package such as `@tsconfig/node18/tsconfig.json`
+fn resolve_extends(directory: &Path, spec: &str) -> Option<PathBuf> {
+    let with_json = |path: PathBuf| -> Option<PathBuf> {
+        if path.is_file() {
+            return Some(path);
+        }
+        let mut with_extension = path.into_os_string();
+        with_extension.push(".json");
+        let with_extension = PathBuf::from(with_extension);
+        with_extension.is_file().then_some(with_extension)
+    };
+
+    if spec.starts_with('.') || Path::new(spec).is_absolute() {
+        return with_json(normalize_path(&directory.join(spec)));
+    }
+
+    for ancestor in directory.ancestors() {
+        let package_path = ancestor.join("node_modules").join(spec);
+        if package_path.is_dir() {
+            let configured = fs::read_to_string(package_path.join("package.json"))
+                .ok()
+                .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+                .and_then(|package| {
+                    package
+                        .get("tsconfig")
+                        .and_then(Value::as_str)
+                        .map(|tsconfig| package_path.join(tsconfig))
+                });
+            if let Some(found) = configured.and_then(with_json) {
+                return Some(found);
+            }
+            return with_json(package_path.join("tsconfig.json"));
+        }
+        if let Some(found) = with_json(package_path) {
+            return Some(found);
+        }
+    }
+
+    None
+}
+
+/// Removes comments and trailing commas so JSONC parses as plain JSON.
+pub fn strip_jsonc(content: &str) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut without_comments = String::with_capacity(content.len());
+    let mut i = 0;
+    let mut in_string = false;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            without_comments.push(c);
+            if c == '\\' && i + 1 < chars.len() {
+                without_comments.push(chars[i + 1]);
+                i += 1;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+            without_comments.push(c);
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+            without_comments.push(' ');
+            continue;
+        } else {
+            without_comments.push(c);
+        }
+        i += 1;
+    }
+
+    let chars: Vec<char> = without_comments.chars().collect();
+    let mut output = String::with_capacity(without_comments.len());
+    let mut in_string = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            output.push(c);
+            if c == '\\' && i + 1 < chars.len() {
+                output.push(chars[i + 1]);
+                i += 1;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+            output.push(c);
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|next| !next.is_whitespace());
+            if !matches!(next, Some('}') | Some(']')) {
+                output.push(c);
+            }
+        } else {
+            output.push(c);
+        }
+        i += 1;
+    }
+
+    output
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_and_trailing_commas_are_stripped() {
+        let content = r#"{
+            // a line comment
+            "compilerOptions": { /* a block
+                comment */ "baseUrl": ".", },
+            "include": ["src", "test",],
+        }"#;
+        let json: Value = serde_json::from_str(&strip_jsonc(content)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "compilerOptions": { "baseUrl": "." },
+                "include": ["src", "test"],
+            })
+        );
+    }
+
+    #[test]
+    fn strings_are_left_alone() {
+        let content = r#"{ "url": "http://example.com/*x*/", "quote": "a\",}", "list": ",]" }"#;
+        let json: Value = serde_json::from_str(&strip_jsonc(content)).unwrap();
+        assert_eq!(json["url"], "http://example.com/*x*/");
+        assert_eq!(json["quote"], "a\",}");
+        assert_eq!(json["list"], ",]");
+    }
+
+    #[test]
+    fn extends_chains_are_applied_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("configs")).unwrap();
+        fs::create_dir_all(root.join("node_modules/@tsconfig/base")).unwrap();
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::write(
+            root.join("node_modules/@tsconfig/base/tsconfig.json"),
+            r#"{ "compilerOptions": { "paths": { "@base/*": ["base/*"] } } }"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("configs/paths.json"),
+            r#"{
+                // Overrides the paths of the package
+                "extends": "@tsconfig/base",
+                "compilerOptions": { "baseUrl": "..", "paths": { "@/*": ["src/*", "lib/*"], }, },
+            }"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("configs/other.json"),
+            r#"{ "compilerOptions": { "baseUrl": "../other" } }"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("app/tsconfig.json"),
+            r#"{ "extends": ["../configs/other", "../configs/paths.json"] }"#,
+        )
+        .unwrap();
+
+        let config = TsConfig::load(&root.join("app/tsconfig.json")).unwrap();
+        assert_eq!(config.base_url, Some(root.to_path_buf()));
+        assert_eq!(config.paths_base, root);
+        assert_eq!(
+            config.resolve_path_alias("@/a"),
+            [root.join("src/a"), root.join("lib/a"), root.join("@/a")]
+        );
+        assert!(!config.has_path_alias("@base/a"));
+        assert_eq!(
+            config.sources,
+            [
+                root.join("app/tsconfig.json"),
+                root.join("configs/other.json"),
+                root.join("configs/paths.json"),
+                root.join("node_modules/@tsconfig/base/tsconfig.json"),
+            ]
+        );
+    }
+
+    #[test]
+    fn circular_extends_chains_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.json"), r#"{ "extends": "./b.json" }"#).unwrap();
+        fs::write(dir.path().join("b.json"), r#"{ "extends": "./a.json" }"#).unwrap();
+        assert!(TsConfig::load(&dir.path().join("a.json")).is_err());
+    }
+
+    #[test]
+    fn exact_and_longest_prefix_patterns_win() {
+        let config = TsConfig {
+            path: PathBuf::from("/p/tsconfig.json"),
+            base_url: None,
+            paths: vec![
+                ("@/*".to_string(), vec!["src/*".to_string()]),
+                ("@/lib/*".to_string(), vec!["lib/*".to_string()]),
+                ("@/lib/exact".to_string(), vec!["exact.ts".to_string()]),
+            ],
+            paths_base: PathBuf::from("/p"),
+            references: Vec::new(),
+            sources: Vec::new(),
+        };
+        assert_eq!(
+            config.resolve_path_alias("@/a"),
+            [PathBuf::from("/p/src/a")]
+        );
+        assert_eq!(
+            config.resolve_path_alias("@/lib/a"),
+            [PathBuf::from("/p/lib/a")]
+        );
+        assert_eq!(
+            config.resolve_path_alias("@/lib/exact"),
+            [PathBuf::from("/p/exact.ts")]
+        );
+        assert!(config.resolve_path_alias("./a").is_empty());
+    }
+
+    #[test]
+    fn dot_components_are_resolved() {
+        assert_eq!(normalize_path(Path::new("/a/./b/../c")), Path::new("/a/c"));
+        assert_eq!(normalize_path(Path::new("a/../../b")), Path::new("../b"));
+    }
+}
@@ -1,12 +1,96 @@
-use crate::dependency_resolver::{DependencyResolver, LanguageResolver};
-use oxc_resolver::{ResolveOptions, Resolver};
+use crate::dependency_resolver::{
+    canonicalize_path, DependencyResolver, Import, ImportKind, LanguageResolver, ParseIssue,
+    ParsedImports, UnresolvedReason,
+};
+use crate::sfc;
+use crate::tsconfig::TsConfig;
+use oxc_resolver::{ResolveOptions, Resolver, TsconfigOptions, TsconfigReferences};
+use regex::Regex;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use tree_sitter::{Parser, Query, QueryCursor};
+use tree_sitter::{Language, Node, Parser, Query, QueryCursor};
+
+// Import forms shared by the TypeScript, TSX and JavaScript grammars
+const IMPORT_QUERY: &str = r#"
+    (import_statement source: (string) @import_path)
+    (export_statement source: (string) @import_path)
+    (call_expression
+        function: (import)
+        arguments: (arguments . (string) @dynamic_path))
+    (call_expression
+        function: (identifier) @callee
+        arguments: (arguments . (string) @require_path)
+        (#eq? @callee "require"))
+    (call_expression
+        function: (member_expression
+            object: (identifier) @mock_object
+            property: (property_identifier) @mock_method)
+        arguments: (arguments . (string) @require_path)
+        (#match? @mock_object "^(jest|vi)$")
+        (#match? @mock_method "^(mock|doMock|unmock|requireActual|requireMock|importActual)$"))
+    (comment) @comment
+"#;
+
+// `import x = require('./x')` only exists in TypeScript
+const TYPESCRIPT_IMPORT_QUERY: &str = r#"
+    (import_require_clause source: (string) @import_path)
+"#;
+
+// Keeps the report readable when a file is badly broken
+const MAX_PARSE_ISSUES: usize = 10;
+
+// Node kinds whose subtree is only evaluated by the type checker
+const TYPE_CONTEXTS: [&str; 5] = [
+    "type_query",
+    "type_annotation",
+    "type_alias_declaration",
+    "interface_declaration",
+    "type_arguments",
+];
+
+/// The grammar used to parse a file, chosen by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Dialect {
+    TypeScript,
+    Tsx,
+    JavaScript,
+}
+
+impl Dialect {
+    fn from_path(file_path: &Path) -> Option<Self> {
+        match file_path.extension()?.to_str()? {
+            "ts" | "mts" | "cts" => Some(Dialect::TypeScript),
+            "tsx" => Some(Dialect::Tsx),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Dialect::JavaScript),
+            _ => None,
+        }
+    }
+
+    fn language(&self) -> Language {
+        match self {
+            Dialect::TypeScript => tree_sitter_typescript::language_typescript(),
+            Dialect::Tsx => tree_sitter_typescript::language_tsx(),
+            Dialect::JavaScript => tree_sitter_javascript::language(),
+        }
+    }
+
+    fn import_query(&self) -> Query {
+        let source = match self {
+            Dialect::TypeScript | Dialect::Tsx => {
+                format!("{}{}", IMPORT_QUERY, TYPESCRIPT_IMPORT_QUERY)
+            }
+            Dialect::JavaScript => IMPORT_QUERY.to_string(),
+        };
+        Query::new(self.language(), &source).unwrap()
+    }
+}
 
 pub struct TypeScriptResolver {
     resolver: Resolver,
-    import_query: Query,
+    tsconfig_resolvers: RefCell<HashMap<PathBuf, Resolver>>,
+    import_queries: HashMap<Dialect, Query>,
+    reference_directive: Regex,
     parser: Parser,
 }
 
@@ -16,8 +100,14 @@ impl TypeScriptResolver {
             extensions: vec![
                 ".ts".to_string(),
                 ".tsx".to_string(),
+                ".mts".to_string(),
+                ".cts".to_string(),
                 ".js".to_string(),
                 ".jsx".to_string(),
+                ".mjs".to_string(),
+                ".cjs".to_string(),
+                ".vue".to_string(),
+                ".svelte".to_string(),
             ],
             condition_names: vec!["import".to_string(), "require".to_string()],
             description_files: vec!["package.json".to_string()],
@@ -25,162 +115,418 @@ impl TypeScriptResolver {
             ..Default::default()
         });
 
-        let mut parser = Parser::new();
-        let language = tree_sitter_typescript::language_typescript();
-        parser.set_language(language).unwrap();
+        let import_queries = [Dialect::TypeScript, Dialect::Tsx, Dialect::JavaScript]
+            .into_iter()
+            .map(|dialect| (dialect, dialect.import_query()))
+            .collect();
 
-        let import_query = Query::new(
-            language,
-            r#"
-            (import_statement source: (string) @import_path)
-            (import_require_clause source: (string) @import_path)
-            (export_statement source: (string) @import_path)
-            "#,
-        )
-        .unwrap();
+        let reference_directive =
+            Regex::new(r#"^///\s*<reference\s+(path|types)\s*=\s*["']([^"']+)["']"#).unwrap();
 
         Self {
             resolver,
-            import_query,
-            parser,
+            tsconfig_resolvers: RefCell::new(HashMap::new()),
+            import_queries,
+            reference_directive,
+            parser: Parser::new(),
         }
     }
 
-    pub fn is_supported_file(file_path: &Path) -> bool {
-        if let Some(extension) = file_path.extension() {
-            matches!(
-                extension.to_str(),
-                Some("ts") | Some("tsx") | Some("js") | Some("jsx")
-            )
+    /// Forgets the files, directories and tsconfig files the module resolver
+    /// has looked at, so that files added or removed since are noticed.
+    pub fn clear_cache(&self) {
+        // Resolvers for a tsconfig share the cache of the plain one
+        self.resolver.clear_cache();
+    }
+
+    // `import type`, `export type`, or a clause where every specifier is `type X`
+    fn is_type_only_statement(statement: Node) -> bool {
+        let mut cursor = statement.walk();
+        let children: Vec<Node> = statement.children(&mut cursor).collect();
+
+        if children.iter().any(|child| child.kind() == "type") {
+            return true;
+        }
+
+        let clause = children
+            .iter()
+            .find(|child| matches!(child.kind(), "import_clause" | "export_clause"));
+        let Some(clause) = clause else {
+            return false;
+        };
+
+        let specifiers_parent = if clause.kind() == "import_clause" {
+            let mut cursor = clause.walk();
+            let clause_children: Vec<Node> = clause.named_children(&mut cursor).collect();
+            // A default or namespace import always brings in a runtime value
+            if clause_children.len() != 1 || clause_children[0].kind() != "named_imports" {
+                return false;
+            }
+            clause_children[0]
         } else {
-            false
+            *clause
+        };
+
+        let mut cursor = specifiers_parent.walk();
+        let specifiers: Vec<Node> = specifiers_parent.named_children(&mut cursor).collect();
+        !specifiers.is_empty()
+            && specifiers.iter().all(|specifier| {
+                let mut cursor = specifier.walk();
+                let is_type = specifier
+                    .children(&mut cursor)
+                    .any(|child| child.kind() == "type");
+                is_type
+            })
+    }
+
+    fn is_in_type_context(node: Node) -> bool {
+        let mut current = node.parent();
+        while let Some(parent) = current {
+            if TYPE_CONTEXTS.contains(&parent.kind()) {
+                return true;
+            }
+            current = parent.parent();
         }
+        false
     }
 
-    fn resolve_with_alias(
+    pub fn is_supported_file(file_path: &Path) -> bool {
+        Dialect::from_path(file_path).is_some() || sfc::is_component_file(file_path)
+    }
+
+    pub fn resolve_import_with_resolver(
         &self,
         import_path: &str,
-        alias_map: &HashMap<String, String>,
-        _base_path: &Path,
-    ) -> Option<String> {
-        if import_path.starts_with('~') {
-            let without_tilde = import_path.strip_prefix('~').unwrap();
-            let path = without_tilde.trim_start_matches('/');
-            if !path.contains('.') {
-                return Some(path.to_string());
-            }
-            return Some(path.to_string());
+        current_file: &Path,
+        dependency_resolver: &DependencyResolver,
+    ) -> Option<PathBuf> {
+        let current_dir = current_file.parent().unwrap_or(Path::new(""));
+        let tsconfig = dependency_resolver.get_tsconfig(current_file);
+
+        if import_path.starts_with('.') {
+            return self.resolve_specifier(tsconfig.as_deref(), current_dir, import_path);
         }
 
-        if import_path.starts_with('@') || !import_path.starts_with('.') {
-            return Some(import_path.to_string());
+        // Try every `paths` fallback and `baseUrl` before looking at packages
+        if let Some(tsconfig) = &tsconfig {
+            for candidate in tsconfig.resolve_path_alias(import_path) {
+                let resolved = self.resolve_specifier(
+                    Some(tsconfig),
+                    current_dir,
+                    &candidate.to_string_lossy(),
+                );
+                if resolved.is_some() {
+                    return resolved;
+                }
+            }
         }
 
-        for (alias, target) in alias_map {
-            if import_path.starts_with(alias) {
-                let resolved = import_path.replacen(alias, target, 1);
-                return Some(resolved);
+        // Sibling workspace packages resolve to their sources, not to build output
+        if let Some(workspace) = dependency_resolver.get_workspace() {
+            for candidate in workspace.resolve_package_import(import_path) {
+                let resolved = self.resolve_specifier(
+                    tsconfig.as_deref(),
+                    current_dir,
+                    &candidate.to_string_lossy(),
+                );
+                if resolved.is_some() {
+                    return resolved;
+                }
             }
         }
 
-        Some(import_path.to_string())
+        self.resolve_specifier(tsconfig.as_deref(), current_dir, import_path)
+            .or_else(|| self.resolve_from_src_dir(import_path, current_file, dependency_resolver))
     }
 
-    pub fn resolve_import_with_resolver(
+    /// Explains why `resolve_import_with_resolver` found nothing for
+    /// `import_path`.
+    pub fn unresolved_reason(
         &self,
         import_path: &str,
         current_file: &Path,
         dependency_resolver: &DependencyResolver,
+    ) -> UnresolvedReason {
+        if import_path.starts_with('.') || Path::new(import_path).is_absolute() {
+            return UnresolvedReason::MissingFile;
+        }
+
+        let tsconfig = dependency_resolver.get_tsconfig(current_file);
+        if tsconfig.is_some_and(|tsconfig| tsconfig.has_path_alias(import_path)) {
+            return UnresolvedReason::UnknownAlias;
+        }
+        if dependency_resolver
+            .get_workspace()
+            .is_some_and(|workspace| !workspace.resolve_package_import(import_path).is_empty())
+        {
+            return UnresolvedReason::MissingFile;
+        }
+
+        // Prefixes commonly mapped to the source directory; package names
+        // cannot start with them
+        if ["~", "@/", "#"]
+            .iter()
+            .any(|prefix| import_path.starts_with(prefix))
+        {
+            UnresolvedReason::UnknownAlias
+        } else {
+            UnresolvedReason::ExternalPackage
+        }
+    }
+
+    // Resolves with the oxc resolver configured for `tsconfig`, falling back to
+    // the plain resolver when oxc cannot use that tsconfig
+    fn resolve_specifier(
+        &self,
+        tsconfig: Option<&TsConfig>,
+        directory: &Path,
+        specifier: &str,
     ) -> Option<PathBuf> {
-        let resolved_path = self.resolve_with_alias(
-            import_path,
-            dependency_resolver
-                .get_alias_map()
-                .unwrap_or(&HashMap::new()),
-            dependency_resolver.get_base_path(),
-        )?;
-
-        let result = if resolved_path.starts_with('@') || !resolved_path.starts_with('.') {
-            let project_root = if let Some(current_dir) = current_file.parent() {
-                let mut dir = current_dir;
-                let mut found_root = None;
-
-                while let Some(parent) = dir.parent() {
-                    if dir.ends_with("src") {
-                        found_root = Some(parent.to_path_buf());
-                        break;
-                    }
-                    dir = parent;
+        let resolved = tsconfig.and_then(|tsconfig| {
+            let mut resolvers = self.tsconfig_resolvers.borrow_mut();
+            let resolver = resolvers.entry(tsconfig.path.clone()).or_insert_with(|| {
+                self.resolver.clone_with_options(ResolveOptions {
+                    tsconfig: Some(TsconfigOptions {
+                        config_file: tsconfig.path.clone(),
+                        references: TsconfigReferences::Paths(tsconfig.references.clone()),
+                    }),
+                    ..self.resolver.options().clone()
+                })
+            });
+            resolver.resolve(directory, specifier).ok()
+        });
+
+        resolved
+            .or_else(|| self.resolver.resolve(directory, specifier).ok())
+            .map(|resolved| canonicalize_path(&resolved.full_path()))
+    }
+
+    // Projects without a tsconfig often import from `src` directly (`~/x` or `x`),
+    // relative to the package that contains the importing file
+    fn resolve_from_src_dir(
+        &self,
+        import_path: &str,
+        current_file: &Path,
+        dependency_resolver: &DependencyResolver,
+    ) -> Option<PathBuf> {
+        let import_path = import_path.trim_start_matches('~').trim_start_matches('/');
+
+        let current_file = canonicalize_path(current_file);
+        let package_root = current_file
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join("package.json").is_file())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| dependency_resolver.get_base_path().to_path_buf());
+
+        let src_dir = package_root.join("src");
+        let direct_path = src_dir.join(import_path);
+
+        if direct_path.is_dir() {
+            let index_extensions = [".tsx", ".ts", ".jsx", ".js"];
+            for ext in index_extensions.iter() {
+                let index_file = direct_path.join(format!("index{}", ext));
+                if index_file.exists() {
+                    return Some(canonicalize_path(&index_file));
                 }
+            }
+            None
+        } else if direct_path.exists() {
+            Some(canonicalize_path(&direct_path))
+        } else {
+            let extensions = [".tsx", ".ts", ".jsx", ".js"];
+            for ext in extensions.iter() {
+                let file_with_ext = direct_path.with_extension(&ext[1..]);
+                if file_with_ext.exists() {
+                    return Some(canonicalize_path(&file_with_ext));
+                }
+            }
+            None
+        }
+    }
+
+    fn parse_imports(&mut self, dialect: Dialect, content: &str) -> ParsedImports {
+        self.parser.set_language(dialect.language()).unwrap();
 
-                found_root.unwrap_or_else(|| current_dir.to_path_buf())
-            } else {
-                dependency_resolver.get_base_path().to_path_buf()
+        let Some(tree) = self.parser.parse(content, None) else {
+            return ParsedImports {
+                imports: Vec::new(),
+                errors: vec![ParseIssue {
+                    line: 1,
+                    column: 1,
+                    message: "parser gave up on this file".to_string(),
+                }],
             };
+        };
+
+        let import_query = &self.import_queries[&dialect];
+        let mut imports = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let capture_names = import_query.capture_names();
+
+        for match_ in cursor.matches(import_query, tree.root_node(), content.as_bytes()) {
+            for capture in match_.captures {
+                let text = capture.node.utf8_text(content.as_bytes()).unwrap();
 
-            let src_dir = project_root.join("src");
-            let direct_path = src_dir.join(&resolved_path);
-
-            if direct_path.exists() {
-                if direct_path.is_dir() {
-                    let index_extensions = [".tsx", ".ts", ".jsx", ".js"];
-                    for ext in index_extensions.iter() {
-                        let index_file = direct_path.join(format!("index{}", ext));
-                        if index_file.exists() {
-                            return Some(index_file);
-                        }
+                let import = match capture_names[capture.index as usize].as_str() {
+                    "import_path" => {
+                        let statement = capture
+                            .node
+                            .parent()
+                            .filter(|parent| parent.kind() != "import_require_clause");
+                        let kind = match statement {
+                            Some(statement) if Self::is_type_only_statement(statement) => {
+                                ImportKind::TypeOnly
+                            }
+                            _ => ImportKind::Runtime,
+                        };
+                        Some((trim_quotes(text), kind))
                     }
-                    None
-                } else {
-                    Some(direct_path)
-                }
-            } else {
-                let extensions = [".tsx", ".ts", ".jsx", ".js"];
-                for ext in extensions.iter() {
-                    let file_with_ext = direct_path.with_extension(&ext[1..]);
-                    if file_with_ext.exists() {
-                        return Some(file_with_ext);
+                    "dynamic_path" => {
+                        let kind = if Self::is_in_type_context(capture.node) {
+                            ImportKind::TypeOnly
+                        } else {
+                            ImportKind::Dynamic
+                        };
+                        Some((trim_quotes(text), kind))
                     }
-                }
+                    "require_path" => Some((trim_quotes(text), ImportKind::Runtime)),
+                    "comment" => self.reference_directive.captures(text).map(|directive| {
+                        let target = directive[2].to_string();
+                        // `path` references are relative to the file even without `./`
+                        let target = if &directive[1] == "path" && !target.starts_with('.') {
+                            format!("./{}", target)
+                        } else {
+                            target
+                        };
+                        (target, ImportKind::TypeOnly)
+                    }),
+                    _ => None,
+                };
 
-                self.resolver
-                    .resolve(&src_dir, &resolved_path)
-                    .ok()
-                    .map(|resolved| {
-                        PathBuf::from(resolved.full_path().to_string_lossy().to_string())
-                    })
+                if let Some((specifier, kind)) = import {
+                    imports.push(Import { specifier, kind });
+                }
             }
-        } else {
-            let current_dir = current_file.parent().unwrap_or(Path::new(""));
-            self.resolver
-                .resolve(current_dir, &resolved_path)
-                .ok()
-                .map(|resolved| PathBuf::from(resolved.full_path().to_string_lossy().to_string()))
-        };
+        }
+
+        let mut errors = Vec::new();
+        if tree.root_node().has_error() {
+            collect_parse_issues(tree.root_node(), content, &mut errors);
+        }
 
-        result
+        ParsedImports { imports, errors }
     }
 }
 
 impl LanguageResolver for TypeScriptResolver {
-    fn get_imports(&mut self, content: &str) -> Vec<String> {
-        let tree = self.parser.parse(content, None).unwrap();
-        let mut imports = Vec::new();
-        let mut cursor = QueryCursor::new();
+    fn get_imports(&mut self, file_path: &Path, content: &str) -> ParsedImports {
+        if !sfc::is_component_file(file_path) {
+            let dialect = Dialect::from_path(file_path).unwrap_or(Dialect::TypeScript);
+            return self.parse_imports(dialect, content);
+        }
 
-        for match_ in cursor.matches(&self.import_query, tree.root_node(), content.as_bytes()) {
-            for capture in match_.captures {
-                let import_path = capture
-                    .node
-                    .utf8_text(content.as_bytes())
-                    .unwrap()
-                    .trim_matches('"')
-                    .trim_matches('\'')
-                    .to_string();
-                imports.push(import_path);
+        let mut parsed = ParsedImports::default();
+        for block in sfc::extract_script_blocks(file_path, content) {
+            if let Some(src) = &block.src {
+                let specifier = if src.starts_with('.') || src.starts_with('/') {
+                    src.clone()
+                } else {
+                    format!("./{}", src)
+                };
+                parsed.imports.push(Import {
+                    specifier,
+                    kind: ImportKind::Runtime,
+                });
             }
+
+            let dialect = match block.lang.as_deref() {
+                Some("tsx") | Some("jsx") => Dialect::Tsx,
+                _ => Dialect::TypeScript,
+            };
+            let block_parsed = self.parse_imports(dialect, &block.content);
+            parsed.imports.extend(block_parsed.imports);
+            parsed
+                .errors
+                .extend(block_parsed.errors.into_iter().map(|error| ParseIssue {
+                    line: error.line + block.line_offset,
+                    column: if error.line == 1 {
+                        error.column + block.column_offset
+                    } else {
+                        error.column
+                    },
+                    ..error
+                }));
+        }
+
+        parsed
+    }
+}
+
+// Records ERROR and MISSING nodes without descending into broken subtrees
+fn collect_parse_issues(node: Node, content: &str, issues: &mut Vec<ParseIssue>) {
+    if issues.len() >= MAX_PARSE_ISSUES {
+        return;
+    }
+
+    let position = node.start_position();
+    if node.is_error() {
+        let snippet: String = node
+            .utf8_text(content.as_bytes())
+            .unwrap_or("")
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .chars()
+            .take(40)
+            .collect();
+        issues.push(ParseIssue {
+            line: position.row + 1,
+            column: position.column + 1,
+            message: format!("unexpected `{}`", snippet),
+        });
+        return;
+    }
+    if node.is_missing() {
+        issues.push(ParseIssue {
+            line: position.row + 1,
+            column: position.column + 1,
+            message: format!("missing `{}`", node.kind()),
+        });
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.has_error() || child.is_missing() {
+            collect_parse_issues(child, content, issues);
         }
+    }
+}
+
+fn trim_quotes(text: &str) -> String {
+    text.trim_matches('"').trim_matches('\'').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn component_parse_errors_point_into_the_component() {
+        let body = "const = 1;\nlet ok = 2;\n";
+        let content = format!("<template />\n<script lang=\"ts\">{}</script>\n", body);
+        let mut resolver = TypeScriptResolver::new();
 
-        imports
+        let standalone = resolver.get_imports(Path::new("setup.ts"), body).errors;
+        let embedded = resolver.get_imports(Path::new("App.vue"), &content).errors;
+        assert!(!standalone.is_empty());
+        assert_eq!(standalone[0].line, 1);
+        assert_eq!(embedded[0].line, 2);
+        assert_eq!(
+            embedded[0].column,
+            standalone[0].column + "<script lang=\"ts\">".len()
+        );
     }
 }
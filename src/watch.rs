@@ -0,0 +1,425 @@
+use crate::cache::ParseCache;
+use crate::combiner::{Assembly, Combiner, Resolvers, Sink};
+use crate::dependency_resolver::canonicalize_path;
+use crate::error::{Error, Result};
+use crate::ignore_patterns::is_ignored;
+use crate::project::CombinedProject;
+use crate::split::is_part_path;
+use crate::stylesheet_resolver::StylesheetResolver;
+use crate::typescript_resolver::TypeScriptResolver;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashSet};
+use std::io;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// One run of `Combiner::watch`.
+#[derive(Debug)]
+pub struct WatchRun {
+    /// The files whose changes triggered the run; empty for the first run
+    pub changed: Vec<PathBuf>,
+    /// The delivered project, or why it could not be combined or delivered
+    pub result: Result<CombinedProject>,
+}
+
+// What the previous run read, and therefore what can make the next one differ
+#[derive(Default)]
+struct Watched {
+    files: HashSet<PathBuf>,
+    config_files: HashSet<PathBuf>,
+    directories: HashSet<PathBuf>,
+    recursive_directories: HashSet<PathBuf>,
+    output: Option<PathBuf>,
+}
+
+impl Combiner {
+    /// Combines and delivers the project, then does it again whenever a
+    /// combined file, one of its dependencies, a tsconfig file or workspace
+    /// manifest changes, or a file is added under a combined directory or,
+    /// when following imports, next to a file that was read.
+    ///
+    /// Changes arriving within `debounce` of each other trigger a single run.
+    /// The resolvers are kept between runs, so only changed files are parsed
+    /// again, and start over when a tsconfig file or manifest changed. Imports
+    /// are resolved again every run, so added and removed files are noticed.
+    /// Changes to `extra_files`, such as the configuration the combiner was
+    /// built from, trigger a run too. Stops when `on_run` breaks.
+    pub fn watch<F>(&self, debounce: Duration, extra_files: &[PathBuf], mut on_run: F) -> Result<()>
+    where
+        F: FnMut(&WatchRun) -> ControlFlow<()>,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+        let mut watched = Watched::default();
+        let mut resolvers: Option<Resolvers> = None;
+        let mut changed = Vec::new();
+
+        loop {
+            if changed
+                .iter()
+                .any(|path| watched.config_files.contains(path))
+            {
+                // Cached tsconfig and workspace data would be stale
+                resolvers = None;
+            }
+
+            let (result, sources) = self.watch_run(&mut resolvers);
+            let next = self.watched(sources, resolvers.as_ref(), extra_files);
+            next.register(&mut watcher, &watched);
+            watched = next;
+
+            let run = WatchRun { changed, result };
+            if on_run(&run).is_break() {
+                return Ok(());
+            }
+
+            changed = self.wait_for_changes(&receiver, debounce, &watched)?;
+        }
+    }
+
+    fn watch_run(
+        &self,
+        resolvers: &mut Option<Resolvers>,
+    ) -> (Result<CombinedProject>, Vec<PathBuf>) {
+        let options = self.options();
+        if options.deps && resolvers.is_none() {
+            match Resolvers::new(options) {
+                Ok(mut created) => {
+                    if !options.cache {
+                        created.deps.set_cache(ParseCache::in_memory());
+                    }
+                    *resolvers = Some(created);
+                }
+                Err(err) => return (Err(err.into()), Vec::new()),
+            }
+        }
+
+        // Imported files may have been added or removed since the last run
+        if let Some(resolvers) = resolvers.as_ref() {
+            resolvers.forget_files();
+        }
+
+        match self.assemble(resolvers.as_mut()) {
+            Ok(Assembly {
+                project, sources, ..
+            }) => (self.deliver(&project).map(|_| project), sources),
+            Err(err) => (Err(err), Vec::new()),
+        }
+    }
+
+    fn watched(
+        &self,
+        sources: Vec<PathBuf>,
+        resolvers: Option<&Resolvers>,
+        extra_files: &[PathBuf],
+    ) -> Watched {
+        let options = self.options();
+        let config_files: HashSet<PathBuf> = resolvers
+            .map(|resolvers| resolvers.deps.config_files())
+            .unwrap_or_default()
+            .iter()
+            .map(|file| canonicalize_path(file))
+            .collect();
+
+        let mut files: HashSet<PathBuf> = sources.into_iter().collect();
+        files.extend(config_files.iter().cloned());
+        files.extend(
+            options
+                .paths
+                .iter()
+                .chain(&options.target_files)
+                .chain(&options.reference_files)
+                .chain(extra_files)
+                .filter(|path| !path.is_dir())
+                .map(|path| canonicalize_path(path)),
+        );
+
+        let recursive_directories: HashSet<PathBuf> = options
+            .paths
+            .iter()
+            .filter(|path| path.is_dir())
+            .map(|path| canonicalize_path(path))
+            .collect();
+
+        // Editors often save by replacing the file, which ends a watch on
+        // the file itself, so the directories holding the files are watched
+        let directories = files
+            .iter()
+            .filter_map(|file| file.parent())
+            .filter(|directory| {
+                !recursive_directories
+                    .iter()
+                    .any(|recursive| directory.starts_with(recursive))
+            })
+            .map(Path::to_path_buf)
+            .collect();
+
+        let output = match self.sink() {
+            Sink::File(path) => Some(canonicalize_path(path)),
+            _ => None,
+        };
+
+        Watched {
+            files,
+            config_files,
+            directories,
+            recursive_directories,
+            output,
+        }
+    }
+
+    // Blocks until a relevant change, then collects the changes that follow
+    // it until `debounce` passes without one
+    fn wait_for_changes(
+        &self,
+        receiver: &Receiver<notify::Result<Event>>,
+        debounce: Duration,
+        watched: &Watched,
+    ) -> Result<Vec<PathBuf>> {
+        let mut changed = BTreeSet::new();
+        // Files added under a combined directory are only listed when needed
+        let mut listed_files: Option<HashSet<PathBuf>> = None;
+
+        loop {
+            let event = if changed.is_empty() {
+                receiver.recv().map_err(|_| stopped())?
+            } else {
+                match receiver.recv_timeout(debounce) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return Err(stopped()),
+                }
+            };
+
+            // Reading the files is not a change
+            let Ok(event) = event else { continue };
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+
+            let created = matches!(event.kind, EventKind::Create(_));
+            for path in event.paths {
+                if watched
+                    .output
+                    .as_ref()
+                    .is_some_and(|output| *output == path || is_part_path(&path, output))
+                {
+                    continue;
+                }
+
+                let relevant = watched.files.contains(&path)
+                    || (watched
+                        .recursive_directories
+                        .iter()
+                        .any(|directory| path.starts_with(directory))
+                        && listed_files
+                            .get_or_insert_with(|| self.listed_files())
+                            .contains(&path));
+                if relevant || (created && self.may_be_imported(&path, watched)) {
+                    changed.insert(path);
+                }
+            }
+        }
+
+        Ok(changed.into_iter().collect())
+    }
+
+    // A new script or stylesheet next to the files read may be what one of
+    // their unresolved imports was looking for
+    fn may_be_imported(&self, path: &Path, watched: &Watched) -> bool {
+        self.options().deps
+            && path
+                .parent()
+                .is_some_and(|directory| watched.directories.contains(directory))
+            && (TypeScriptResolver::is_supported_file(path)
+                || StylesheetResolver::is_stylesheet(path))
+            && !is_ignored(path, &self.options().ignore_patterns)
+    }
+
+    fn listed_files(&self) -> HashSet<PathBuf> {
+        self.walk_paths()
+            .into_iter()
+            .filter(|file| !is_ignored(file, &self.options().ignore_patterns))
+            .map(|file| canonicalize_path(&file))
+            .collect()
+    }
+}
+
+impl Watched {
+    // Watches what is new and stops watching what the last run no longer read
+    fn register(&self, watcher: &mut RecommendedWatcher, previous: &Watched) {
+        // A directory that disappeared cannot be watched; it comes back as a
+        // change in its parent if it was a combined path
+        for directory in previous.directories.difference(&self.directories) {
+            let _ = watcher.unwatch(directory);
+        }
+        for directory in previous
+            .recursive_directories
+            .difference(&self.recursive_directories)
+        {
+            let _ = watcher.unwatch(directory);
+        }
+        for directory in self.directories.difference(&previous.directories) {
+            let _ = watcher.watch(directory, RecursiveMode::NonRecursive);
+        }
+        for directory in self
+            .recursive_directories
+            .difference(&previous.recursive_directories)
+        {
+            let _ = watcher.watch(directory, RecursiveMode::Recursive);
+        }
+    }
+}
+
+fn watch_error(err: notify::Error) -> Error {
+    Error::from(io::Error::other(err))
+}
+
+fn stopped() -> Error {
+    Error::from(io::Error::other("the file watcher stopped"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combiner::PathStyle;
+    use std::fs;
+    use std::sync::mpsc::Sender;
+    use std::thread;
+
+    // What a run produced: its changed files, dependencies and unresolved
+    // imports, or its error
+    type Summary = (
+        Vec<PathBuf>,
+        std::result::Result<(Vec<PathBuf>, Vec<String>), String>,
+    );
+
+    fn summary(run: &WatchRun) -> Summary {
+        let result = match &run.result {
+            Ok(project) => Ok((
+                project
+                    .dependencies
+                    .iter()
+                    .map(|dependency| dependency.path.clone())
+                    .collect(),
+                project
+                    .unresolved
+                    .iter()
+                    .map(|unresolved| unresolved.specifier.clone())
+                    .collect(),
+            )),
+            Err(err) => Err(err.to_string()),
+        };
+        (run.changed.clone(), result)
+    }
+
+    // Watches `combiner` on another thread, calling `step` with the number of
+    // each run; the watch stops once `step` returns false
+    fn watch(
+        combiner: Combiner,
+        mut step: impl FnMut(usize) -> bool + Send + 'static,
+    ) -> Receiver<Summary> {
+        let (sender, receiver): (Sender<Summary>, _) = mpsc::channel();
+        thread::spawn(move || {
+            let mut runs = 0;
+            combiner
+                .watch(Duration::from_millis(50), &[], |run| {
+                    runs += 1;
+                    let _ = sender.send(summary(run));
+                    if step(runs) {
+                        ControlFlow::Continue(())
+                    } else {
+                        ControlFlow::Break(())
+                    }
+                })
+                .unwrap();
+        });
+        receiver
+    }
+
+    fn next(receiver: &Receiver<Summary>) -> Summary {
+        receiver
+            .recv_timeout(Duration::from_secs(20))
+            .expect("the watch ran again")
+    }
+
+    fn project() -> (tempfile::TempDir, PathBuf, Combiner) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/main.ts"), "import { b } from './b';\n").unwrap();
+        let combiner = Combiner::builder()
+            .path(root.join("src/main.ts"))
+            .deps(true)
+            .cache(false)
+            .path_style(PathStyle::RootRelative)
+            .root(&root)
+            .build()
+            .unwrap();
+        (dir, root, combiner)
+    }
+
+    #[test]
+    fn the_first_run_has_no_changes() {
+        let (_dir, _root, combiner) = project();
+        let receiver = watch(combiner, |_| false);
+        let (changed, result) = next(&receiver);
+        assert!(changed.is_empty());
+        assert_eq!(result, Ok((Vec::new(), vec!["./b".to_string()])));
+    }
+
+    #[test]
+    fn imported_files_are_followed_as_they_come_and_go() {
+        let (_dir, root, combiner) = project();
+        let b = root.join("src/b.ts");
+        let step_b = b.clone();
+        let receiver = watch(combiner, move |run| {
+            match run {
+                1 => fs::write(&step_b, "export const b = 1;\n").unwrap(),
+                2 => fs::remove_file(&step_b).unwrap(),
+                _ => return false,
+            }
+            true
+        });
+
+        let (_, result) = next(&receiver);
+        assert_eq!(result, Ok((Vec::new(), vec!["./b".to_string()])));
+
+        let (changed, result) = next(&receiver);
+        assert_eq!(changed, std::slice::from_ref(&b));
+        assert_eq!(result, Ok((vec![PathBuf::from("src/b.ts")], Vec::new())));
+
+        let (changed, result) = next(&receiver);
+        assert_eq!(changed, [b]);
+        assert_eq!(result, Ok((Vec::new(), vec!["./b".to_string()])));
+    }
+
+    #[test]
+    fn changes_to_the_output_are_ignored() {
+        let (_dir, root, _) = project();
+        let output = root.join("combined.txt");
+        let combiner = Combiner::builder()
+            .path(root.join("src/main.ts"))
+            .cache(false)
+            .sink(Sink::File(output.clone()))
+            .build()
+            .unwrap();
+        let main = root.join("src/main.ts");
+        let receiver = watch(combiner, move |run| {
+            if run == 1 {
+                // Written before the source, so only the source can be seen
+                fs::write(&output, "edited").unwrap();
+                thread::sleep(Duration::from_millis(200));
+                fs::write(&main, "export const a = 1;\n").unwrap();
+                return true;
+            }
+            false
+        });
+
+        assert!(next(&receiver).1.is_ok());
+        let (changed, _) = next(&receiver);
+        assert_eq!(changed, [root.join("src/main.ts")]);
+    }
+}
@@ -0,0 +1,483 @@
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Conditions tried when picking an `exports` target, most source-like first
+const EXPORT_CONDITIONS: [&str; 6] = ["source", "import", "module", "default", "require", "node"];
+
+// Where a package's sources usually start, tried before its entry fields
+const SOURCE_ENTRY: &str = "src/index";
+
+/// A package that belongs to the workspace.
+#[derive(Debug, Clone)]
+pub struct WorkspacePackage {
+    pub dir: PathBuf,
+    manifest: Value,
+}
+
+/// An npm, yarn or pnpm workspace and the packages it contains.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+    packages: HashMap<String, WorkspacePackage>,
+}
+
+impl Workspace {
+    /// Finds the workspace containing `start` by walking up to the first
+    /// directory whose `package.json` has `workspaces` or that holds a
+    /// `pnpm-workspace.yaml`.
+    pub fn discover(start: &Path) -> Option<Self> {
+        let start = fs::canonicalize(start).ok()?;
+
+        for dir in start.ancestors() {
+            let patterns = read_pnpm_workspace(dir).or_else(|| read_package_workspaces(dir));
+            if let Some(patterns) = patterns {
+                return Some(Self::from_patterns(dir, &patterns));
+            }
+        }
+
+        None
+    }
+
+    fn from_patterns(root: &Path, patterns: &[String]) -> Self {
+        let mut included = Vec::new();
+        let mut excluded = HashSet::new();
+
+        for pattern in patterns {
+            match pattern.strip_prefix('!') {
+                Some(negated) => excluded.extend(expand_pattern(root, negated)),
+                None => included.extend(expand_pattern(root, pattern)),
+            }
+        }
+
+        let mut packages = HashMap::new();
+        for dir in included {
+            if excluded.contains(&dir) {
+                continue;
+            }
+            let Some(manifest) = read_json(&dir.join("package.json")) else {
+                continue;
+            };
+            if let Some(name) = manifest.get("name").and_then(Value::as_str) {
+                let name = name.to_string();
+                let dir = fs::canonicalize(&dir).unwrap_or(dir);
+                packages.insert(name, WorkspacePackage { dir, manifest });
+            }
+        }
+
+        Self {
+            root: root.to_path_buf(),
+            packages,
+        }
+    }
+
+    /// The manifests that define the workspace and its packages.
+    pub fn manifest_files(&self) -> Vec<PathBuf> {
+        let mut files = vec![
+            self.root.join("package.json"),
+            self.root.join("pnpm-workspace.yaml"),
+        ];
+        files.extend(
+            self.packages
+                .values()
+                .map(|package| package.dir.join("package.json")),
+        );
+        files
+    }
+
+    /// Returns candidate source files for a bare specifier naming a
+    /// workspace package, such as `@acme/ui` or `@acme/ui/button`.
+    ///
+    /// Candidates may lack an extension and are meant to be passed through
+    /// the module resolver in order.
+    pub fn resolve_package_import(&self, specifier: &str) -> Vec<PathBuf> {
+        let Some((name, subpath)) = split_package_specifier(specifier) else {
+            return Vec::new();
+        };
+        let Some(package) = self.packages.get(name) else {
+            return Vec::new();
+        };
+
+        package.entry_candidates(&subpath)
+    }
+}
+
+impl WorkspacePackage {
+    // `subpath` is `.` for the package itself or `./name` for a deep import
+    fn entry_candidates(&self, subpath: &str) -> Vec<PathBuf> {
+        let exported = match self.manifest.get("exports") {
+            Some(exports) => match resolve_exports(exports, subpath) {
+                Some(exported) => exported,
+                None => return Vec::new(),
+            },
+            None => Vec::new(),
+        };
+        let relative = subpath.trim_start_matches("./");
+
+        // Sources come first: the built files named by `exports`, `module`
+        // and `main` may be stale, and are not what the package's code is
+        let mut targets = Vec::new();
+        if subpath == "." {
+            if let Some(source) = self.manifest.get("source").and_then(Value::as_str) {
+                targets.push(source.to_string());
+            }
+            targets.push(SOURCE_ENTRY.to_string());
+        } else {
+            targets.push(format!("src/{}", relative));
+        }
+
+        targets.extend(exported);
+
+        if subpath == "." {
+            for field in ["module", "main"] {
+                if let Some(entry) = self.manifest.get(field).and_then(Value::as_str) {
+                    targets.push(entry.to_string());
+                }
+            }
+            targets.push("index".to_string());
+        } else {
+            targets.push(relative.to_string());
+        }
+
+        let mut seen = HashSet::new();
+        targets
+            .into_iter()
+            .map(|target| crate::tsconfig::normalize_path(&self.dir.join(target)))
+            .filter(|candidate| seen.insert(candidate.clone()))
+            .collect()
+    }
+}
+
+// The targets `exports` maps `subpath` to, or `None` when a `null` target
+// keeps the subpath from being exported
+fn resolve_exports(exports: &Value, subpath: &str) -> Option<Vec<String>> {
+    let is_subpath_map = exports
+        .as_object()
+        .is_some_and(|map| map.keys().any(|key| key.starts_with('.')));
+
+    if !is_subpath_map {
+        return Some(if subpath == "." {
+            resolve_conditions(exports)
+        } else {
+            Vec::new()
+        });
+    }
+
+    let map = exports.as_object().unwrap();
+    if let Some(target) = map.get(subpath).filter(|_| !subpath.contains('*')) {
+        return exported_targets(target, "");
+    }
+
+    // Subpath patterns such as `"./*": "./src/*.ts"`; like Node.js, the
+    // pattern with the longest prefix before `*` wins, then the longest one
+    let best = map
+        .iter()
+        .filter_map(|(key, target)| {
+            let (prefix, suffix) = key.split_once('*')?;
+            if suffix.contains('*') {
+                return None;
+            }
+            let matched = subpath.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            (!matched.is_empty()).then_some(((prefix.len(), key.len()), target, matched))
+        })
+        .max_by_key(|(specificity, _, _)| *specificity);
+    match best {
+        Some((_, target, matched)) => exported_targets(target, matched),
+        None => Some(Vec::new()),
+    }
+}
+
+fn exported_targets(target: &Value, matched: &str) -> Option<Vec<String>> {
+    if target.is_null() {
+        return None;
+    }
+    Some(
+        resolve_conditions(target)
+            .into_iter()
+            .map(|resolved| resolved.replace('*', matched))
+            .collect(),
+    )
+}
+
+fn resolve_conditions(target: &Value) -> Vec<String> {
+    match target {
+        Value::String(path) => vec![path.clone()],
+        Value::Array(targets) => targets.iter().flat_map(resolve_conditions).collect(),
+        Value::Object(conditions) => EXPORT_CONDITIONS
+            .iter()
+            .filter_map(|condition| conditions.get(*condition))
+            .flat_map(resolve_conditions)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Splits `@scope/name/sub/path` into (`@scope/name`, `./sub/path`)
+fn split_package_specifier(specifier: &str) -> Option<(&str, String)> {
+    let name_len = if specifier.starts_with('@') {
+        let scope_end = specifier.find('/')?;
+        specifier[scope_end + 1..]
+            .find('/')
+            .map_or(specifier.len(), |index| scope_end + 1 + index)
+    } else {
+        specifier.find('/').unwrap_or(specifier.len())
+    };
+
+    let (name, rest) = specifier.split_at(name_len);
+    Some((name, format!(".{}", rest)))
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+// `workspaces` is either a list of globs or `{ "packages": [...] }` (yarn)
+fn read_package_workspaces(dir: &Path) -> Option<Vec<String>> {
+    let manifest = read_json(&dir.join("package.json"))?;
+    let workspaces = manifest.get("workspaces")?;
+    let patterns = workspaces
+        .as_array()
+        .or_else(|| workspaces.get("packages").and_then(Value::as_array))?;
+
+    Some(
+        patterns
+            .iter()
+            .filter_map(|pattern| pattern.as_str().map(str::to_string))
+            .collect(),
+    )
+}
+
+// Reads the `packages:` list of pnpm-workspace.yaml, the only key pnpm
+// uses for discovery
+fn read_pnpm_workspace(dir: &Path) -> Option<Vec<String>> {
+    let content = fs::read_to_string(dir.join("pnpm-workspace.yaml")).ok()?;
+    let mut patterns = Vec::new();
+    let mut in_packages = false;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) && !trimmed.starts_with('-') {
+            in_packages = trimmed.starts_with("packages:");
+            continue;
+        }
+        if in_packages {
+            if let Some(item) = trimmed.strip_prefix('-') {
+                let item = item.split(" #").next().unwrap_or("").trim();
+                patterns.push(item.trim_matches(|c| c == '"' || c == '\'').to_string());
+            }
+        }
+    }
+
+    Some(patterns)
+}
+
+// Expands a workspace glob like `packages/*` or `apps/**` into directories
+fn expand_pattern(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+
+    for segment in pattern.trim_end_matches('/').split('/') {
+        dirs = match segment {
+            "" | "." => dirs,
+            "**" => dirs
+                .iter()
+                .flat_map(|dir| {
+                    let mut all = vec![dir.clone()];
+                    all.extend(descendant_dirs(dir));
+                    all
+                })
+                .collect(),
+            segment if segment.contains('*') || segment.contains('?') => dirs
+                .iter()
+                .flat_map(|dir| child_dirs(dir))
+                .filter(|dir| {
+                    dir.file_name()
+                        .is_some_and(|name| matches_wildcard(segment, &name.to_string_lossy()))
+                })
+                .collect(),
+            segment => dirs
+                .iter()
+                .map(|dir| dir.join(segment))
+                .filter(|dir| dir.is_dir())
+                .collect(),
+        };
+    }
+
+    dirs
+}
+
+fn child_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_dir()
+                && path.file_name().is_some_and(|name| {
+                    let name = name.to_string_lossy();
+                    name != "node_modules" && !name.starts_with('.')
+                })
+        })
+        .collect()
+}
+
+fn descendant_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut result = Vec::new();
+    let mut stack = child_dirs(dir);
+    while let Some(current) = stack.pop() {
+        stack.extend(child_dirs(&current));
+        result.push(current);
+    }
+    result
+}
+
+fn matches_wildcard(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dependency_resolver::DependencyResolver;
+    use crate::typescript_resolver::TypeScriptResolver;
+    use serde_json::json;
+
+    fn package(exports: Value) -> WorkspacePackage {
+        WorkspacePackage {
+            dir: PathBuf::from("/ws/ui"),
+            manifest: json!({ "name": "@acme/ui", "exports": exports }),
+        }
+    }
+
+    #[test]
+    fn the_most_specific_pattern_wins() {
+        let exports = json!({
+            "./*": "./src/*.ts",
+            "./components/*": "./src/components/*/index.ts",
+            "./components/*.css": "./styles/*.css",
+        });
+        assert_eq!(
+            resolve_exports(&exports, "./button"),
+            Some(vec!["./src/button.ts".to_string()])
+        );
+        assert_eq!(
+            resolve_exports(&exports, "./components/button"),
+            Some(vec!["./src/components/button/index.ts".to_string()])
+        );
+        assert_eq!(
+            resolve_exports(&exports, "./components/button.css"),
+            Some(vec!["./styles/button.css".to_string()])
+        );
+    }
+
+    #[test]
+    fn exact_keys_take_precedence_over_patterns() {
+        let exports = json!({
+            "./*": "./src/*.ts",
+            "./theme": { "import": "./dist/theme.js", "source": "./src/theme/index.ts" },
+        });
+        assert_eq!(
+            resolve_exports(&exports, "./theme"),
+            Some(vec![
+                "./src/theme/index.ts".to_string(),
+                "./dist/theme.js".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn null_targets_are_not_exported() {
+        let exports = json!({
+            ".": "./src/index.ts",
+            "./*": "./src/*.ts",
+            "./internal/*": null,
+            "./private": null,
+        });
+        assert_eq!(resolve_exports(&exports, "./internal/secret"), None);
+        assert_eq!(resolve_exports(&exports, "./private"), None);
+        assert!(package(exports.clone())
+            .entry_candidates("./internal/secret")
+            .is_empty());
+        assert_eq!(
+            package(exports).entry_candidates("./button")[..2],
+            [
+                PathBuf::from("/ws/ui/src/button"),
+                PathBuf::from("/ws/ui/src/button.ts")
+            ]
+        );
+    }
+
+    #[test]
+    fn unmatched_subpaths_fall_back_to_sources() {
+        let exports = json!({ ".": "./dist/index.js" });
+        assert_eq!(resolve_exports(&exports, "./button"), Some(Vec::new()));
+        assert_eq!(
+            package(exports).entry_candidates("./button"),
+            [
+                PathBuf::from("/ws/ui/src/button"),
+                PathBuf::from("/ws/ui/button")
+            ]
+        );
+    }
+
+    #[test]
+    fn sources_are_preferred_over_built_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let write = |path: &str, content: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write("package.json", r#"{ "workspaces": ["packages/*"] }"#);
+        write(
+            "packages/ui/package.json",
+            r#"{
+                "name": "@acme/ui",
+                "main": "./dist/index.js",
+                "module": "./dist/index.js",
+                "exports": { ".": { "import": "./dist/index.js" } }
+            }"#,
+        );
+        write("packages/ui/dist/index.js", "export const built = true;");
+        write("packages/ui/src/index.ts", "export const source = true;");
+        write("packages/app/src/main.ts", "import '@acme/ui';");
+
+        let importer = root.join("packages/app/src/main.ts");
+        let dependencies = DependencyResolver::new(&root, false).unwrap();
+        let resolved = TypeScriptResolver::new().resolve_import_with_resolver(
+            "@acme/ui",
+            &importer,
+            &dependencies,
+        );
+        assert_eq!(resolved, Some(root.join("packages/ui/src/index.ts")));
+    }
+}